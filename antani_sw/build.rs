@@ -11,15 +11,26 @@
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod build_scenes;
 
 fn main() {
+    // the rp2350 has no boot2 and more flash and ram, see memory-rp2350.x
+    let rp2350 = env::var_os("CARGO_FEATURE_RP2350").is_some();
+
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let memory: &[u8] = if rp2350 {
+        include_bytes!("memory-rp2350.x")
+    } else {
+        include_bytes!("memory.x")
+    };
     File::create(out.join("memory.x"))
         .unwrap()
-        .write_all(include_bytes!("memory.x"))
+        .write_all(memory)
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
@@ -28,14 +39,84 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=memory-rp2350.x");
+    println!("cargo:rerun-if-changed=scenes.ron");
 
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
-    println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
+    // the boot2 of the rp2040
+    if !rp2350 {
+        println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
+    }
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
 
     ::capnpc::CompilerCommand::new()
         .file("usb_messages.capnp")
         .run()
         .expect("compiling schema");
+
+    build_scenes::compile_scenes(Path::new("scenes.ron"), out);
+
+    // for the binary info read by picotool, see src/binary_info.rs
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=BOARD_VARIANT={}", board_variant());
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// the day of the build as yyyy-mm-dd, from SOURCE_DATE_EPOCH for reproducible builds
+fn build_date() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        });
+
+    // days since 1970-01-01 to a date of the gregorian calendar, by eras of 400 years
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// the board and the extra hardware of this build, like "minibadge+mic+buzzer" or
+/// "pico-rp2350+accel"
+fn board_variant() -> String {
+    let mut variant = String::from(if env::var_os("CARGO_FEATURE_BOARD_PICO").is_some() {
+        "pico"
+    } else {
+        "minibadge"
+    });
+    if env::var_os("CARGO_FEATURE_RP2350").is_some() {
+        variant.push_str("-rp2350");
+    }
+
+    for feature in [
+        "mic",
+        "accel",
+        "i2c-slave",
+        "uart",
+        "spi-slave",
+        "buzzer",
+        "strobe-sync",
+    ] {
+        let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+        if env::var_os(var).is_some() {
+            variant.push('+');
+            variant.push_str(feature);
+        }
+    }
+
+    variant
 }
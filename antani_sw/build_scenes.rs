@@ -0,0 +1,338 @@
+// turns scenes.ron into code, used by build.rs and by the build script of minibadge-sim
+
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::Path;
+
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+// mirror of the firmware types used to describe a scene in scenes.ron,
+// see src/rgbeffects.rs
+//
+// the ones with Debug are written out with it, the only reader of their fields, so those
+// with fields allow dead_code
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternRef {
+    Bits(u16),
+    Named(String), // one of the PATTERNS in src/scenes.rs
+}
+
+#[derive(Deserialize)]
+enum Pattern {
+    Simple(PatternRef),
+    Animation(String, f32),
+    AnimationReverse(String, f32),
+    AnimationRandom(String, u16),
+    AnimationBeat(String),
+    AnimationLoop(String, f32, LoopMode),
+    AnimationTimed(String, LoopMode),
+    AnimationRle(String, f32, LoopMode),
+    AnimationColor(String, f32, LoopMode),
+    Sprite(String, (f32, f32), (f32, f32)),
+    Transformed(PatternRef, Transform, f32),
+    RandomWalk(u8, f32, f32),
+    Scanner(ScanAxis, f32, f32),
+    Ripple((u8, u8), f32, f32, Trigger),
+    BinaryClock(ClockDigits),
+    Spectrum,
+}
+
+#[derive(Deserialize, Debug)]
+enum LoopMode {
+    Forward,
+    PingPong,
+    Hold,
+    Clear,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+enum Transform {
+    Rotate,
+    Shift(i8, i8),
+    Mirror,
+    Flip,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+enum ScanAxis {
+    Row(u8),
+    Column(u8),
+    Diagonal,
+    AntiDiagonal,
+}
+
+#[derive(Deserialize, Debug)]
+enum Trigger {
+    Loop,
+    Beat,
+    Button,
+    Ir,
+}
+
+#[derive(Deserialize, Debug)]
+enum ClockDigits {
+    Hours,
+    Minutes,
+}
+
+#[derive(Deserialize)]
+enum ColorPalette {
+    Rainbow(f32),
+    Solid((u8, u8, u8)),
+    Custom(Vec<(u8, u8, u8)>, f32),
+    Sensor(Sensor, Vec<(u8, u8, u8)>),
+    Indexed(f32),
+    Duotone((u8, u8, u8), (u8, u8, u8), f32),
+}
+
+#[derive(Deserialize, Debug)]
+enum Sensor {
+    Temperature,
+    AudioLevel,
+    Bass,
+    Mid,
+    Treble,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+enum FragmentShader {
+    Breathing(f32),
+    Blinking(f32),
+    LowPass(f32),
+    LowPassWithPeak(f32),
+    Rainbow2D(f32),
+    AudioLevel(f32),
+    BeatPulse(f32),
+    WhitePoint(f32),
+    Blur(f32),
+    Glitch(f32),
+    Strobe(f32),
+    Heartbeat(f32),
+    PaletteCycle(f32),
+    Flash(Trigger, f32),
+}
+
+#[derive(Deserialize)]
+struct Layer {
+    effect: Option<Pattern>,
+    color: Option<ColorPalette>,
+    #[serde(default)]
+    pattern_shaders: Vec<FragmentShader>,
+    #[serde(default)]
+    screen_shaders: Vec<FragmentShader>,
+    time_offset: Option<f64>,
+}
+
+// a scene is a list of layers, or (gain: 0.6, layers: [..]) when it has to be dimmed
+enum Scene {
+    Layers(Vec<Layer>),
+    WithGain { gain: f32, layers: Vec<Layer> },
+}
+
+// by hand, ron can't read the enums of the layers inside an untagged enum
+impl<'de> Deserialize<'de> for Scene {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Gain,
+            Layers,
+        }
+
+        struct SceneVisitor;
+
+        impl<'de> Visitor<'de> for SceneVisitor {
+            type Value = Scene;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of layers or (gain: .., layers: [..])")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Scene, A::Error> {
+                let mut layers = Vec::new();
+                while let Some(layer) = seq.next_element()? {
+                    layers.push(layer);
+                }
+                Ok(Scene::Layers(layers))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Scene, A::Error> {
+                let (mut gain, mut layers) = (None, None);
+                while let Some(field) = map.next_key()? {
+                    match field {
+                        Field::Gain => gain = Some(map.next_value()?),
+                        Field::Layers => layers = Some(map.next_value()?),
+                    }
+                }
+                Ok(Scene::WithGain {
+                    gain: gain.ok_or_else(|| de::Error::missing_field("gain"))?,
+                    layers: layers.ok_or_else(|| de::Error::missing_field("layers"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(SceneVisitor)
+    }
+}
+
+fn pattern_ref_code(pattern: &PatternRef) -> String {
+    match pattern {
+        PatternRef::Bits(bits) => format!("{:#011b}", bits),
+        PatternRef::Named(name) => format!("patterns.{}", name),
+    }
+}
+
+fn pattern_code(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Simple(pattern) => format!("Pattern::Simple({})", pattern_ref_code(pattern)),
+        Pattern::Animation(name, speed) => {
+            format!("Pattern::Animation(patterns.{}, {:?})", name, speed)
+        }
+        Pattern::AnimationReverse(name, speed) => {
+            format!("Pattern::AnimationReverse(patterns.{}, {:?})", name, speed)
+        }
+        Pattern::AnimationRandom(name, decimation) => {
+            format!(
+                "Pattern::AnimationRandom(patterns.{}, {})",
+                name, decimation
+            )
+        }
+        Pattern::AnimationBeat(name) => format!("Pattern::AnimationBeat(patterns.{})", name),
+        Pattern::AnimationLoop(name, speed, mode) => format!(
+            "Pattern::AnimationLoop(patterns.{}, {:?}, crate::rgbeffects::LoopMode::{:?})",
+            name, speed, mode
+        ),
+        Pattern::Spectrum => "Pattern::Spectrum".to_string(),
+        Pattern::AnimationTimed(name, mode) => format!(
+            "Pattern::AnimationTimed(patterns.{}, crate::rgbeffects::LoopMode::{:?})",
+            name, mode
+        ),
+        Pattern::AnimationRle(name, speed, mode) => format!(
+            "Pattern::AnimationRle(patterns.{}, {:?}, crate::rgbeffects::LoopMode::{:?})",
+            name, speed, mode
+        ),
+        Pattern::AnimationColor(name, speed, mode) => format!(
+            "Pattern::AnimationColor(patterns.{}, {:?}, crate::rgbeffects::LoopMode::{:?})",
+            name, speed, mode
+        ),
+        Pattern::Sprite(name, position, speed) => format!(
+            "Pattern::Sprite(patterns.{}, {:?}, {:?})",
+            name, position, speed
+        ),
+        Pattern::Transformed(pattern, transform, speed) => format!(
+            "Pattern::Transformed({}, crate::rgbeffects::Transform::{:?}, {:?})",
+            pattern_ref_code(pattern),
+            transform,
+            speed
+        ),
+        Pattern::RandomWalk(count, speed, decay) => {
+            format!("Pattern::RandomWalk({}, {:?}, {:?})", count, speed, decay)
+        }
+        Pattern::Scanner(axis, speed, trail) => format!(
+            "Pattern::Scanner(crate::rgbeffects::ScanAxis::{:?}, {:?}, {:?})",
+            axis, speed, trail
+        ),
+        Pattern::Ripple(origin, speed, decay, trigger) => format!(
+            "Pattern::Ripple({:?}, {:?}, {:?}, crate::rgbeffects::Trigger::{:?})",
+            origin, speed, decay, trigger
+        ),
+        Pattern::BinaryClock(digits) => format!("Pattern::BinaryClock(ClockDigits::{:?})", digits),
+    }
+}
+
+fn color_code(color: &ColorPalette) -> String {
+    match color {
+        ColorPalette::Rainbow(speed) => format!("ColorPalette::Rainbow({:?})", speed),
+        ColorPalette::Solid(rgb) => format!("ColorPalette::Solid({:?}.into())", rgb),
+        ColorPalette::Custom(colors, speed) => {
+            let colors: Vec<String> = colors.iter().map(|c| format!("{:?}.into()", c)).collect();
+            format!(
+                "ColorPalette::Custom(palette![{}], {:?})",
+                colors.join(", "),
+                speed
+            )
+        }
+        ColorPalette::Indexed(speed) => format!("ColorPalette::Indexed({:?})", speed),
+        ColorPalette::Duotone(a, b, bias) => format!(
+            "ColorPalette::Duotone({:?}.into(), {:?}.into(), {:?})",
+            a, b, bias
+        ),
+        ColorPalette::Sensor(sensor, colors) => {
+            let colors: Vec<String> = colors.iter().map(|c| format!("{:?}.into()", c)).collect();
+            format!(
+                "ColorPalette::Sensor(crate::rgbeffects::Sensor::{:?}, palette![{}])",
+                sensor,
+                colors.join(", ")
+            )
+        }
+    }
+}
+
+fn shaders_code(shaders: &[FragmentShader]) -> String {
+    let shaders: Vec<String> = shaders
+        .iter()
+        .map(|s| match s {
+            FragmentShader::Flash(trigger, decay) => format!(
+                "FragmentShader::Flash(crate::rgbeffects::Trigger::{:?}, {:?})",
+                trigger, decay
+            ),
+            _ => format!("FragmentShader::{:?}", s),
+        })
+        .collect();
+    format!("shaders![{}]", shaders.join(", "))
+}
+
+/// turns scenes.ron into an expression using the macros in src/scenes.rs,
+/// included by scenes::scenes()
+pub fn compile_scenes(source: &Path, out: &Path) {
+    let source = fs::read_to_string(source).expect("reading scenes.ron");
+    let scenes: Vec<Scene> = ron::Options::default()
+        .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+        .from_str(&source)
+        .unwrap_or_else(|e| panic!("parsing scenes.ron: {}", e));
+
+    let mut code = String::from("scene_list![\n");
+    for scene in &scenes {
+        let layers = match scene {
+            Scene::Layers(layers) => {
+                code.push_str("    scene![\n");
+                layers
+            }
+            Scene::WithGain { gain, layers } => {
+                writeln!(code, "    scene![gain: {:?};", gain).unwrap();
+                layers
+            }
+        };
+        for layer in layers {
+            code.push_str("        layer! {\n");
+            if let Some(effect) = &layer.effect {
+                writeln!(code, "            effect: {},", pattern_code(effect)).unwrap();
+            }
+            if let Some(color) = &layer.color {
+                writeln!(code, "            color: {},", color_code(color)).unwrap();
+            }
+            if !layer.pattern_shaders.is_empty() {
+                let shaders = shaders_code(&layer.pattern_shaders);
+                writeln!(code, "            pattern_shaders: {},", shaders).unwrap();
+            }
+            if !layer.screen_shaders.is_empty() {
+                let shaders = shaders_code(&layer.screen_shaders);
+                writeln!(code, "            screen_shaders: {},", shaders).unwrap();
+            }
+            if let Some(time_offset) = layer.time_offset {
+                writeln!(code, "            time_offset: {:?},", time_offset).unwrap();
+            }
+            code.push_str("        },\n");
+        }
+        code.push_str("    ],\n");
+    }
+    code.push_str("]\n");
+
+    fs::write(out.join("scenes.rs"), code).expect("writing scenes.rs");
+}
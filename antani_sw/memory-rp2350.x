@@ -0,0 +1,48 @@
+MEMORY {
+    /* the rp2350 boots without boot2, the boot rom looks for the image definition of
+       src/main.rs in the first 4K, see .start_block */
+    /* the last 256K are reserved for user data (config etc.), see src/config.rs */
+    FLASH : ORIGIN = 0x10000000, LENGTH = 4096K - 256K
+
+    /* all the striped ram banks as one block, the two small ones apart */
+    RAM : ORIGIN = 0x20000000, LENGTH = 512K
+    SRAM8 : ORIGIN = 0x20080000, LENGTH = 4K
+    SRAM9 : ORIGIN = 0x20081000, LENGTH = 4K
+}
+
+SECTIONS {
+    /* the image definition, then the header of the binary info, both after the vector
+       table in the first 4K where the boot rom and picotool look for them */
+    .start_block : ALIGN(4)
+    {
+        __start_block_addr = .;
+        KEEP(*(.start_block));
+        KEEP(*(.boot_info));
+    } > FLASH
+} INSERT AFTER .vector_table;
+
+/* the code starts after the blocks */
+_stext = ADDR(.start_block) + SIZEOF(.start_block);
+
+SECTIONS {
+    /* the entries of the binary info, the header points to them */
+    .bi_entries : ALIGN(4)
+    {
+        __bi_entries_start = .;
+        KEEP(*(.bi_entries));
+        . = ALIGN(4);
+        __bi_entries_end = .;
+    } > FLASH
+} INSERT AFTER .text;
+
+SECTIONS {
+    /* the end of the block loop that starts with the image definition */
+    .end_block : ALIGN(4)
+    {
+        __end_block_addr = .;
+        KEEP(*(.end_block));
+    } > FLASH
+} INSERT AFTER .uninit;
+
+PROVIDE(start_to_end = __end_block_addr - __start_block_addr);
+PROVIDE(end_to_start = __start_block_addr - __end_block_addr);
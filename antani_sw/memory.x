@@ -1,6 +1,7 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* the last 256K are reserved for user data (config etc.), see src/config.rs */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 256K
 
     /* Pick one of the two options for RAM layout     */
 
@@ -14,4 +15,27 @@ MEMORY {
     /* RAM: ORIGIN = 0x20000000, LENGTH = 256K        */
     /* SCRATCH_A: ORIGIN = 0x20040000, LENGTH = 4K    */
     /* SCRATCH_B: ORIGIN = 0x20041000, LENGTH = 4K    */
-}
\ No newline at end of file
+}
+
+SECTIONS {
+    /* the header of the binary info, after the vector table so it's in the first 256
+       bytes after boot2 where picotool looks for it, see src/binary_info.rs */
+    .boot_info : ALIGN(4)
+    {
+        KEEP(*(.boot_info));
+    } > FLASH
+} INSERT AFTER .vector_table;
+
+/* the code starts after the header */
+_stext = ADDR(.boot_info) + SIZEOF(.boot_info);
+
+SECTIONS {
+    /* the entries of the binary info, the header points to them */
+    .bi_entries : ALIGN(4)
+    {
+        __bi_entries_start = .;
+        KEEP(*(.bi_entries));
+        . = ALIGN(4);
+        __bi_entries_end = .;
+    } > FLASH
+} INSERT AFTER .text;
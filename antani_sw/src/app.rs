@@ -0,0 +1,926 @@
+// the main loop of the badge, it handles the commands and renders the frames,
+// the mini apps it can run are in apps.rs
+
+use defmt::println;
+use embassy_time::{Duration, Instant, Ticker};
+use heapless::Vec;
+use log::{info, warn};
+use rand::rngs::SmallRng;
+use rand::{RngCore, SeedableRng};
+
+use crate::rgbeffects::{
+    ColorPalette, FragmentShader, LoopMode, PaletteTable, Pattern, RenderCommand, RenderManager,
+    RenderTarget, Trigger,
+};
+use crate::scenes::Scenes;
+use crate::time::Time;
+use crate::{
+    apps, boot_logo, buzzer, clock, demo, drawing, editor, identity, initials, input, midi, mixer,
+    notifications, party, power, reboot, recorder, records, remote, scenes, settings, stats, strip,
+    thermal, ws2812,
+};
+use crate::{
+    LedMatrix, LedPixel, OutputPower, RawFramebuffer, TaskCommand, WhiteLedCommand, WorkingMode,
+    BRIGHTNESS_LEVELS, CHAIN_SIZE, HOST_FRAME_SIGNAL, LED_MATRIX_SIZE, MEGA_CHANNEL,
+    WHITE_LED_SIGNAL,
+};
+
+// the built-in boot animation is shown for the first half second
+const BOOT_ANIMATION_END: Time = Time::from_micros(500_000);
+// seconds the brightness bar stays on top of the scene after a change
+const POWER_BAR_DURATION: f32 = 1.0;
+
+#[embassy_executor::task]
+pub async fn main_tsk(
+    mut frames: ws2812::FrameSender,
+    scenes: &'static Scenes,
+    config: records::Config,
+    seed: u64,
+    palette_table: PaletteTable,
+    boot_logo: &'static boot_logo::BootLogo,
+) {
+    info!("Program start");
+    println!("Program start");
+
+    let mut midi_framebuffer = RawFramebuffer::new();
+    let mut midi_controls = midi::MidiControls::default();
+
+    let mut renderman = RenderManager {
+        mtrx: LedMatrix::new(),
+        rng: SmallRng::seed_from_u64(seed),
+        persistent_data: Default::default(),
+        palette_table,
+    };
+    renderman.mtrx.set_calibration(config.calibration);
+
+    let patterns = scenes::PATTERNS.get();
+
+    let built_in_boot_animation = RenderCommand {
+        effect: Pattern::AnimationLoop(
+            patterns.boot_animation,
+            (patterns.boot_animation.len() as f32) * 2.0,
+            LoopMode::Clear,
+        ),
+        color: ColorPalette::Rainbow(1.0),
+        pattern_shaders: Vec::from_slice(&[FragmentShader::LowPassWithPeak(50.0)]).unwrap(),
+        ..Default::default()
+    };
+    // the logo uploaded by the owner, if any
+    let (boot_animation, boot_animation_end) = boot_logo
+        .animation()
+        .unwrap_or((built_in_boot_animation, BOOT_ANIMATION_END));
+    // override normal rendering with a special effect, if needed
+    let mut working_mode = WorkingMode::SpecialTimeout(boot_animation.clone(), boot_animation_end);
+
+    // the rng is seeded from hardware entropy, so every boot starts differently
+    let mut scene_id = if config.random_boot_scene {
+        renderman.rng.next_u32() as usize % scenes.len()
+    } else if (config.scene as usize) < scenes.len() {
+        config.scene as usize
+    } else {
+        0
+    };
+    let mut scene_player = scenes::ScenePlayer::new(config.playback);
+    let mut demo = config.demo_mode.then(demo::Demo::new);
+    // only for the settings menu, it's used at boot
+    let mut random_boot_scene = config.random_boot_scene;
+    let mut out_power = OutputPower::scaled(config.brightness, BRIGHTNESS_LEVELS - 1);
+    // what the badge starts from at the next boot
+    let mut saved_state = (out_power.level(), scene_id as u8);
+    // the gains below the one of the user, the brightness keys don't go above them
+    let mut thermal_gain = 1.0;
+    let mut max_brightness = config.max_brightness;
+    // a lower ceiling at night, and whether it's night now
+    let mut quiet_hours = config.quiet_hours;
+    let mut quiet = false;
+    // when the brightness changed, the bar is drawn on top of the scene for a while
+    let mut power_bar: Option<Time> = None;
+    // the keys of the remote held down, the volume changes the brightness while held
+    let mut remote_keys = remote::KeyRepeat::default();
+    // the leds are off and nothing is rendered, until the power key of the remote again
+    let mut blanked = false;
+
+    let mut sleep_timer = power::SleepTimer::default();
+
+    // notifications drawn on top of everything else, one at a time
+    let mut notifications = notifications::Queue::default();
+    // a still busy sign instead of the scenes, and no notifications
+    let mut do_not_disturb = config.do_not_disturb;
+    notifications.set_muted(do_not_disturb);
+    // the letters of the owner in place of the scene every few seconds, if set
+    let mut initials = initials::Initials::new(&config.initials);
+
+    // scene being edited over usb, shown instead of everything else
+    let mut preview: Option<scenes::Scene> = None;
+
+    // a second scene mixed with the current one, with a crossfader
+    let mut mix: Option<mixer::Mix> = None;
+
+    // the frames shown being recorded, to keep them as a scene
+    let mut recorder: Option<recorder::Recorder> = None;
+
+    // log every frame, to inspect the effects on a pc
+    let mut frame_capture = false;
+
+    // a corner led lit by the ir traffic, to align the badges
+    let mut ir_overlay = false;
+    let mut ir_traffic: Option<(input::IrTraffic, Time)> = None;
+
+    let mut party = party::Party::default();
+
+    // painted over ir by the host of an event, on top of the scenes
+    let mut drawing = drawing::Drawing::default();
+
+    let mut reboot_sequence = reboot::IrSequence::default();
+
+    let mega_publisher = match MEGA_CHANNEL.publisher() {
+        Ok(p) => p,
+        Err(e) => {
+            println!("Error getting publisher: {:?}", e);
+            panic!("Error getting publisher");
+        }
+    };
+
+    let mut mega_subscriber = match MEGA_CHANNEL.subscriber() {
+        Ok(p) => p,
+        Err(e) => {
+            println!("Error getting subscriber: {:?}", e);
+            panic!("Error getting subscriber");
+        }
+    };
+
+    info!("Starting loop");
+    mega_publisher
+        .publish(TaskCommand::SendIrNec(0, 66, false))
+        .await;
+    // say hello to the badges around
+    mega_publisher
+        .publish(TaskCommand::SendIrNec(
+            identity::ID_IR_ADDRESS,
+            identity::get().id(),
+            false,
+        ))
+        .await;
+
+    let mut fps = config.fps;
+    let mut ticker = Ticker::every(Duration::from_hz(fps as u64));
+    renderman.mtrx.set_frame_rate(fps);
+    input::set_ir_filter(config.ir_filter);
+    strip::set(config.strip);
+    renderman.mtrx.set_chained_badges(config.chained_badges);
+    ws2812::set_chained_badges(config.chained_badges);
+
+    // the time seen by the effects is counted in frames, so the animations play the
+    // same however long a frame takes to render, time_base keeps the time going
+    // when the frame rate changes
+    let mut frame: u32 = 0;
+    let mut time_base = Time::default();
+
+    let mut stats = stats::FrameStats::default();
+    loop {
+        let frame_start = stats.frame_start(Duration::from_hz(fps as u64));
+        let t = time_base.after_frames(frame, fps);
+        // the time of the scenes and of the events they wait for, see party.rs
+        let scene_t = party.time(t);
+
+        // the time of the day is only known once it's set
+        let now_quiet = quiet_hours
+            .zip(clock::seconds_of_day())
+            .is_some_and(|(hours, seconds)| hours.contains(seconds));
+        if now_quiet != quiet {
+            info!(
+                "Quiet hours {}",
+                if now_quiet { "started" } else { "ended" }
+            );
+            quiet = now_quiet;
+        }
+        let brightness_ceiling = match quiet_hours {
+            Some(hours) if quiet => max_brightness.min(hours.max_brightness),
+            _ => max_brightness,
+        };
+        renderman.mtrx.set_max_brightness(brightness_ceiling);
+
+        let gain = out_power.gain();
+        // the brightest level the throttle and the ceiling let through
+        let ceiling = OutputPower::at_most(thermal_gain * brightness_ceiling as f32 / 100.0);
+        // the scenes that are much brighter than the others are dimmed a bit
+        let trim = match working_mode {
+            WorkingMode::Normal if preview.is_none() => scenes[scene_id].gain,
+            _ => 1.0,
+        };
+        renderman.mtrx.set_gain(gain * trim * sleep_timer.fade());
+
+        if matches!(working_mode, WorkingMode::Normal)
+            && demo.is_none()
+            && scene_player.timer_expired()
+        {
+            scene_id = scene_player.next(scene_id, scenes.len(), &mut renderman.rng);
+            // a scene the badge moved to on its own is not a choice of the user to save
+            saved_state.1 = scene_id as u8;
+        }
+
+        if sleep_timer.expired() {
+            info!("Sleep timer expired");
+            mega_publisher.publish(TaskCommand::PowerOff).await;
+        }
+
+        if let Some(message) = mega_subscriber.try_next_message_pure() {
+            info!("Handling message: {:?}", message);
+
+            // for the patterns and shaders waiting for an input event
+            match message {
+                TaskCommand::ShortButtonPress
+                | TaskCommand::LongButtonPress
+                | TaskCommand::MultiButtonPress(_) => renderman.trigger(Trigger::Button, scene_t),
+                TaskCommand::ReceivedIrNec(..) => renderman.trigger(Trigger::Ir, scene_t),
+                TaskCommand::Beat => renderman.trigger(Trigger::Beat, scene_t),
+                _ => {}
+            }
+
+            match message {
+                TaskCommand::ReceivedIrNec(addr, cmd, repeat) => {
+                    let consumed = match &mut working_mode {
+                        WorkingMode::App(app) => app.ir_received(addr, cmd, repeat),
+                        _ => false,
+                    };
+
+                    match (addr, cmd, repeat) {
+                        // the running app already handled it
+                        _ if consumed => buzzer::play(buzzer::Sound::IrReceived),
+
+                        (input::STROBE_SYNC_IR_ADDRESS, _, false) => {
+                            renderman.persistent_data.strobe_sync = Some(scene_t);
+                        }
+
+                        (identity::ID_IR_ADDRESS, id, false) => {
+                            info!("Met badge {}", id);
+                        }
+
+                        (party::PARTY_TIME_IR_ADDRESS, low, false) => {
+                            party.received_time(low, t);
+                        }
+                        (party::PARTY_TIME_HIGH_IR_ADDRESS, high, false) => {
+                            party.received_time_high(high);
+                        }
+                        (party::PARTY_SCENE_IR_ADDRESS, id, false)
+                            if party.role == party::Role::Follower =>
+                        {
+                            if (id as usize) < scenes.len() {
+                                scene_id = id as usize;
+                            }
+                        }
+
+                        (drawing::DRAW_IR_ADDRESS, cmd, false) => drawing.received(cmd),
+
+                        (mixer::MIX_IR_ADDRESS, cmd, false) => {
+                            if let Some(command) = mixer::received(cmd) {
+                                mega_publisher.publish(command).await;
+                            }
+                        }
+
+                        // a badge nearby plays, but this one isn't in the game
+                        (apps::RPS_IR_ADDRESS, _, false) => {
+                            notifications.push(scenes::Notification::Challenge, t);
+                        }
+
+                        (reboot::REBOOT_IR_ADDRESS, cmd, false) => {
+                            if let Some(target) = reboot_sequence.received(cmd, t) {
+                                mega_publisher.publish(TaskCommand::Reboot(target)).await;
+                            }
+                        }
+
+                        // all those are commands of the chinese ir rgb remote
+                        (0, 70, false) => {
+                            mega_publisher
+                                .publish(TaskCommand::DecreaseBrightness)
+                                .await;
+                        }
+                        (0, 69, false) => {
+                            mega_publisher
+                                .publish(TaskCommand::IncreaseBrightness)
+                                .await;
+                        }
+
+                        (0, 71, false) => {
+                            // off
+                            mega_publisher.publish(TaskCommand::SleepTimer).await;
+                        }
+
+                        (0, 67, false) => {
+                            // on
+                            // this is used to sync clocks between multiple devices
+                            mega_publisher.publish(TaskCommand::ResetTime).await;
+                        }
+
+                        (0, 68, false) => {
+                            // animations
+                            mega_publisher.publish(TaskCommand::NextPattern).await;
+                        }
+                        // END of ir command from the chinese remote
+
+                        // startup ir command sent by another badge
+                        // say hi to the other badge
+                        (0, 66, false) => {
+                            // we do this so the animation starts in the correct time
+                            mega_publisher.publish(TaskCommand::ResetTime).await;
+
+                            mega_publisher
+                                .publish(TaskCommand::SetWorkingMode(WorkingMode::SpecialTimeout(
+                                    boot_animation.clone(),
+                                    boot_animation_end,
+                                )))
+                                .await;
+                        }
+
+                        // set the clock, hours and minutes are sent separately
+                        // because they don't fit in a single command
+                        (0xC1, hour, false) => {
+                            let now = clock::seconds_of_day().unwrap_or(0);
+                            mega_publisher
+                                .publish(TaskCommand::SetTime(hour, (now / 60 % 60) as u8, 0))
+                                .await;
+                        }
+                        (0xC2, minute, false) => {
+                            let now = clock::seconds_of_day().unwrap_or(0);
+                            mega_publisher
+                                .publish(TaskCommand::SetTime((now / 3600) as u8, minute, 0))
+                                .await;
+                        }
+
+                        // samsung tv remote, the volume sets the brightness, the digits
+                        // choose the scene and the other keys are sent to the pc
+                        (remote::SAMSUNG_IR_ADDRESS, cmd, repeat) => {
+                            match remote::samsung_key(cmd) {
+                                Some(key @ (remote::Key::VolumeUp | remote::Key::VolumeDown)) => {
+                                    if remote_keys.packet(key, t) {
+                                        let command = match key {
+                                            remote::Key::VolumeUp => {
+                                                TaskCommand::IncreaseBrightness
+                                            }
+                                            _ => TaskCommand::DecreaseBrightness,
+                                        };
+                                        mega_publisher.publish(command).await;
+                                    }
+                                }
+                                Some(key @ remote::Key::Power) => {
+                                    if remote_keys.press(key, t) {
+                                        blanked = !blanked;
+                                        info!("Blanked: {}", blanked);
+                                    }
+                                }
+                                _ if repeat => {}
+                                Some(remote::Key::Digit(digit)) => {
+                                    let id = digit as usize - 1;
+                                    if id < scenes.len() {
+                                        scene_id = id;
+                                        working_mode = WorkingMode::SpecialTimeout(
+                                            scenes::scene_number(digit),
+                                            t + scenes::SCENE_NUMBER_DURATION,
+                                        );
+                                    } else {
+                                        warn!("No scene {}", digit);
+                                    }
+                                }
+                                Some(key) => {
+                                    if let Some(usage) = key.keyboard_usage() {
+                                        mega_publisher
+                                            .publish(TaskCommand::SendHidKeyboard(usage))
+                                            .await;
+                                    }
+                                }
+                                None => {}
+                            }
+                        }
+
+                        _ => {}
+                    }
+                    // not even the white led during a talk
+                    if !blanked {
+                        WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
+                    }
+                }
+                // the button turns a blanked badge back on too, without the remote
+                TaskCommand::ShortButtonPress if blanked => {
+                    blanked = false;
+                    info!("Blanked: false");
+                }
+                TaskCommand::ShortButtonPress => {
+                    buzzer::play(buzzer::Sound::Click);
+                    if let WorkingMode::App(app) = &mut working_mode {
+                        if let Some(command) = app.press() {
+                            mega_publisher.publish(command).await;
+                        }
+                    } else if let WorkingMode::Settings(menu) = &mut working_mode {
+                        menu.press(t);
+                    } else if let WorkingMode::Calibration(led) = working_mode {
+                        // back to the scenes after the last one
+                        working_mode = if led + 1 < LED_MATRIX_SIZE {
+                            WorkingMode::Calibration(led + 1)
+                        } else {
+                            WorkingMode::Normal
+                        };
+                    } else {
+                        mega_publisher.publish(TaskCommand::NextPattern).await;
+                    }
+                }
+                TaskCommand::MultiButtonPress(2) => {
+                    mega_publisher
+                        .publish(TaskCommand::SetDoNotDisturb(!do_not_disturb))
+                        .await;
+                }
+                TaskCommand::MultiButtonPress(3) => {
+                    let app = match &working_mode {
+                        WorkingMode::App(app) => Some(app),
+                        _ => None,
+                    };
+
+                    working_mode = match apps::App::next(app) {
+                        Some(app) => WorkingMode::App(app),
+                        None => WorkingMode::Normal,
+                    };
+                }
+                TaskCommand::MultiButtonPress(4) => {
+                    mega_publisher
+                        .publish(TaskCommand::SetDemoMode(demo.is_none()))
+                        .await;
+                }
+                TaskCommand::MultiButtonPress(5) => {
+                    working_mode = WorkingMode::Settings(settings::Menu::new(
+                        settings::Settings {
+                            random_boot_scene,
+                            playback: scene_player.playback,
+                            max_brightness,
+                            demo_mode: demo.is_some(),
+                        },
+                        t,
+                    ));
+                }
+                TaskCommand::MultiButtonPress(_) => {}
+                TaskCommand::LongButtonPress => {
+                    if let WorkingMode::Settings(menu) = &mut working_mode {
+                        // after the last setting, the config task saves the changes
+                        if !menu.long_press(t) {
+                            let changes = menu.changes();
+                            working_mode = WorkingMode::Normal;
+                            for command in changes {
+                                mega_publisher.publish(command).await;
+                            }
+                        }
+                    } else {
+                        mega_publisher.publish(TaskCommand::CycleBrightness).await;
+                    }
+                }
+
+                TaskCommand::MidiSetPixel(x, y, channel, value) => {
+                    let px = midi_framebuffer.get_pixel(x as usize, y as usize);
+
+                    let rgb = match channel {
+                        0 => (value, px.g, px.b).into(),
+                        1 => (px.r, value, px.b).into(),
+                        2 => (px.r, px.g, value).into(),
+                        _ => px,
+                    };
+
+                    midi_framebuffer.set_pixel(x as usize, y as usize, rgb);
+
+                    working_mode = WorkingMode::RawFramebuffer(midi_framebuffer);
+                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
+                }
+
+                TaskCommand::MidiControlChange(controller, value) => {
+                    if let Some(command) = midi_controls.control_change(controller, value) {
+                        mega_publisher.publish(command).await;
+                    }
+                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
+                }
+
+                // don't receive what we are transmitting
+                TaskCommand::SendIrNec(_, _, _) => {
+                    input::set_enabled(input::Source::Ir, false);
+                }
+
+                TaskCommand::IrTxDone => {
+                    input::set_enabled(input::Source::Ir, true);
+                }
+
+                TaskCommand::RecordInput(enabled) => {
+                    if enabled {
+                        input::start_recording();
+                    } else {
+                        input::stop_recording();
+                    }
+                }
+                TaskCommand::ReplayInput => input::replay(),
+                TaskCommand::RecordFrames(enabled) => {
+                    if enabled {
+                        info!("Recording frames");
+                        recorder = Some(recorder::Recorder::default());
+                    } else if let Some(finished) = recorder.take() {
+                        // the config task has the flash
+                        recorder::FINISHED.signal(finished.finish());
+                        mega_publisher.publish(TaskCommand::SaveRecording).await;
+                    }
+                }
+
+                TaskCommand::NextPattern => {
+                    if let WorkingMode::Normal = working_mode {
+                        scene_id = scene_player.next(scene_id, scenes.len(), &mut renderman.rng);
+                    } else {
+                        working_mode = WorkingMode::Normal;
+                    }
+                }
+
+                TaskCommand::SetScene(id) => {
+                    if (id as usize) < scenes.len() {
+                        scene_id = id as usize;
+                        working_mode = WorkingMode::Normal;
+                    } else {
+                        warn!("No scene {}", id);
+                    }
+                }
+
+                TaskCommand::IncreaseBrightness
+                | TaskCommand::DecreaseBrightness
+                | TaskCommand::CycleBrightness => {
+                    out_power = match message {
+                        TaskCommand::IncreaseBrightness => out_power.increase_up_to(ceiling),
+                        TaskCommand::DecreaseBrightness => out_power.decrease(),
+                        _ => out_power.cycle(),
+                    };
+
+                    power_bar = Some(t);
+                }
+
+                TaskCommand::SetRandomBootScene(enabled) => {
+                    random_boot_scene = enabled;
+                }
+
+                TaskCommand::SetPlayback(playback) => {
+                    scene_player.playback = playback;
+                }
+
+                TaskCommand::SetDemoMode(enabled) => {
+                    info!("Demo mode: {}", enabled);
+                    demo = enabled.then(demo::Demo::new);
+                    working_mode = WorkingMode::Normal;
+                }
+
+                TaskCommand::SetDoNotDisturb(enabled) => {
+                    info!("Do not disturb: {}", enabled);
+                    do_not_disturb = enabled;
+                    notifications.set_muted(enabled);
+                }
+
+                TaskCommand::SetWorkingMode(wm) => {
+                    working_mode = wm;
+                }
+
+                TaskCommand::ResetTime => {
+                    time_base = Time::default();
+                    frame = 0;
+                }
+
+                TaskCommand::SetFrameRate(new_fps) => {
+                    info!("Frame rate: {}", new_fps);
+                    time_base = t;
+                    frame = 0;
+                    fps = new_fps;
+                    ticker = Ticker::every(Duration::from_hz(fps as u64));
+                    renderman.mtrx.set_frame_rate(fps);
+                }
+
+                TaskCommand::SetMaxBrightness(percent) => {
+                    info!("Maximum brightness: {}%", percent);
+                    max_brightness = percent;
+                }
+
+                TaskCommand::SetMixScene(scene) => {
+                    mix =
+                        ((scene as usize) < scenes.len()).then(|| mixer::Mix::new(scene as usize));
+                    info!("Mix: {:?}", mix.as_ref().map(|mix| mix.scene));
+                }
+                TaskCommand::SetMixAmount(percent) => {
+                    if let Some(mix) = &mut mix {
+                        mix.amount = percent as f32 / 100.0;
+                    }
+                }
+
+                TaskCommand::SetQuietHours(hours) => {
+                    info!("Quiet hours: {:?}", hours);
+                    quiet_hours = hours;
+                }
+
+                TaskCommand::SetBrightness(b) => {
+                    out_power = b;
+                }
+
+                TaskCommand::PowerOff => {
+                    // blank the leds, the button task will put the chip to sleep
+                    working_mode = WorkingMode::Special(RenderCommand {
+                        effect: Pattern::Simple(0),
+                        ..Default::default()
+                    });
+                    power::DORMANT_SIGNAL.signal(());
+                }
+
+                TaskCommand::WakeUp => {
+                    mega_publisher.publish(TaskCommand::ResetTime).await;
+                    mega_publisher
+                        .publish(TaskCommand::SetWorkingMode(WorkingMode::SpecialTimeout(
+                            boot_animation.clone(),
+                            boot_animation_end,
+                        )))
+                        .await;
+                }
+
+                TaskCommand::SleepTimer => {
+                    sleep_timer.press();
+
+                    // show the remaining time, one led every ~7 minutes
+                    let effect = match sleep_timer.remaining_leds() {
+                        Some(leds) => Pattern::Simple((1 << leds) - 1),
+                        None => Pattern::Simple(patterns.cross),
+                    };
+
+                    if !matches!(working_mode, WorkingMode::RawFramebuffer(_)) {
+                        working_mode = WorkingMode::SpecialTimeout(
+                            RenderCommand {
+                                effect,
+                                color: ColorPalette::Solid((128, 0, 255).into()),
+                                ..Default::default()
+                            },
+                            t + 2.0,
+                        );
+                    }
+                }
+
+                TaskCommand::SetFrameCapture(enabled) => {
+                    frame_capture = enabled;
+                }
+                TaskCommand::SetPartyRole(role) => party.set_role(role),
+                TaskCommand::SetIrFilter(filter) => {
+                    info!("IR filter: {:?}", filter.remotes);
+                    input::set_ir_filter(filter);
+                }
+                TaskCommand::SetStrip(strip) => {
+                    info!("Strip: {:?}", strip);
+                    strip::set(strip);
+                }
+                TaskCommand::SetChainedBadges(badges) => {
+                    info!("Chained badges: {}", badges);
+                    renderman.mtrx.set_chained_badges(badges);
+                    ws2812::set_chained_badges(badges);
+                }
+                TaskCommand::SetInitials(letters) => {
+                    info!("Initials: {}", letters);
+                    initials = initials::Initials::new(&letters);
+                }
+
+                TaskCommand::SetIrOverlay(enabled) => {
+                    ir_overlay = enabled;
+                    ir_traffic = None;
+                    input::IR_TRAFFIC.reset();
+                }
+                TaskCommand::LogStats => stats.log(),
+                TaskCommand::Calibrate(edit) => {
+                    // shown right away, the config task saves it
+                    let mut calibration = renderman.mtrx.calibration;
+                    calibration.apply(&edit);
+                    renderman.mtrx.set_calibration(calibration);
+                }
+                TaskCommand::StreamFrame(fb) => {
+                    working_mode = WorkingMode::RawFramebuffer(fb);
+                }
+                TaskCommand::SetPaletteTable(table) => {
+                    renderman.palette_table = table;
+                }
+                TaskCommand::Beat => {
+                    renderman.persistent_data.strobe_sync = Some(scene_t);
+                }
+                TaskCommand::StrobeSync => {
+                    renderman.persistent_data.strobe_sync = Some(scene_t);
+                }
+                TaskCommand::Shake => {
+                    // shake to shuffle, only while showing the normal scenes
+                    if matches!(working_mode, WorkingMode::Normal) && demo.is_none() {
+                        scene_id = scene_player.random(scene_id, scenes.len(), &mut renderman.rng);
+                    }
+                }
+                TaskCommand::Orientation(orientation) => {
+                    // keep the last rotation while the badge lies flat
+                    if let Some(quarter_turns) = orientation.quarter_turns() {
+                        renderman.mtrx.set_rotation(quarter_turns);
+                    }
+                }
+                TaskCommand::Swing(rightwards) => {
+                    if let WorkingMode::App(app) = &mut working_mode {
+                        app.swing(rightwards);
+                    }
+                }
+                TaskCommand::Tap => {
+                    // a tap on the badge works like the button, but not while waving it around
+                    if !matches!(working_mode, WorkingMode::App(apps::App::Pov(_))) {
+                        mega_publisher.publish(TaskCommand::ShortButtonPress).await;
+                    }
+                }
+                TaskCommand::Notify(n) => notifications.push(n, t),
+
+                TaskCommand::UsbActivity => {
+                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
+                }
+
+                TaskCommand::Reboot(_) => {
+                    // the config task restarts the chip
+                    blanked = true;
+                }
+
+                TaskCommand::Error => {
+                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Error);
+                    buzzer::play(buzzer::Sound::Error);
+                }
+
+                TaskCommand::None
+                | TaskCommand::SendHidKeyboard(_)
+                | TaskCommand::EditScene(_)
+                | TaskCommand::SaveRecording
+                | TaskCommand::NewBootLogo
+                | TaskCommand::AddBootLogoFrame(_)
+                | TaskCommand::SaveBootLogo(_)
+                | TaskCommand::SaveState(_, _)
+                | TaskCommand::SetName(_)
+                | TaskCommand::FactoryReset
+                | TaskCommand::SetTime(_, _, _) => {}
+            }
+        }
+
+        // the scenes of the demo mode are not the choice of the user
+        let state = (out_power.level(), scene_id as u8);
+        if state != saved_state && demo.is_none() {
+            saved_state = state;
+            mega_publisher
+                .publish(TaskCommand::SaveState(state.0, state.1))
+                .await;
+        }
+
+        // the followers get the time of the leader, and its scene
+        if let Some(packets) = party.beacon(t, scene_id) {
+            for packet in packets {
+                mega_publisher.publish(packet).await;
+            }
+        }
+
+        if let Some(gain) = thermal::THERMAL_THROTTLE.try_take() {
+            if gain < 1.0 {
+                warn!("Thermal throttling! {}", gain);
+            }
+            if gain < 1.0 && thermal_gain == 1.0 {
+                notifications.push(scenes::Notification::Overheating, t);
+            }
+            renderman.mtrx.set_raw_gain(gain);
+            thermal_gain = gain;
+        }
+
+        if blanked {
+            // the messages are still handled every frame, only the rendering stops
+            *frames.send().await = [LedPixel::default(); CHAIN_SIZE];
+            frames.send_done();
+            ticker.next().await;
+            frame += 1;
+            continue;
+        }
+
+        if let Some(fb) = HOST_FRAME_SIGNAL.try_take() {
+            working_mode = WorkingMode::RawFramebuffer(fb);
+        }
+
+        if let Some(scene) = editor::PREVIEW.try_take() {
+            preview = scene;
+        }
+
+        if do_not_disturb {
+            renderman.render(&[scenes::do_not_disturb()], t);
+        } else if let Some(scene) = &preview {
+            renderman.render(scene, t);
+        } else {
+            match &mut working_mode {
+                WorkingMode::Normal => {
+                    if let Some(demo) = &mut demo {
+                        demo.render(
+                            &mut renderman,
+                            scenes,
+                            &mut scene_id,
+                            &mut scene_player,
+                            &out_power,
+                            scene_t,
+                        );
+                    } else if !initials.render(&mut renderman, t) {
+                        midi_controls.render(&mut renderman, &scenes[scene_id], scene_t);
+                        if let Some(mix) = &mut mix {
+                            mix.render(&mut renderman, scenes, scene_t);
+                        }
+                    }
+                    drawing.draw(&mut renderman.mtrx.raw_framebuffer);
+                }
+                WorkingMode::SpecialTimeout(scene, timeout) => {
+                    renderman.render(&[scene.clone()], t);
+
+                    if t > *timeout {
+                        working_mode = WorkingMode::Normal;
+                    }
+                }
+                WorkingMode::Special(scene) => {
+                    renderman.render(&[scene.clone()], t);
+                }
+                WorkingMode::RawFramebuffer(fb) => {
+                    renderman.mtrx.raw_framebuffer = *fb;
+                }
+                WorkingMode::App(app) => {
+                    app.render(&mut renderman, t);
+                }
+                WorkingMode::Settings(menu) => {
+                    menu.render(&mut renderman, t);
+                }
+                WorkingMode::Calibration(led) => {
+                    renderman.mtrx.raw_framebuffer.framebuffer[*led] = (255, 255, 255).into();
+                }
+            }
+        }
+
+        // the effects without the notifications and the overlays
+        if let Some(recorder) = &mut recorder {
+            if recorder.record(&renderman.mtrx.raw_framebuffer, t) {
+                mega_publisher
+                    .publish(TaskCommand::RecordFrames(false))
+                    .await;
+            }
+        }
+
+        notifications.render(&mut renderman, t);
+
+        if let Some(start) = power_bar {
+            // the levels the throttle and the ceiling don't let through are red
+            let bar = out_power.pattern();
+            let allowed = bar & ceiling.pattern();
+
+            renderman.render(
+                &[
+                    RenderCommand {
+                        effect: Pattern::Simple(allowed),
+                        color: ColorPalette::Solid((255, 255, 255).into()),
+                        ..Default::default()
+                    },
+                    RenderCommand {
+                        effect: Pattern::Simple(bar & !allowed),
+                        color: ColorPalette::Solid((255, 0, 0).into()),
+                        ..Default::default()
+                    },
+                ],
+                t,
+            );
+
+            if t.since(start) > POWER_BAR_DURATION {
+                power_bar = None;
+            }
+        }
+
+        if ir_overlay {
+            if let Some(traffic) = input::IR_TRAFFIC.try_take() {
+                // the edges don't cut short a packet
+                let showing_packet = ir_traffic.is_some_and(|(last, at)| {
+                    last != input::IrTraffic::Edge && t.since(at) < last.overlay().1
+                });
+
+                if traffic != input::IrTraffic::Edge || !showing_packet {
+                    ir_traffic = Some((traffic, t));
+                }
+            }
+
+            if let Some((traffic, at)) = ir_traffic {
+                let (color, duration) = traffic.overlay();
+
+                if t.since(at) < duration {
+                    let (width, height) = renderman.mtrx.size();
+                    renderman.mtrx.set_pixel(width - 1, height - 1, color);
+                }
+            }
+        }
+
+        if frame_capture {
+            // frames are dropped if the host doesn't read the debug port fast enough
+            info!(
+                "frame {} {}",
+                Instant::now().as_millis(),
+                renderman.mtrx.raw_framebuffer
+            );
+        }
+
+        let send_start = Instant::now();
+        // waits only if the leds are still busy with the frame before the last one
+        *frames.send().await = *renderman.mtrx.get_gamma_corrected();
+        frames.send_done();
+        stats.frame_end(frame_start, send_start);
+        ticker.next().await;
+        frame += 1;
+        renderman.mtrx.clear();
+    }
+}
@@ -0,0 +1,604 @@
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use rand::Rng;
+
+use crate::buzzer::Sound;
+use crate::rgbeffects::{
+    ColorPalette, FragmentShader, LedPattern, Pattern, RenderCommand, RenderManager,
+};
+use crate::time::Time;
+use crate::{LedMatrix, LedPixel, TaskCommand};
+
+// small interactive applications, they take over the button while they are running
+// and are cycled with a triple press
+#[derive(Clone, Debug)]
+pub enum App {
+    Stopwatch(Stopwatch),
+    Dice(Dice),
+    Rps(Rps),
+    Tag(Tag),
+    Pov(Pov),
+}
+
+impl App {
+    /// the app after this one, None to go back to the normal scenes
+    pub fn next(app: Option<&App>) -> Option<App> {
+        match app {
+            None => Some(App::Stopwatch(Stopwatch::default())),
+            Some(App::Stopwatch(_)) => Some(App::Dice(Dice::default())),
+            Some(App::Dice(_)) => Some(App::Rps(Rps::default())),
+            Some(App::Rps(_)) => Some(App::Tag(Tag::new())),
+            Some(App::Tag(_)) => Some(App::Pov(Pov::default())),
+            Some(App::Pov(_)) => None,
+        }
+    }
+
+    /// returns a command to publish, used by the games to talk over IR
+    pub fn press(&mut self) -> Option<TaskCommand> {
+        match self {
+            App::Stopwatch(stopwatch) => stopwatch.press(),
+            App::Dice(dice) => dice.press(),
+            App::Pov(pov) => pov.press(),
+            App::Rps(rps) => return rps.press(),
+            App::Tag(tag) => return tag.press(),
+        }
+        None
+    }
+
+    /// returns true if the IR command was for this app
+    pub fn ir_received(&mut self, addr: u8, cmd: u8, repeat: bool) -> bool {
+        match self {
+            App::Rps(rps) if addr == RPS_IR_ADDRESS && !repeat => rps.received(cmd),
+            App::Tag(tag) if addr == TAG_IR_ADDRESS && !repeat => tag.hit(cmd),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        match self {
+            App::Stopwatch(stopwatch) => stopwatch.render(renderman, t),
+            App::Dice(dice) => dice.render(renderman, t),
+            App::Rps(rps) => rps.render(renderman, t),
+            App::Tag(tag) => tag.render(renderman, t),
+            App::Pov(pov) => pov.render(renderman, t),
+        }
+    }
+
+    /// the badge changed direction while being waved
+    pub fn swing(&mut self, rightwards: bool) {
+        if let App::Pov(pov) = self {
+            pov.swing = Some((Instant::now(), rightwards));
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum Stopwatch {
+    #[default]
+    Idle,
+    Running(Instant),
+    Stopped(u64), // elapsed seconds
+}
+
+impl Stopwatch {
+    // every minute has its own color
+    const MINUTE_COLORS: [(u8, u8, u8); 8] = [
+        (0, 255, 0),
+        (255, 255, 0),
+        (255, 100, 0),
+        (255, 0, 0),
+        (255, 0, 255),
+        (0, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+
+    fn press(&mut self) {
+        *self = match self {
+            Stopwatch::Idle => Stopwatch::Running(Instant::now()),
+            Stopwatch::Running(start) => Stopwatch::Stopped(start.elapsed().as_secs()),
+            Stopwatch::Stopped(_) => Stopwatch::Idle,
+        };
+    }
+
+    fn render(&self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        let elapsed = match self {
+            Stopwatch::Idle => {
+                // waiting for the start
+                renderman.render(
+                    &[RenderCommand {
+                        effect: Pattern::Simple(0b000010000),
+                        pattern_shaders: Vec::from_slice(&[FragmentShader::Breathing(0.5)])
+                            .unwrap(),
+                        ..Default::default()
+                    }],
+                    t,
+                );
+                return;
+            }
+            Stopwatch::Running(start) => start.elapsed().as_secs(),
+            Stopwatch::Stopped(elapsed) => *elapsed,
+        };
+
+        // one led every 10 seconds, the last one blinks while running
+        let leds = (elapsed % 60 / 10) as u32 + 1;
+        let color: LedPixel =
+            Self::MINUTE_COLORS[(elapsed / 60) as usize % Self::MINUTE_COLORS.len()].into();
+
+        let done: LedPattern = (1 << (leds - 1)) - 1;
+        let current: LedPattern = 1 << (leds - 1);
+
+        let current_shaders = if let Stopwatch::Running(_) = self {
+            Vec::from_slice(&[FragmentShader::Blinking(1.0)]).unwrap()
+        } else {
+            Vec::new()
+        };
+
+        renderman.render(
+            &[
+                RenderCommand {
+                    effect: Pattern::Simple(done),
+                    color: ColorPalette::Solid(color),
+                    ..Default::default()
+                },
+                RenderCommand {
+                    effect: Pattern::Simple(current),
+                    color: ColorPalette::Solid(color),
+                    pattern_shaders: current_shaders,
+                    ..Default::default()
+                },
+            ],
+            t,
+        );
+    }
+}
+
+/// 6 for a normal die, up to 9
+const DICE_SIDES: usize = 6;
+const DICE_ROLL_TIME: Duration = Duration::from_millis(1200);
+const DICE_FACE_TIME: Duration = Duration::from_millis(80);
+const DICE_FLASH_TIME: Duration = Duration::from_millis(150);
+
+#[derive(Clone, Debug, Default)]
+pub enum Dice {
+    #[default]
+    Idle,
+    Rolling(Instant, usize, Instant), // start, shown face, last face change
+    Rolled(usize, Instant),           // face, end of the roll
+}
+
+impl Dice {
+    fn press(&mut self) {
+        if !matches!(self, Dice::Rolling(..)) {
+            let now = Instant::now();
+            *self = Dice::Rolling(now, 0, now);
+        }
+    }
+
+    fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        let faces = crate::scenes::PATTERNS.get().dice;
+
+        let command = match *self {
+            Dice::Idle => RenderCommand {
+                effect: Pattern::Simple(faces[DICE_SIDES - 1]),
+                pattern_shaders: Vec::from_slice(&[FragmentShader::Breathing(0.5)]).unwrap(),
+                ..Default::default()
+            },
+            Dice::Rolling(start, face, changed) => {
+                if start.elapsed() > DICE_ROLL_TIME {
+                    *self = Dice::Rolled(renderman.rng.gen_range(0..DICE_SIDES), Instant::now());
+                    return self.render(renderman, t);
+                }
+
+                let face = if changed.elapsed() > DICE_FACE_TIME {
+                    let face = renderman.rng.gen_range(0..DICE_SIDES);
+                    *self = Dice::Rolling(start, face, Instant::now());
+                    face
+                } else {
+                    face
+                };
+
+                RenderCommand {
+                    effect: Pattern::Simple(faces[face]),
+                    color: ColorPalette::Rainbow(2.0),
+                    ..Default::default()
+                }
+            }
+            Dice::Rolled(face, end) => {
+                // flash everything when the roll locks in
+                if end.elapsed() < DICE_FLASH_TIME {
+                    RenderCommand {
+                        effect: Pattern::Simple(faces[faces.len() - 1]),
+                        ..Default::default()
+                    }
+                } else {
+                    RenderCommand {
+                        effect: Pattern::Simple(faces[face]),
+                        color: ColorPalette::Solid((255, 0, 0).into()),
+                        ..Default::default()
+                    }
+                }
+            }
+        };
+
+        renderman.render(&[command], t);
+    }
+}
+
+// rock paper scissors, the command is the choice
+pub const RPS_IR_ADDRESS: u8 = 0x52;
+// how long in ms each choice is shown on the matrix, the one shown is thrown by the press
+const RPS_CHOICE_TIME: u64 = 700;
+// how long we wait for the other badge to throw
+const RPS_TIMEOUT: Duration = Duration::from_secs(10);
+const RPS_RESULT_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RpsChoice {
+    Rock,
+    Paper,
+    Scissors,
+}
+
+impl RpsChoice {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(RpsChoice::Rock),
+            1 => Some(RpsChoice::Paper),
+            2 => Some(RpsChoice::Scissors),
+            _ => None,
+        }
+    }
+
+    fn beats(&self, other: &RpsChoice) -> bool {
+        matches!(
+            (self, other),
+            (RpsChoice::Rock, RpsChoice::Scissors)
+                | (RpsChoice::Paper, RpsChoice::Rock)
+                | (RpsChoice::Scissors, RpsChoice::Paper)
+        )
+    }
+
+    fn render_command(&self) -> RenderCommand {
+        let patterns = crate::scenes::PATTERNS.get();
+
+        let (pattern, color) = match self {
+            RpsChoice::Rock => (patterns.plus, (255, 100, 0)),
+            RpsChoice::Paper => (patterns.all_on, (255, 255, 255)),
+            RpsChoice::Scissors => (patterns.cross, (0, 200, 255)),
+        };
+
+        RenderCommand {
+            effect: Pattern::Simple(pattern),
+            color: ColorPalette::Solid(color.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum RpsResult {
+    Win,
+    Lose,
+    Draw,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum RpsState {
+    #[default]
+    Choosing,
+    Thrown(RpsChoice, Instant),
+    Result(RpsResult, Instant),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Rps {
+    state: RpsState,
+    // the other badge may throw before us
+    theirs: Option<(RpsChoice, Instant)>,
+}
+
+impl Rps {
+    fn current_choice() -> RpsChoice {
+        let idx = Instant::now().as_millis() / RPS_CHOICE_TIME % 3;
+        RpsChoice::from_u8(idx as u8).unwrap_or(RpsChoice::Rock)
+    }
+
+    fn press(&mut self) -> Option<TaskCommand> {
+        if !matches!(self.state, RpsState::Choosing) {
+            return None;
+        }
+
+        let ours = Self::current_choice();
+        self.state = RpsState::Thrown(ours, Instant::now());
+        self.update();
+
+        Some(TaskCommand::SendIrNec(RPS_IR_ADDRESS, ours as u8, false))
+    }
+
+    fn received(&mut self, cmd: u8) {
+        if let Some(theirs) = RpsChoice::from_u8(cmd) {
+            log::info!("Other badge threw {:?}", theirs);
+            self.theirs = Some((theirs, Instant::now()));
+            self.update();
+        }
+    }
+
+    fn update(&mut self) {
+        // forget old throws, they were for another round
+        if self
+            .theirs
+            .is_some_and(|(_, at)| at.elapsed() > RPS_TIMEOUT)
+        {
+            self.theirs = None;
+        }
+
+        match (self.state, self.theirs) {
+            (RpsState::Thrown(ours, _), Some((theirs, _))) => {
+                let result = if ours == theirs {
+                    RpsResult::Draw
+                } else if ours.beats(&theirs) {
+                    RpsResult::Win
+                } else {
+                    RpsResult::Lose
+                };
+
+                crate::buzzer::play(match result {
+                    RpsResult::Win => Sound::Win,
+                    RpsResult::Lose => Sound::Lose,
+                    RpsResult::Draw => Sound::Click,
+                });
+
+                self.state = RpsState::Result(result, Instant::now());
+                self.theirs = None;
+            }
+            (RpsState::Thrown(_, at), None) if at.elapsed() > RPS_TIMEOUT => {
+                log::info!("Nobody threw against us");
+                self.state = RpsState::Choosing;
+            }
+            (RpsState::Result(_, at), _) if at.elapsed() > RPS_RESULT_TIME => {
+                self.state = RpsState::Choosing;
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        self.update();
+
+        let patterns = crate::scenes::PATTERNS.get();
+
+        let command = match &self.state {
+            RpsState::Choosing => Self::current_choice().render_command(),
+            RpsState::Thrown(ours, _) => {
+                // waiting for the other badge
+                let mut command = ours.render_command();
+                command
+                    .pattern_shaders
+                    .push(FragmentShader::Breathing(1.0))
+                    .ok();
+                command
+            }
+            RpsState::Result(RpsResult::Win, _) => RenderCommand {
+                effect: Pattern::Simple(patterns.all_on),
+                color: ColorPalette::Rainbow(1.0),
+                pattern_shaders: Vec::from_slice(&[FragmentShader::Rainbow2D(1.0)]).unwrap(),
+                ..Default::default()
+            },
+            RpsState::Result(RpsResult::Lose, _) => RenderCommand {
+                effect: Pattern::Simple(patterns.cross),
+                color: ColorPalette::Solid((255, 0, 0).into()),
+                pattern_shaders: Vec::from_slice(&[FragmentShader::Blinking(2.0)]).unwrap(),
+                ..Default::default()
+            },
+            RpsState::Result(RpsResult::Draw, _) => RenderCommand {
+                effect: Pattern::Simple(patterns.vertical_stripe_2),
+                color: ColorPalette::Solid((255, 255, 0).into()),
+                pattern_shaders: Vec::from_slice(&[FragmentShader::Breathing(1.0)]).unwrap(),
+                ..Default::default()
+            },
+        };
+
+        renderman.render(&[command], t);
+    }
+}
+
+// laser tag, the command is the id of the shooter
+const TAG_IR_ADDRESS: u8 = 0x54;
+const TAG_MAX_HEALTH: u8 = 9;
+const TAG_FIRE_COOLDOWN: Duration = Duration::from_millis(500);
+const TAG_REGEN_TIME: Duration = Duration::from_secs(10);
+const TAG_RESPAWN_TIME: Duration = Duration::from_secs(5);
+const TAG_FLASH_TIME: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Debug)]
+pub struct Tag {
+    id: u8,
+    health: u8,
+    last_fire: Instant,
+    last_hit: Instant,
+    last_regen: Instant,
+}
+
+impl Tag {
+    fn new() -> Self {
+        let now = Instant::now();
+
+        Self {
+            id: crate::identity::get().id(),
+            health: TAG_MAX_HEALTH,
+            last_fire: Instant::from_ticks(0),
+            last_hit: Instant::from_ticks(0),
+            last_regen: now,
+        }
+    }
+
+    fn press(&mut self) -> Option<TaskCommand> {
+        if self.health == 0 || self.last_fire.elapsed() < TAG_FIRE_COOLDOWN {
+            return None;
+        }
+
+        self.last_fire = Instant::now();
+        Some(TaskCommand::SendIrNec(TAG_IR_ADDRESS, self.id, false))
+    }
+
+    fn hit(&mut self, shooter: u8) {
+        if shooter == self.id || self.health == 0 {
+            return;
+        }
+
+        log::info!("Hit by {}", shooter);
+        self.health -= 1;
+        self.last_hit = Instant::now();
+
+        crate::buzzer::play(if self.health == 0 {
+            Sound::Lose
+        } else {
+            Sound::Hit
+        });
+        self.last_regen = self.last_hit;
+    }
+
+    fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        // dead, wait for the respawn
+        if self.health == 0 {
+            if self.last_hit.elapsed() > TAG_RESPAWN_TIME {
+                self.health = TAG_MAX_HEALTH;
+                self.last_regen = Instant::now();
+            } else {
+                renderman.render(
+                    &[RenderCommand {
+                        effect: Pattern::Simple(crate::scenes::PATTERNS.get().cross),
+                        color: ColorPalette::Solid((255, 0, 0).into()),
+                        pattern_shaders: Vec::from_slice(&[FragmentShader::Blinking(2.0)]).unwrap(),
+                        ..Default::default()
+                    }],
+                    t,
+                );
+                return;
+            }
+        }
+
+        if self.health < TAG_MAX_HEALTH && self.last_regen.elapsed() > TAG_REGEN_TIME {
+            self.health += 1;
+            self.last_regen = Instant::now();
+        }
+
+        let command = if self.last_hit.elapsed() < TAG_FLASH_TIME {
+            RenderCommand {
+                effect: Pattern::Simple(0b111111111),
+                color: ColorPalette::Solid((255, 0, 0).into()),
+                ..Default::default()
+            }
+        } else if self.last_fire.elapsed() < TAG_FLASH_TIME {
+            RenderCommand {
+                effect: Pattern::Simple(0b000010000),
+                color: ColorPalette::Solid(crate::identity::get().color()),
+                ..Default::default()
+            }
+        } else {
+            // one led for every health point
+            let color = if self.health > 3 {
+                (0, 255, 0)
+            } else {
+                (255, 100, 0)
+            };
+
+            RenderCommand {
+                effect: Pattern::Simple((1 << self.health) - 1),
+                color: ColorPalette::Solid(color.into()),
+                ..Default::default()
+            }
+        };
+
+        renderman.render(&[command], t);
+    }
+}
+
+// persistence of vision, one column of the image at a time on the middle column
+// of the matrix, waving the badge paints the whole image in the air
+// every column is 3 bits, the highest is the top led
+const POV_IMAGE: &[u8] = &[
+    0b111, 0b010, 0b111, // H
+    0b000, 0b111, // I
+    0b000, 0b000, 0b110, 0b011, 0b110, // heart
+];
+// columns per second, changed by the button, the frames are at 100Hz
+const POV_RATES: [f32; 3] = [25.0, 50.0, 100.0];
+// without the accelerometer the image is repeated after this many dark columns
+const POV_GAP: usize = 5;
+// stop painting if the badge is not waved for this long
+const POV_SWING_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct Pov {
+    rate: usize,
+    // with an accelerometer the image starts at every swing
+    gated: bool,
+    swing: Option<(Instant, bool)>, // start, towards the right
+}
+
+impl Default for Pov {
+    fn default() -> Self {
+        Self {
+            rate: 1,
+            gated: cfg!(feature = "accel"),
+            swing: None,
+        }
+    }
+}
+
+impl Pov {
+    fn press(&mut self) {
+        self.rate = (self.rate + 1) % POV_RATES.len();
+        log::info!("POV rate: {} columns per second", POV_RATES[self.rate]);
+    }
+
+    fn column(&self) -> Option<u8> {
+        let rate = POV_RATES[self.rate];
+
+        if !self.gated {
+            let i = (Instant::now().as_micros() as f32 / 1_000_000.0 * rate) as usize;
+            return POV_IMAGE.get(i % (POV_IMAGE.len() + POV_GAP)).copied();
+        }
+
+        let (start, rightwards) = self.swing?;
+        if start.elapsed() > POV_SWING_TIMEOUT {
+            return None;
+        }
+
+        let i = (start.elapsed().as_micros() as f32 / 1_000_000.0 * rate) as usize;
+        if i >= POV_IMAGE.len() {
+            return None;
+        }
+
+        // going left the first column painted is the last of the image
+        Some(if rightwards {
+            POV_IMAGE[i]
+        } else {
+            POV_IMAGE[POV_IMAGE.len() - 1 - i]
+        })
+    }
+
+    fn render(&self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        let Some(column) = self.column() else {
+            return;
+        };
+
+        // the row from the bottom r of the middle column is bit r * 3 + 1
+        let pattern: LedPattern = (0..3)
+            .filter(|r| column & (1 << r) != 0)
+            .map(|r| 1 << (r * 3 + 1))
+            .sum();
+
+        renderman.render(
+            &[RenderCommand {
+                effect: Pattern::Simple(pattern),
+                color: ColorPalette::Rainbow(0.5),
+                ..Default::default()
+            }],
+            t,
+        );
+    }
+}
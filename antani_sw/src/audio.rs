@@ -0,0 +1,269 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "rp2350")]
+use num_traits::real::Real;
+
+#[cfg(feature = "mic")]
+use embassy_time::Duration;
+
+// sound level from 0.0 to 1.0, stored as f32 bits,
+// written by the adc task and read by the render task on the other core
+static LEVEL: AtomicU32 = AtomicU32::new(0);
+
+// low, mid and high frequencies, same as the level
+static BANDS: [AtomicU32; 3] = [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+
+/// current sound level, from 0.0 to 1.0, always 0.0 without a microphone
+pub fn level() -> f32 {
+    f32::from_bits(LEVEL.load(Ordering::Relaxed))
+}
+
+/// low, mid and high frequencies, from 0.0 to 1.0
+pub fn bands() -> [f32; 3] {
+    BANDS
+        .each_ref()
+        .map(|band| f32::from_bits(band.load(Ordering::Relaxed)))
+}
+
+#[cfg(feature = "mic")]
+pub const SAMPLE_RATE: u32 = 2000;
+#[cfg(feature = "mic")]
+pub const SAMPLE_PERIOD: Duration = Duration::from_micros(1_000_000 / SAMPLE_RATE as u64);
+
+// amplitude, in adc counts, that gives a full level
+const FULL_SCALE: f32 = 512.0;
+
+// smoothing factors per sample, about 5ms attack, 200ms release and 0.5s for the dc offset
+const ATTACK: f32 = 0.1;
+const RELEASE: f32 = 0.0025;
+const DC_TRACKING: f32 = 0.001;
+
+/// turns the raw microphone samples into a sound level
+pub struct EnvelopeFollower {
+    dc: f32,
+    envelope: f32,
+}
+
+impl Default for EnvelopeFollower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnvelopeFollower {
+    pub fn new() -> Self {
+        Self {
+            // the mic is biased at half the supply
+            dc: 2048.0,
+            envelope: 0.0,
+        }
+    }
+
+    /// returns the sample without the dc offset
+    pub fn sample(&mut self, raw: u16) -> f32 {
+        let x = raw as f32;
+
+        self.dc += (x - self.dc) * DC_TRACKING;
+        let sample = x - self.dc;
+        let amplitude = sample.abs();
+
+        let k = if amplitude > self.envelope {
+            ATTACK
+        } else {
+            RELEASE
+        };
+        self.envelope += (amplitude - self.envelope) * k;
+
+        let level = (self.envelope / FULL_SCALE).min(1.0);
+        LEVEL.store(level.to_bits(), Ordering::Relaxed);
+
+        sample
+    }
+}
+
+// the energy is measured over windows of 20ms
+const BEAT_WINDOW: u32 = 40;
+// smoothing factor of the average energy flux, about 1s of windows
+const BEAT_HISTORY: f32 = 0.02;
+// how much the flux has to be above the average to be a beat
+const BEAT_SENSITIVITY: f32 = 2.5;
+// ignore the background noise
+const BEAT_MIN_FLUX: f32 = (FULL_SCALE * 0.05) * (FULL_SCALE * 0.05);
+// at most 4 beats per second, in samples
+const BEAT_MIN_INTERVAL: u32 = 500;
+
+/// onset detector, looks for sudden rises of the sound energy
+#[derive(Default)]
+pub struct BeatDetector {
+    energy: f32,
+    samples: u32,
+    last_energy: f32,
+    average_flux: f32,
+    since_beat: u32,
+}
+
+impl BeatDetector {
+    /// takes the sample from the envelope follower, true on a beat
+    pub fn sample(&mut self, sample: f32) -> bool {
+        self.energy += sample * sample;
+        self.samples += 1;
+        self.since_beat = self.since_beat.saturating_add(1);
+
+        if self.samples < BEAT_WINDOW {
+            return false;
+        }
+
+        let energy = self.energy / BEAT_WINDOW as f32;
+        self.energy = 0.0;
+        self.samples = 0;
+
+        // only the rises of energy count
+        let flux = (energy - self.last_energy).max(0.0);
+        self.last_energy = energy;
+
+        let beat = flux > BEAT_MIN_FLUX
+            && flux > self.average_flux * BEAT_SENSITIVITY
+            && self.since_beat > BEAT_MIN_INTERVAL;
+
+        // the threshold adapts to the loudness of the music
+        self.average_flux += (flux - self.average_flux) * BEAT_HISTORY;
+
+        if beat {
+            self.since_beat = 0;
+        }
+        beat
+    }
+}
+
+const FFT_BITS: u32 = 6;
+const FFT_SIZE: usize = 1 << FFT_BITS;
+
+// sin(2 * pi * k / 64) in Q15, the rest of the wave is mirrored from the first quarter
+const QUARTER_SINE: [i32; FFT_SIZE / 4 + 1] = [
+    0, 3212, 6393, 9512, 12539, 15446, 18204, 20787, 23170, 25329, 27245, 28898, 30273, 31356,
+    32137, 32609, 32767,
+];
+
+fn sin_q15(k: usize) -> i32 {
+    let k = k % FFT_SIZE;
+    let quarter = FFT_SIZE / 4;
+
+    match k / quarter {
+        0 => QUARTER_SINE[k],
+        1 => QUARTER_SINE[2 * quarter - k],
+        2 => -QUARTER_SINE[k - 2 * quarter],
+        _ => -QUARTER_SINE[FFT_SIZE - k],
+    }
+}
+
+fn cos_q15(k: usize) -> i32 {
+    sin_q15(k + FFT_SIZE / 4)
+}
+
+/// in place radix-2 fft, every stage halves the values so nothing can overflow,
+/// the results are the spectrum divided by FFT_SIZE
+fn fft(re: &mut [i32; FFT_SIZE], im: &mut [i32; FFT_SIZE]) {
+    for i in 0..FFT_SIZE {
+        let j = ((i as u32).reverse_bits() >> (32 - FFT_BITS)) as usize;
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= FFT_SIZE {
+        let step = FFT_SIZE / len;
+
+        for start in (0..FFT_SIZE).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = (cos_q15(k * step), -sin_q15(k * step));
+                let a = start + k;
+                let b = a + len / 2;
+
+                let tr = (re[b] * wr - im[b] * wi) >> 15;
+                let ti = (re[b] * wi + im[b] * wr) >> 15;
+
+                re[b] = (re[a] - tr) >> 1;
+                im[b] = (im[a] - ti) >> 1;
+                re[a] = (re[a] + tr) >> 1;
+                im[a] = (im[a] + ti) >> 1;
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+/// sqrt(re^2 + im^2), cheaply approximated without an fpu
+#[cfg(feature = "rp2040")]
+fn magnitude(re: i32, im: i32) -> i32 {
+    let (a, b) = (re.abs(), im.abs());
+    a.max(b) + a.min(b) / 2
+}
+
+/// sqrt(re^2 + im^2), the fpu of the rp2350 computes it exactly
+#[cfg(feature = "rp2350")]
+fn magnitude(re: i32, im: i32) -> i32 {
+    let (re, im) = (re as f32, im as f32);
+    (re * re + im * im).sqrt() as i32
+}
+
+// fft bins for the low, mid and high frequencies, at 2kHz every bin is 31.25Hz
+const BAND_BINS: [(usize, usize); 3] = [(1, 3), (4, 11), (12, FFT_SIZE / 2 - 1)];
+// the loudest value of a band slowly decays, so every band uses the whole display
+const BAND_PEAK_DECAY: f32 = 0.99;
+// below this a band is silent, in adc counts
+const BAND_FLOOR: f32 = 16.0;
+
+/// splits the microphone samples in 3 frequency bands
+pub struct SpectrumAnalyzer {
+    samples: [i32; FFT_SIZE],
+    len: usize,
+    peaks: [f32; 3],
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        Self {
+            samples: [0; FFT_SIZE],
+            len: 0,
+            peaks: [BAND_FLOOR; 3],
+        }
+    }
+}
+
+impl SpectrumAnalyzer {
+    /// takes the sample from the envelope follower
+    pub fn sample(&mut self, sample: f32) {
+        self.samples[self.len] = sample as i32;
+        self.len += 1;
+
+        if self.len < FFT_SIZE {
+            return;
+        }
+        self.len = 0;
+
+        let mut re = self.samples;
+        let mut im = [0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        for (i, (first, last)) in BAND_BINS.iter().enumerate() {
+            let magnitude: i32 = (*first..=*last)
+                .map(|bin| magnitude(re[bin], im[bin]))
+                .sum();
+            let magnitude = magnitude as f32;
+
+            self.peaks[i] = (self.peaks[i] * BAND_PEAK_DECAY)
+                .max(magnitude)
+                .max(BAND_FLOOR);
+
+            let level = if magnitude < BAND_FLOOR {
+                0.0
+            } else {
+                magnitude / self.peaks[i]
+            };
+            BANDS[i].store(level.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// what `picotool info` reports about the firmware of a connected badge, the header is
+// in .boot_info and the entries in .bi_entries, see memory.x
+
+use core::ffi::CStr;
+
+// a string of the build script, see build.rs
+macro_rules! build_env {
+    ($name:literal) => {
+        match CStr::from_bytes_with_nul(concat!(env!($name), "\0").as_bytes()) {
+            Ok(value) => value,
+            Err(_) => panic!("nul byte in {}", $name),
+        }
+    };
+}
+
+#[link_section = ".bi_entries"]
+#[used]
+pub static PICOTOOL_ENTRIES: [rp_binary_info::EntryAddr; 6] = [
+    rp_binary_info::rp_program_name!(c"minibadge"),
+    rp_binary_info::rp_cargo_version!(),
+    rp_binary_info::rp_program_description!(c"End Summer Camp Mini Badge firmware"),
+    rp_binary_info::rp_program_build_date_string!(build_env!("BUILD_DATE")),
+    rp_binary_info::rp_pico_board!(build_env!("BOARD_VARIANT")),
+    rp_binary_info::rp_program_build_attribute!(),
+];
@@ -0,0 +1,194 @@
+// the boards the firmware runs on, one board-* feature each: the pin of every part, the
+// size of the matrix and the optional parts of the build
+//
+// every pin of embassy is a type of its own, so main takes them from the peripherals
+// with pin!(p.name) instead of a table
+//
+// the revisions of a board are told apart at boot by the strap resistors to ground on
+// strap_0 and strap_1, so one firmware runs on every batch: the pins left open read 1
+
+use embassy_rp::gpio::Input;
+use embassy_time::{block_for, Duration};
+
+/// leds of the matrix, the patterns are 9 bits so every board has 3x3 for now
+pub const MATRIX_WIDTH: usize = 3;
+pub const MATRIX_HEIGHT: usize = 3;
+
+/// the optional parts, each with the feature of the same name
+pub const HAS_MIC: bool = cfg!(feature = "mic");
+pub const HAS_ACCEL: bool = cfg!(feature = "accel");
+pub const HAS_BUZZER: bool = cfg!(feature = "buzzer");
+pub const HAS_STRIP: bool = cfg!(feature = "strip");
+
+#[cfg(all(feature = "board-minibadge", feature = "board-pico"))]
+compile_error!(
+    "build for one board, the pico with --no-default-features --features rp2040,board-pico"
+);
+#[cfg(not(any(feature = "board-minibadge", feature = "board-pico")))]
+compile_error!("build with the feature of the board, board-minibadge or board-pico");
+
+// the pins are the same on every board but the white led: pins! makes the pin! macro of
+// the board, $d is the $ of pin!, that can't be written inside pins!
+#[rustfmt::skip]
+macro_rules! pins {
+    ($d:tt, white_led: $white_led:ident) => {
+        macro_rules! pin {
+            ($d p:ident.button) => {
+                $d p.PIN_8
+            };
+            ($d p:ident.white_led) => {
+                $d p.$white_led
+            };
+            // the ir receiver, and the footprint of a second one kept as a floating input
+            ($d p:ident.ir_sensor) => {
+                $d p.PIN_10
+            };
+            ($d p:ident.ir_sensor_2) => {
+                $d p.PIN_9
+            };
+            // the channel b of the pwm slice drives the ir led
+            ($d p:ident.ir_blaster) => {
+                $d p.PIN_11
+            };
+            ($d p:ident.ir_blaster_pwm) => {
+                $d p.PWM_SLICE5
+            };
+            ($d p:ident.leds) => {
+                $d p.PIN_19
+            };
+            ($d p:ident.leds_alt) => {
+                $d p.PIN_18
+            };
+            ($d p:ident.strap_0) => {
+                $d p.PIN_21
+            };
+            ($d p:ident.strap_1) => {
+                $d p.PIN_22
+            };
+            ($d p:ident.mic) => {
+                $d p.PIN_26
+            };
+            ($d p:ident.buzzer) => {
+                $d p.PIN_15
+            };
+            ($d p:ident.buzzer_pwm) => {
+                $d p.PWM_SLICE7
+            };
+            ($d p:ident.strobe_sync) => {
+                $d p.PIN_6
+            };
+            // the data pad of the breakout
+            ($d p:ident.strip) => {
+                $d p.PIN_16
+            };
+            ($d p:ident.accel_sda) => {
+                $d p.PIN_4
+            };
+            ($d p:ident.accel_scl) => {
+                $d p.PIN_5
+            };
+            ($d p:ident.i2c_slave_sda) => {
+                $d p.PIN_2
+            };
+            ($d p:ident.i2c_slave_scl) => {
+                $d p.PIN_3
+            };
+            ($d p:ident.uart_rx) => {
+                $d p.PIN_1
+            };
+            ($d p:ident.spi_mosi) => {
+                $d p.PIN_12
+            };
+            ($d p:ident.spi_sck) => {
+                $d p.PIN_13
+            };
+            ($d p:ident.spi_cs) => {
+                $d p.PIN_14
+            };
+        }
+    };
+}
+
+/// the End Summer Camp mini badge
+#[cfg(feature = "board-minibadge")]
+pub const NAME: &str = "minibadge";
+
+#[cfg(feature = "board-minibadge")]
+pins!($, white_led: PIN_20);
+
+/// a Raspberry Pi Pico (or Pico 2) on a breadboard, wired like the badge, the white led
+/// is the green one of the pico
+#[cfg(feature = "board-pico")]
+pub const NAME: &str = "pico";
+
+#[cfg(feature = "board-pico")]
+pins!($, white_led: PIN_25);
+
+pub(crate) use pin;
+
+/// the pin of the led chain, pin!(p.leds) or pin!(p.leds_alt)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LedsPin {
+    Main,
+    Alt,
+}
+
+/// what changes between the batches of a board
+#[derive(Debug)]
+pub struct Revision {
+    pub name: &'static str,
+    pub leds_pin: LedsPin,
+}
+
+// by the value of the straps, strap_1 is the high bit
+static REVISIONS: [(u8, Revision); 2] = [
+    // no strap resistors, every badge before the straps
+    (
+        0b11,
+        Revision {
+            name: "1",
+            leds_pin: LedsPin::Main,
+        },
+    ),
+    // strap_0 to ground, the led chain moved next to the pio pins of the connector
+    (
+        0b10,
+        Revision {
+            name: "2",
+            leds_pin: LedsPin::Alt,
+        },
+    ),
+];
+
+impl Revision {
+    /// reads the straps, with the pull ups of the inputs enabled
+    pub fn detect(strap_0: &Input<'_>, strap_1: &Input<'_>) -> &'static Revision {
+        // the time for the pull ups to charge the pins
+        block_for(Duration::from_micros(10));
+        let straps = (strap_1.is_high() as u8) << 1 | strap_0.is_high() as u8;
+
+        match REVISIONS.iter().find(|(value, _)| *value == straps) {
+            Some((_, revision)) => revision,
+            None => {
+                log::warn!(
+                    "Unknown board revision {:#04b}, using the first one",
+                    straps
+                );
+                &REVISIONS[0].1
+            }
+        }
+    }
+}
+
+/// logs the board, its revision and its optional parts at boot
+pub fn log(revision: &Revision) {
+    log::info!(
+        "Board: {} revision {}, mic: {}, accelerometer: {}, buzzer: {}, strip: {}",
+        NAME,
+        revision.name,
+        HAS_MIC,
+        HAS_ACCEL,
+        HAS_BUZZER,
+        HAS_STRIP
+    );
+}
@@ -0,0 +1,96 @@
+use embassy_rp::flash::ERASE_SIZE;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BadgeFlash, BOOT_LOGO_OFFSET};
+use crate::rgbeffects::{ColorFrame, LoopMode, Pattern, RenderCommand};
+use crate::time::Time;
+
+// a boot animation of the owner instead of the built-in one, full color frames uploaded
+// over usb one at a time, then saved all at once and played from the next boot
+//
+// same layout as the palette table: magic, postcard data length, then the logo
+// serialized with postcard
+
+const BOOT_LOGO_MAGIC: u32 = 0x4D424C47; // "MBLG"
+const HEADER_SIZE: usize = 8;
+const MAX_SIZE: usize = 512;
+
+/// frames of a logo, 27 bytes each
+pub const MAX_FRAMES: usize = 16;
+/// how long a frame is shown, the whole logo lasts 4 seconds at most
+pub const MIN_FRAME_MILLIS: u16 = 20;
+pub const MAX_FRAME_MILLIS: u16 = 250;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BootLogo {
+    pub frames: Vec<ColorFrame, MAX_FRAMES>,
+    pub frame_millis: u16,
+}
+
+impl BootLogo {
+    /// the boot animation and the time it ends, None for the built-in one
+    pub fn animation(&'static self) -> Option<(RenderCommand, Time)> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        let millis = self.frame_millis.clamp(MIN_FRAME_MILLIS, MAX_FRAME_MILLIS);
+        let animation = RenderCommand {
+            effect: Pattern::AnimationColor(&self.frames, 1000.0 / millis as f32, LoopMode::Clear),
+            ..Default::default()
+        };
+        let end = Time::from_micros(self.frames.len() as i64 * millis as i64 * 1000);
+        Some((animation, end))
+    }
+}
+
+/// reads the logo from flash, an erased or invalid sector gives no logo
+pub fn load(flash: &mut BadgeFlash) -> BootLogo {
+    let mut data = [0; HEADER_SIZE + MAX_SIZE];
+
+    if let Err(e) = flash.blocking_read(BOOT_LOGO_OFFSET, &mut data) {
+        log::error!("Error reading boot logo: {:?}", e);
+        return BootLogo::default();
+    }
+
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    if magic != BOOT_LOGO_MAGIC || len > MAX_SIZE {
+        log::info!("No boot logo in flash");
+        return BootLogo::default();
+    }
+
+    postcard::from_bytes(&data[HEADER_SIZE..HEADER_SIZE + len]).unwrap_or_else(|e| {
+        log::error!("Error decoding boot logo: {:?}", e);
+        BootLogo::default()
+    })
+}
+
+/// a logo without frames erases the one in flash, for the built-in one
+pub fn store(flash: &mut BadgeFlash, logo: &BootLogo) {
+    let mut data = [0xFF; HEADER_SIZE + MAX_SIZE];
+
+    let len = match postcard::to_slice(logo, &mut data[HEADER_SIZE..]) {
+        Ok(encoded) => encoded.len(),
+        Err(e) => {
+            log::error!("Error encoding boot logo: {:?}", e);
+            return;
+        }
+    };
+    data[0..4].copy_from_slice(&BOOT_LOGO_MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+
+    if let Err(e) = flash.blocking_erase(BOOT_LOGO_OFFSET, BOOT_LOGO_OFFSET + ERASE_SIZE as u32) {
+        log::error!("Error erasing boot logo: {:?}", e);
+        return;
+    }
+
+    if logo.frames.is_empty() {
+        return;
+    }
+    if let Err(e) = flash.blocking_write(BOOT_LOGO_OFFSET, &data[..HEADER_SIZE + len]) {
+        log::error!("Error writing boot logo: {:?}", e);
+    }
+}
@@ -0,0 +1,89 @@
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+#[cfg(feature = "buzzer")]
+use embassy_futures::select::{select, Either};
+#[cfg(feature = "buzzer")]
+use embassy_rp::pwm;
+#[cfg(feature = "buzzer")]
+use embassy_time::{Duration, Timer};
+
+// a new sound interrupts the one that is playing
+static BUZZER_SIGNAL: Signal<CriticalSectionRawMutex, Sound> = Signal::new();
+
+#[derive(Clone, Copy, Debug)]
+pub enum Sound {
+    Click,
+    Notification,
+    IrReceived,
+    Win,
+    Lose,
+    Hit,
+    Error,
+}
+
+// frequency in Hz (0 is a pause) and duration in ms
+type Tone = (u16, u16);
+
+impl Sound {
+    fn melody(self) -> &'static [Tone] {
+        match self {
+            Sound::Click => &[(2000, 15)],
+            Sound::Notification => &[(1319, 80), (0, 40), (1760, 120)],
+            Sound::IrReceived => &[(1760, 50), (2349, 50)],
+            Sound::Win => &[(1047, 100), (1319, 100), (1568, 100), (2093, 250)],
+            Sound::Lose => &[(784, 150), (659, 150), (523, 300)],
+            Sound::Hit => &[(300, 150)],
+            Sound::Error => &[(200, 100), (0, 50), (200, 100)],
+        }
+    }
+}
+
+/// plays a sound on the buzzer, does nothing if the badge has no buzzer
+pub fn play(sound: Sound) {
+    if cfg!(feature = "buzzer") {
+        BUZZER_SIGNAL.signal(sound);
+    }
+}
+
+// the pwm counts at 125MHz / 64 (150MHz / 64 on the rp2350), so even 100Hz fits in
+// the 16 bits of top
+#[cfg(feature = "buzzer")]
+const DIVIDER: u8 = 64;
+
+#[cfg(feature = "buzzer")]
+#[embassy_executor::task]
+pub async fn buzzer_tsk(mut buzzer: pwm::Pwm<'static>) {
+    let mut cfg = pwm::Config::default();
+    cfg.divider = fixed::FixedU16::from_num(DIVIDER);
+    let pwm_clock = embassy_rp::clocks::clk_sys_freq() / DIVIDER as u32;
+
+    let mut next = None;
+
+    loop {
+        let sound = match next.take() {
+            Some(sound) => sound,
+            None => BUZZER_SIGNAL.wait().await,
+        };
+
+        for &(frequency, ms) in sound.melody() {
+            if frequency > 0 {
+                cfg.top = (pwm_clock / frequency as u32 - 1) as u16;
+                // square wave, the loudest for a piezo
+                cfg.compare_b = cfg.top / 2;
+            } else {
+                cfg.compare_b = 0;
+            }
+            buzzer.set_config(&cfg);
+
+            let tone = Timer::after(Duration::from_millis(ms as u64));
+            if let Either::Second(sound) = select(tone, BUZZER_SIGNAL.wait()).await {
+                next = Some(sound);
+                break;
+            }
+        }
+
+        cfg.compare_b = 0;
+        buzzer.set_config(&cfg);
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{LedPixel, LED_MATRIX_SIZE};
+
+// per led corrections measured during the factory test, saved with the config
+//
+// the firmwares before the config store saved them in their own sector, with the same
+// layout as the user scenes: magic, postcard data length, postcard data
+
+const CALIBRATION_MAGIC: u32 = 0x4D42434C; // "MBCL"
+const HEADER_SIZE: usize = 8;
+const MAX_SIZE: usize = 256;
+/// bytes of the sector of the older firmwares to read
+pub const LEGACY_SIZE: usize = HEADER_SIZE + MAX_SIZE;
+
+/// the gamma of the old fixed table, 2.2 looks wrong at low gains on these leds
+pub const DEFAULT_GAMMA: [f32; 3] = [2.8; 3];
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Calibration {
+    /// r, g, b scale of every led, 255 = unchanged, in the order of the ws2812 chain
+    pub white_balance: [[u8; 3]; LED_MATRIX_SIZE],
+    /// brightness of every led, 255 = unchanged, so the brighter ones can match the others
+    pub luminance: [u8; LED_MATRIX_SIZE],
+    /// broken leds, so a badge with a dead led can still show the scenes
+    pub pixels: [PixelStatus; LED_MATRIX_SIZE],
+    /// gamma of red, green and blue
+    pub gamma: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PixelStatus {
+    #[default]
+    Working,
+    Dead,        // always off, a half broken led may show the wrong colors
+    MovedTo(u8), // off, what it should show is added to the given led
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            white_balance: [[255; 3]; LED_MATRIX_SIZE],
+            luminance: [255; LED_MATRIX_SIZE],
+            pixels: [PixelStatus::Working; LED_MATRIX_SIZE],
+            gamma: DEFAULT_GAMMA,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CalibrationEdit {
+    WhiteBalance(u8, [u8; 3]), // led, r g b scale
+    Luminance(u8, u8),         // led, scale
+    Pixel(u8, PixelStatus),
+    Gamma([f32; 3]), // r g b
+}
+
+impl Calibration {
+    pub fn apply(&mut self, edit: &CalibrationEdit) {
+        match *edit {
+            CalibrationEdit::WhiteBalance(led, rgb) => {
+                if let Some(scale) = self.white_balance.get_mut(led as usize) {
+                    *scale = rgb;
+                }
+            }
+            CalibrationEdit::Luminance(led, luminance) => {
+                if let Some(scale) = self.luminance.get_mut(led as usize) {
+                    *scale = luminance;
+                }
+            }
+            CalibrationEdit::Pixel(led, status) => {
+                if let Some(pixel) = self.pixels.get_mut(led as usize) {
+                    *pixel = status;
+                }
+            }
+            CalibrationEdit::Gamma(gamma) => {
+                // saved, a nan would break the gamma tables at every boot
+                if gamma.iter().any(|g| !g.is_finite() || *g <= 0.0) {
+                    log::warn!("Invalid gamma {:?}", gamma);
+                    return;
+                }
+                // 1.0 is linear, anything outside this range makes no sense
+                self.gamma = gamma.map(|g| g.clamp(1.0, 4.0));
+            }
+        }
+    }
+
+    /// how much to scale each channel of a led
+    pub fn scale(&self, led: usize) -> [u8; 3] {
+        let luminance = self.luminance[led] as u16;
+        self.white_balance[led].map(|c| (c as u16 * luminance / 255) as u8)
+    }
+
+    /// turns off the dead leds, moving their colors to the neighbours if asked to
+    pub fn remap(&self, leds: &mut [LedPixel; LED_MATRIX_SIZE]) {
+        for (led, status) in self.pixels.iter().enumerate() {
+            let target = match *status {
+                PixelStatus::Working => continue,
+                PixelStatus::Dead => None,
+                PixelStatus::MovedTo(target) => Some(target as usize),
+            };
+
+            let colour = core::mem::take(&mut leds[led]);
+
+            // the brightest of the two, so the neighbour keeps its own content too
+            if let Some(target) = target.filter(|&t| t < LED_MATRIX_SIZE && t != led) {
+                let other = &mut leds[target];
+                other.r = other.r.max(colour.r);
+                other.g = other.g.max(colour.g);
+                other.b = other.b.max(colour.b);
+                other.w = other.w.max(colour.w);
+            }
+        }
+    }
+
+    /// the calibration in the sector where the firmwares before the config store saved it,
+    /// or no correction at all
+    pub fn from_legacy_bytes(data: &[u8; LEGACY_SIZE]) -> Self {
+        let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+        if magic != CALIBRATION_MAGIC || len > MAX_SIZE {
+            log::info!("No calibration in flash");
+            return Self::default();
+        }
+
+        postcard::from_bytes(&data[HEADER_SIZE..HEADER_SIZE + len]).unwrap_or_else(|e| {
+            log::error!("Error decoding calibration: {:?}", e);
+            Self::default()
+        })
+    }
+}
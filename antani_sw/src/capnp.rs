@@ -1,8 +1,16 @@
 use capnp::{message::ReaderOptions, serialize};
 
 use crate::{
-    rgbeffects::{ColorPalette, RenderCommand},
-    usb_messages_capnp, LedPixel, RawFramebuffer, TaskCommand,
+    calibration::{CalibrationEdit, PixelStatus},
+    editor::SceneEdit,
+    input::{IrFilter, IrProtocol},
+    party,
+    rgbeffects::{
+        color_frame, ColorPalette, FragmentShader, RenderCommand, Sensor, MAX_PALETTE_COLORS,
+        PALETTE_TABLE_SIZE,
+    },
+    scenes::{Notification, Playback},
+    strip, usb_messages_capnp, LedPixel, RawFramebuffer, TaskCommand,
 };
 
 pub fn deserialize_message(data: &mut &[u8]) -> Result<TaskCommand, capnp::Error> {
@@ -68,6 +76,369 @@ pub fn deserialize_message(data: &mut &[u8]) -> Result<TaskCommand, capnp::Error
             return Ok(TaskCommand::SendIrNec(address, _command, repeat));
         }
 
+        usb_messages_capnp::badge_bound::Which::SetTime(time) => {
+            let time = time?;
+
+            return Ok(TaskCommand::SetTime(
+                time.get_hour(),
+                time.get_minute(),
+                time.get_second(),
+            ));
+        }
+
+        usb_messages_capnp::badge_bound::Which::Notify(notification) => {
+            let notification = match notification? {
+                usb_messages_capnp::Notification::Mention => Notification::Mention,
+                usb_messages_capnp::Notification::Message => Notification::Message,
+                usb_messages_capnp::Notification::BuildFailed => Notification::BuildFailed,
+                usb_messages_capnp::Notification::BuildPassed => Notification::BuildPassed,
+            };
+
+            return Ok(TaskCommand::Notify(notification));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetRandomBootScene(enabled) => {
+            return Ok(TaskCommand::SetRandomBootScene(enabled));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetPlayback(playback) => {
+            let playback = playback?;
+
+            let playback = if playback.get_shuffle() {
+                Playback::Shuffle {
+                    interval: playback.get_interval(),
+                }
+            } else {
+                Playback::Sequential
+            };
+
+            return Ok(TaskCommand::SetPlayback(playback));
+        }
+
+        usb_messages_capnp::badge_bound::Which::EditScene(edit) => {
+            use usb_messages_capnp::scene_edit::Which;
+
+            let edit = match edit?.which()? {
+                Which::New(()) => SceneEdit::New,
+                Which::AddLayer(()) => SceneEdit::AddLayer,
+                Which::SetPattern(pattern) => SceneEdit::SetPattern(pattern),
+                Which::SetColor(color) => {
+                    let color = color?;
+
+                    SceneEdit::SetColor((color.get_r(), color.get_g(), color.get_b()).into())
+                }
+                Which::SetPalette(palette) => {
+                    let palette = palette?;
+
+                    if palette.get_indexed() {
+                        return Ok(TaskCommand::EditScene(SceneEdit::SetIndexedPalette(
+                            palette.get_speed(),
+                        )));
+                    }
+
+                    // extra colors are dropped
+                    let colors = palette
+                        .get_colors()?
+                        .iter()
+                        .map(|c| (c.get_r(), c.get_g(), c.get_b()).into())
+                        .take(MAX_PALETTE_COLORS)
+                        .collect();
+
+                    SceneEdit::SetPalette(colors, palette.get_speed())
+                }
+                Which::SetDuotone(duotone) => {
+                    let duotone = duotone?;
+                    let (a, b) = (duotone.get_a()?, duotone.get_b()?);
+
+                    SceneEdit::SetDuotone(
+                        (a.get_r(), a.get_g(), a.get_b()).into(),
+                        (b.get_r(), b.get_g(), b.get_b()).into(),
+                        duotone.get_bias(),
+                    )
+                }
+                Which::SetSensorPalette(palette) => {
+                    let palette = palette?;
+
+                    let sensor = match palette.get_sensor()? {
+                        usb_messages_capnp::Sensor::Temperature => Sensor::Temperature,
+                        usb_messages_capnp::Sensor::AudioLevel => Sensor::AudioLevel,
+                        usb_messages_capnp::Sensor::Bass => Sensor::Bass,
+                        usb_messages_capnp::Sensor::Mid => Sensor::Mid,
+                        usb_messages_capnp::Sensor::Treble => Sensor::Treble,
+                    };
+
+                    // extra colors are dropped
+                    let colors = palette
+                        .get_colors()?
+                        .iter()
+                        .map(|c| (c.get_r(), c.get_g(), c.get_b()).into())
+                        .take(MAX_PALETTE_COLORS)
+                        .collect();
+
+                    SceneEdit::SetSensorPalette(sensor, colors)
+                }
+                Which::AddShader(shader) => {
+                    let shader = shader?;
+                    let param = shader.get_param();
+
+                    let fragment_shader = match shader.get_kind()? {
+                        usb_messages_capnp::ShaderKind::Breathing => {
+                            FragmentShader::Breathing(param)
+                        }
+                        usb_messages_capnp::ShaderKind::Blinking => FragmentShader::Blinking(param),
+                        usb_messages_capnp::ShaderKind::LowPass => FragmentShader::LowPass(param),
+                        usb_messages_capnp::ShaderKind::LowPassWithPeak => {
+                            FragmentShader::LowPassWithPeak(param)
+                        }
+                        usb_messages_capnp::ShaderKind::Rainbow2d => {
+                            FragmentShader::Rainbow2D(param)
+                        }
+                        usb_messages_capnp::ShaderKind::AudioLevel => {
+                            FragmentShader::AudioLevel(param)
+                        }
+                        usb_messages_capnp::ShaderKind::BeatPulse => {
+                            FragmentShader::BeatPulse(param)
+                        }
+                        usb_messages_capnp::ShaderKind::WhitePoint => {
+                            FragmentShader::WhitePoint(param)
+                        }
+                        usb_messages_capnp::ShaderKind::Blur => FragmentShader::Blur(param),
+                        usb_messages_capnp::ShaderKind::Glitch => FragmentShader::Glitch(param),
+                        usb_messages_capnp::ShaderKind::Strobe => FragmentShader::Strobe(param),
+                        usb_messages_capnp::ShaderKind::Heartbeat => {
+                            FragmentShader::Heartbeat(param)
+                        }
+                        usb_messages_capnp::ShaderKind::PaletteCycle => {
+                            FragmentShader::PaletteCycle(param)
+                        }
+                    };
+
+                    SceneEdit::AddShader(fragment_shader, shader.get_screen())
+                }
+                Which::Save(()) => SceneEdit::Save,
+                Which::Discard(()) => SceneEdit::Discard,
+            };
+
+            return Ok(TaskCommand::EditScene(edit));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetFrameCapture(enabled) => {
+            return Ok(TaskCommand::SetFrameCapture(enabled));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetFrameRate(fps) => {
+            return Ok(TaskCommand::SetFrameRate(crate::records::clamp_fps(fps)));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetMaxBrightness(percent) => {
+            return Ok(TaskCommand::SetMaxBrightness(
+                crate::records::clamp_max_brightness(percent),
+            ));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetQuietHours(quiet_hours) => {
+            let quiet_hours = quiet_hours?;
+            let minutes = |time: usb_messages_capnp::time::Reader| {
+                time.get_hour().min(23) as u16 * 60 + time.get_minute().min(59) as u16
+            };
+
+            let start = minutes(quiet_hours.get_start()?);
+            let end = minutes(quiet_hours.get_end()?);
+            return Ok(TaskCommand::SetQuietHours((start != end).then(|| {
+                crate::clock::QuietHours {
+                    start,
+                    end,
+                    max_brightness: crate::records::clamp_max_brightness(
+                        quiet_hours.get_max_brightness(),
+                    ),
+                }
+            })));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetPaletteTable(colors) => {
+            // extra colors are dropped
+            let table = colors?
+                .iter()
+                .map(|c| (c.get_r(), c.get_g(), c.get_b()).into())
+                .take(PALETTE_TABLE_SIZE)
+                .collect();
+
+            return Ok(TaskCommand::SetPaletteTable(table));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetIrOverlay(enabled) => {
+            return Ok(TaskCommand::SetIrOverlay(enabled));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetIrFilter(remotes) => {
+            // extra remotes are dropped
+            let mut filter = IrFilter::default();
+            for (slot, remote) in filter.remotes.iter_mut().zip(remotes?.iter()) {
+                let protocol = match remote.get_protocol()? {
+                    usb_messages_capnp::IrProtocol::Nec => IrProtocol::Nec,
+                    usb_messages_capnp::IrProtocol::Samsung => IrProtocol::Samsung,
+                };
+                *slot = Some((protocol, remote.get_address()));
+            }
+
+            return Ok(TaskCommand::SetIrFilter(filter));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetName(name) => {
+            // a longer name is cut at the last whole character that fits
+            let mut stored = heapless::String::new();
+            for c in name?.to_str()?.chars() {
+                if stored.push(c).is_err() {
+                    break;
+                }
+            }
+
+            return Ok(TaskCommand::SetName(stored));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetInitials(initials) => {
+            return Ok(TaskCommand::SetInitials(crate::initials::parse(
+                initials?.to_str()?,
+            )));
+        }
+
+        usb_messages_capnp::badge_bound::Which::Reboot(reboot) => {
+            let reboot = reboot?;
+            if reboot.get_magic() != crate::reboot::MAGIC {
+                log::warn!("Reboot with a wrong magic");
+                return Ok(TaskCommand::Error);
+            }
+
+            let target = if reboot.get_bootloader() {
+                crate::reboot::Target::Bootloader
+            } else {
+                crate::reboot::Target::Firmware
+            };
+            return Ok(TaskCommand::Reboot(target));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetStrip(settings) => {
+            let settings = settings?;
+            let mapping = match settings.get_mapping()? {
+                usb_messages_capnp::StripMapping::Mirror => strip::Mapping::Mirror,
+                usb_messages_capnp::StripMapping::Gradient => strip::Mapping::Gradient,
+            };
+
+            // a longer strip lights its first MAX_LEDS leds
+            return Ok(TaskCommand::SetStrip(strip::Strip {
+                length: settings.get_length().min(strip::MAX_LEDS as u16),
+                mapping,
+            }));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetChainedBadges(badges) => {
+            return Ok(TaskCommand::SetChainedBadges(
+                crate::records::clamp_chained_badges(badges),
+            ));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetPartyRole(role) => {
+            let role = match role? {
+                usb_messages_capnp::PartyRole::Off => party::Role::Off,
+                usb_messages_capnp::PartyRole::Leader => party::Role::Leader,
+                usb_messages_capnp::PartyRole::Follower => party::Role::Follower,
+            };
+
+            return Ok(TaskCommand::SetPartyRole(role));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetBrightness(level) => {
+            return Ok(TaskCommand::SetBrightness(crate::OutputPower::scaled(
+                level,
+                crate::BRIGHTNESS_LEVELS - 1,
+            )));
+        }
+
+        usb_messages_capnp::badge_bound::Which::LogStats(()) => {
+            return Ok(TaskCommand::LogStats);
+        }
+
+        usb_messages_capnp::badge_bound::Which::RecordInput(enabled) => {
+            return Ok(TaskCommand::RecordInput(enabled));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetMixScene(scene) => {
+            return Ok(TaskCommand::SetMixScene(scene));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SetMixAmount(percent) => {
+            return Ok(TaskCommand::SetMixAmount(percent.min(100)));
+        }
+
+        usb_messages_capnp::badge_bound::Which::RecordFrames(enabled) => {
+            return Ok(TaskCommand::RecordFrames(enabled));
+        }
+
+        usb_messages_capnp::badge_bound::Which::ReplayInput(()) => {
+            return Ok(TaskCommand::ReplayInput);
+        }
+
+        usb_messages_capnp::badge_bound::Which::Calibrate(calibration) => {
+            use usb_messages_capnp::calibration::Which;
+
+            let edit = match calibration?.which()? {
+                Which::StepLeds(()) => {
+                    return Ok(TaskCommand::SetWorkingMode(
+                        crate::WorkingMode::Calibration(0),
+                    ));
+                }
+                Which::WhiteBalance(white_balance) => {
+                    let white_balance = white_balance?;
+                    let scale = white_balance.get_scale()?;
+
+                    CalibrationEdit::WhiteBalance(
+                        white_balance.get_led(),
+                        [scale.get_r(), scale.get_g(), scale.get_b()],
+                    )
+                }
+                Which::Luminance(luminance) => {
+                    let luminance = luminance?;
+                    CalibrationEdit::Luminance(luminance.get_led(), luminance.get_scale())
+                }
+                Which::PixelStatus(pixel) => {
+                    use usb_messages_capnp::pixel_status::Which;
+
+                    let pixel = pixel?;
+                    let status = match pixel.which()? {
+                        Which::Working(()) => PixelStatus::Working,
+                        Which::Dead(()) => PixelStatus::Dead,
+                        Which::MovedTo(target) => PixelStatus::MovedTo(target),
+                    };
+
+                    CalibrationEdit::Pixel(pixel.get_led(), status)
+                }
+                Which::Gamma(gamma) => {
+                    let gamma = gamma?;
+                    CalibrationEdit::Gamma([gamma.get_r(), gamma.get_g(), gamma.get_b()])
+                }
+            };
+
+            return Ok(TaskCommand::Calibrate(edit));
+        }
+
+        usb_messages_capnp::badge_bound::Which::NewBootLogo(_) => {
+            return Ok(TaskCommand::NewBootLogo);
+        }
+
+        usb_messages_capnp::badge_bound::Which::AddBootLogoFrame(data) => {
+            // the same picture as a streamed frame
+            let frame = color_frame(&RawFramebuffer::from_rgb(data?));
+            return Ok(TaskCommand::AddBootLogoFrame(frame));
+        }
+
+        usb_messages_capnp::badge_bound::Which::SaveBootLogo(frame_millis) => {
+            return Ok(TaskCommand::SaveBootLogo(frame_millis));
+        }
+
+        usb_messages_capnp::badge_bound::Which::StreamFrame(data) => {
+            return Ok(TaskCommand::StreamFrame(RawFramebuffer::from_rgb(data?)));
+        }
+
         usb_messages_capnp::badge_bound::Which::Null(_) => {}
     }
 
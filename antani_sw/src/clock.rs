@@ -0,0 +1,157 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_futures::select::{select, Either};
+#[cfg(feature = "rp2040")]
+use embassy_rp::peripherals::RTC;
+#[cfg(feature = "rp2040")]
+use embassy_rp::rtc::{DateTime, DayOfWeek};
+use embassy_rp::watchdog::Watchdog;
+#[cfg(feature = "rp2350")]
+use embassy_time::Instant;
+use embassy_time::{Duration, Ticker};
+use serde::{Deserialize, Serialize};
+
+use crate::{MegaSubscriber, TaskCommand};
+
+// the rtc is reset by embassy_rp::init, the watchdog scratch registers are not,
+// so we keep a copy of the time there to survive soft resets
+// (scratch 4-7 are used by the bootrom)
+const SCRATCH_MAGIC_IDX: usize = 0;
+const SCRATCH_TIME_IDX: usize = 1;
+const SCRATCH_MAGIC: u32 = 0xA7A11C10;
+
+const TIME_NOT_SET: u32 = u32::MAX;
+
+// seconds since midnight, read by the render task running on the other core
+static SECONDS_OF_DAY: AtomicU32 = AtomicU32::new(TIME_NOT_SET);
+
+/// current time as seconds since midnight, None if nobody told us what time it is
+pub fn seconds_of_day() -> Option<u32> {
+    match SECONDS_OF_DAY.load(Ordering::Relaxed) {
+        TIME_NOT_SET => None,
+        s => Some(s),
+    }
+}
+
+/// the hours the brightness is kept low, for the badges left on a desk at night
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// minutes since midnight, the end is the next day when before the start
+    pub start: u16,
+    pub end: u16,
+    /// ceiling of the brightness in percent in between
+    pub max_brightness: u8,
+}
+
+impl QuietHours {
+    pub fn contains(&self, seconds_of_day: u32) -> bool {
+        let minute = (seconds_of_day / 60) as u16;
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub type Rtc = embassy_rp::rtc::Rtc<'static, RTC>;
+
+/// the rp2350 has no rtc, the time is counted from the timer since it was set
+#[cfg(feature = "rp2350")]
+#[derive(Default)]
+pub struct Rtc {
+    // when the time was set, and to what
+    set: Option<(Instant, u32)>,
+}
+
+/// what the clock needs of the rtc of the chip
+trait TimeOfDay {
+    fn set_seconds_of_day(&mut self, seconds_of_day: u32) -> bool;
+    /// None while the time was never set
+    fn seconds_of_day(&self) -> Option<u32>;
+}
+
+#[cfg(feature = "rp2040")]
+impl TimeOfDay for Rtc {
+    fn set_seconds_of_day(&mut self, seconds_of_day: u32) -> bool {
+        // we only care about the time of the day, the date is a placeholder
+        let now = DateTime {
+            year: 2024,
+            month: 1,
+            day: 1,
+            day_of_week: DayOfWeek::Monday,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day / 60 % 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+        };
+
+        match self.set_datetime(now) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("Error setting the rtc: {:?}", e);
+                false
+            }
+        }
+    }
+
+    fn seconds_of_day(&self) -> Option<u32> {
+        // not running = the time was never set
+        let now = self.now().ok()?;
+        Some(now.hour as u32 * 3600 + now.minute as u32 * 60 + now.second as u32)
+    }
+}
+
+#[cfg(feature = "rp2350")]
+impl TimeOfDay for Rtc {
+    fn set_seconds_of_day(&mut self, seconds_of_day: u32) -> bool {
+        self.set = Some((Instant::now(), seconds_of_day));
+        true
+    }
+
+    fn seconds_of_day(&self) -> Option<u32> {
+        let (at, seconds) = self.set?;
+        Some((seconds + at.elapsed().as_secs() as u32) % (24 * 3600))
+    }
+}
+
+fn set_time(rtc: &mut Rtc, seconds_of_day: u32) {
+    let seconds_of_day = seconds_of_day % (24 * 3600);
+
+    if rtc.set_seconds_of_day(seconds_of_day) {
+        SECONDS_OF_DAY.store(seconds_of_day, Ordering::Relaxed);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn rtc_tsk(mut rtc: Rtc, mut watchdog: Watchdog, mut subscriber: MegaSubscriber) {
+    if watchdog.get_scratch(SCRATCH_MAGIC_IDX) == SCRATCH_MAGIC {
+        let saved = watchdog.get_scratch(SCRATCH_TIME_IDX);
+        log::info!("Restoring time after reset: {}", saved);
+        set_time(&mut rtc, saved);
+    }
+
+    let mut ticker = Ticker::every(Duration::from_secs(1));
+
+    loop {
+        match select(ticker.next(), subscriber.next_message_pure()).await {
+            Either::First(_) => {
+                let Some(seconds) = rtc.seconds_of_day() else {
+                    continue;
+                };
+                SECONDS_OF_DAY.store(seconds, Ordering::Relaxed);
+
+                watchdog.set_scratch(SCRATCH_TIME_IDX, seconds);
+                watchdog.set_scratch(SCRATCH_MAGIC_IDX, SCRATCH_MAGIC);
+            }
+            Either::Second(TaskCommand::SetTime(hour, minute, second)) => {
+                log::info!("Setting time to {}:{}:{}", hour, minute, second);
+                set_time(
+                    &mut rtc,
+                    hour as u32 * 3600 + minute as u32 * 60 + second as u32,
+                );
+            }
+            Either::Second(_) => {}
+        }
+    }
+}
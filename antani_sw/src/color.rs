@@ -0,0 +1,381 @@
+// color math without any hardware behind it, shared by the shaders and the output
+// of the LedMatrix, the tests run on a pc with `cargo test` in minibadge-sim
+//
+// the output works with 16 bits per channel and the gains in 16.16 fixed point,
+// 1.0 = 65536, so a full gain keeps 65535 without overflowing a u32
+
+#[cfg(not(feature = "std"))]
+use num_traits::real::Real;
+
+use crate::LedPixel;
+
+// the color of a black body from 1000 K to 12000 K every 500 K, relative to the 6500 K
+// white of the leds, from Tanner Helland's fit of the CIE tables
+const WHITE_POINTS: [[u8; 3]; 23] = [
+    [255, 68, 0],
+    [255, 109, 0],
+    [255, 137, 14],
+    [255, 160, 71],
+    [255, 178, 112],
+    [255, 193, 144],
+    [255, 207, 169],
+    [255, 218, 191],
+    [255, 229, 210],
+    [255, 238, 227],
+    [255, 247, 242],
+    [255, 255, 255],
+    [243, 243, 255],
+    [230, 236, 255],
+    [221, 231, 255],
+    [215, 227, 255],
+    [210, 224, 255],
+    [205, 221, 255],
+    [202, 219, 255],
+    [199, 217, 255],
+    [196, 215, 255],
+    [193, 214, 255],
+    [191, 212, 255],
+];
+
+/// gamma correction tables of red, green, blue and white, with 16 bits outputs so
+/// the dim colors are not rounded to black
+pub struct GammaTable {
+    tables: [[u16; 256]; 4],
+}
+
+impl GammaTable {
+    pub fn new(gamma: [f32; 3]) -> Self {
+        // the white led has no calibration of its own
+        let white = (gamma[0] + gamma[1] + gamma[2]) / 3.0;
+        let gamma = [gamma[0], gamma[1], gamma[2], white];
+
+        let mut tables = [[0; 256]; 4];
+        for (table, gamma) in tables.iter_mut().zip(gamma) {
+            for (i, value) in table.iter_mut().enumerate() {
+                *value = ((i as f32 / 255.0).powf(gamma) * 65535.0 + 0.5) as u16;
+            }
+        }
+
+        Self { tables }
+    }
+
+    /// channel 0 is red, 1 green, 2 blue, 3 white, value goes from 0 to 65535
+    pub fn correct(&self, channel: usize, value: u16) -> u16 {
+        let table = &self.tables[channel];
+        // the entries are 257 values apart, like the ones made by expand(), in 8.8 fixed point
+        let position = value as u32 * 255 * 256 / 65535;
+        let low = (position >> 8) as usize;
+        let high = (low + 1).min(255);
+
+        // linear between the two closest entries
+        let fraction = position & 0xFF;
+        ((table[low] as u32 * (256 - fraction) + table[high] as u32 * fraction) >> 8) as u16
+    }
+}
+
+/// a gain from 0.0 to 1.0 in fixed point, anything outside is clamped
+pub fn gain(gain: f32) -> u32 {
+    (gain.clamp(0.0, 1.0) * 65536.0) as u32
+}
+
+/// 8 bits to 16 bits, 255 becomes 65535
+pub fn expand(value: u8) -> u16 {
+    value as u16 * 257
+}
+
+/// value times a gain made by gain()
+pub fn apply_gain(value: u16, gain: u32) -> u16 {
+    ((value as u32 * gain) >> 16) as u16
+}
+
+/// value times scale / 255, like the scales of the calibration
+pub fn scale(value: u16, scale: u8) -> u16 {
+    (value as u32 * scale as u32 / 255) as u16
+}
+
+/// mix of two 16 bits values, amount is a gain made by gain(), 0 = all a, 65536 = all b
+pub fn mix(a: u16, b: u16, amount: u32) -> u16 {
+    ((a as u32 * (65536 - amount) + b as u32 * amount) >> 16) as u16
+}
+
+/// scales the channels down so none is above ceiling, keeping their ratios and so the hue
+pub fn limit(channels: [u16; 4], ceiling: u16) -> [u16; 4] {
+    let peak = channels.iter().copied().max().unwrap_or(0);
+    if peak <= ceiling {
+        return channels;
+    }
+    channels.map(|c| (c as u32 * ceiling as u32 / peak as u32) as u16)
+}
+
+/// r g b scales that turn the white of the leds into the white of a light at kelvin
+pub fn white_point(kelvin: f32) -> [u8; 3] {
+    let position = (kelvin.clamp(1000.0, 12000.0) - 1000.0) / 500.0;
+    let low = position as usize;
+    let high = (low + 1).min(WHITE_POINTS.len() - 1);
+    let fraction = position - low as f32;
+
+    core::array::from_fn(|c| {
+        let (a, b) = (WHITE_POINTS[low][c] as f32, WHITE_POINTS[high][c] as f32);
+        (a + (b - a) * fraction + 0.5) as u8
+    })
+}
+
+/// every channel times its scale, 255 = unchanged
+pub fn tint(color: LedPixel, scale: [u8; 3]) -> LedPixel {
+    let channel = |c: u8, s: u8| (c as u16 * s as u16 / 255) as u8;
+    (
+        channel(color.r, scale[0]),
+        channel(color.g, scale[1]),
+        channel(color.b, scale[2]),
+    )
+        .into()
+}
+
+/// the color with its brightness multiplied by level, from 0.0 to 1.0
+pub fn dim(color: LedPixel, level: f32) -> LedPixel {
+    let c = (
+        color.r as f32 * level,
+        color.g as f32 * level,
+        color.b as f32 * level,
+    );
+    (c.0 as u8, c.1 as u8, c.2 as u8).into()
+}
+
+/// interpolation between two colors, amount 0.0 = all a, 1.0 = all b
+pub fn lerp(a: LedPixel, b: LedPixel, amount: f32) -> LedPixel {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount) as u8;
+    (channel(a.r, b.r), channel(a.g, b.g), channel(a.b, b.b)).into()
+}
+
+/// three pixels in a row through a [side, 1 - 2 * side, side] kernel, the middle one blurred
+pub fn smooth(previous: LedPixel, pixel: LedPixel, next: LedPixel, side: f32) -> LedPixel {
+    let channel = |a: u8, b: u8, c: u8| {
+        ((a as f32 + c as f32) * side + b as f32 * (1.0 - 2.0 * side) + 0.5) as u8
+    };
+
+    (
+        channel(previous.r, pixel.r, next.r),
+        channel(previous.g, pixel.g, next.g),
+        channel(previous.b, pixel.b, next.b),
+    )
+        .into()
+}
+
+/// a color along evenly spaced colors, value 0.0 = the first one, 1.0 = the last one
+pub fn ramp(colors: &[LedPixel], value: f32) -> LedPixel {
+    let Some(last) = colors.len().checked_sub(1) else {
+        return LedPixel::default();
+    };
+
+    let position = value.clamp(0.0, 1.0) * last as f32;
+    let low = position as usize;
+    let high = (low + 1).min(last);
+
+    lerp(colors[low], colors[high], position - low as f32)
+}
+
+/// fully saturated color of a hue in turns, same as hsv with s = 1.0 and v = 1.0
+pub fn hue_wheel(hue: f32) -> LedPixel {
+    // 6 sectors of 256 steps, in each one a channel goes up or down
+    let hue = ((hue - hue.floor()) * 1536.0) as u32 % 1536;
+    let rising = (hue & 0xFF) as u8;
+    let falling = 255 - rising;
+
+    match hue >> 8 {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, falling),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_keeps_black_and_full() {
+        let table = GammaTable::new([2.8, 2.2, 1.0]);
+        for channel in 0..4 {
+            assert_eq!(table.correct(channel, 0), 0);
+            assert_eq!(table.correct(channel, 65535), 65535);
+        }
+    }
+
+    #[test]
+    fn gamma_looks_up_the_entries() {
+        let table = GammaTable::new([2.8, 2.2, 1.8]);
+        for channel in 0..4 {
+            for value in [0, 1, 128, 254, 255] {
+                assert_eq!(
+                    table.correct(channel, expand(value)),
+                    table.tables[channel][value as usize]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_is_monotonic() {
+        let table = GammaTable::new([2.8; 3]);
+        let mut last = 0;
+        for value in (0..=65535).step_by(97) {
+            let corrected = table.correct(0, value);
+            assert!(corrected >= last, "{} goes down at {}", corrected, value);
+            last = corrected;
+        }
+    }
+
+    #[test]
+    fn gamma_one_is_linear() {
+        let table = GammaTable::new([1.0; 3]);
+        for value in (0..=65535u16).step_by(251) {
+            let corrected = table.correct(2, value);
+            assert!(
+                corrected.abs_diff(value) <= 256,
+                "{} -> {}",
+                value,
+                corrected
+            );
+        }
+    }
+
+    #[test]
+    fn full_gain_does_not_overflow() {
+        assert_eq!(apply_gain(expand(255), gain(1.0)), 65535);
+        assert_eq!(apply_gain(expand(255), gain(2.0)), 65535);
+        assert_eq!(apply_gain(expand(255), gain(0.0)), 0);
+        assert_eq!(apply_gain(expand(255), gain(-1.0)), 0);
+        assert_eq!(apply_gain(65535, gain(0.5)), 32767);
+    }
+
+    #[test]
+    fn scale_255_is_unchanged() {
+        assert_eq!(scale(65535, 255), 65535);
+        assert_eq!(scale(1234, 255), 1234);
+        assert_eq!(scale(65535, 0), 0);
+    }
+
+    #[test]
+    fn mix_ends_are_the_inputs() {
+        assert_eq!(mix(1000, 65535, 0), 1000);
+        assert_eq!(mix(1000, 65535, gain(1.0)), 65535);
+        assert_eq!(mix(0, 65535, gain(0.5)), 32767);
+    }
+
+    #[test]
+    fn limit_keeps_the_hue() {
+        assert_eq!(limit([65535, 32768, 0, 0], 32768), [32768, 16384, 0, 0]);
+        assert_eq!(limit([1000, 2000, 3000, 0], 32768), [1000, 2000, 3000, 0]);
+        assert_eq!(limit([65535; 4], 0), [0; 4]);
+    }
+
+    #[test]
+    fn white_point_of_the_leds_is_unchanged() {
+        assert_eq!(white_point(6500.0), [255; 3]);
+        let white: LedPixel = (255, 255, 255).into();
+        assert_eq!(tint(white, white_point(6500.0)), white);
+    }
+
+    #[test]
+    fn white_point_is_clamped() {
+        assert_eq!(white_point(0.0), white_point(1000.0));
+        assert_eq!(white_point(1e6), white_point(12000.0));
+        assert_eq!(white_point(f32::MAX), [191, 212, 255]);
+    }
+
+    #[test]
+    fn warmer_is_less_blue() {
+        let mut last = 0;
+        for kelvin in (1000..=6500).step_by(100) {
+            let blue = white_point(kelvin as f32)[2];
+            assert!(blue >= last, "{} K is bluer than the one before", kelvin);
+            last = blue;
+        }
+    }
+
+    #[test]
+    fn tint_scales_the_channels() {
+        let white: LedPixel = (255, 255, 255).into();
+        assert_eq!(tint(white, [255, 128, 0]), (255, 128, 0).into());
+        assert_eq!(
+            tint((100, 100, 100).into(), [255; 3]),
+            (100, 100, 100).into()
+        );
+    }
+
+    #[test]
+    fn smooth_spreads_a_bright_pixel() {
+        let black = LedPixel::default();
+        let white: LedPixel = (255, 255, 255).into();
+
+        assert_eq!(smooth(black, white, black, 0.25), (128, 128, 128).into());
+        assert_eq!(smooth(white, black, black, 0.25), (64, 64, 64).into());
+        assert_eq!(smooth(white, black, white, 0.0), black);
+    }
+
+    #[test]
+    fn smooth_keeps_flat_colors() {
+        let color: LedPixel = (10, 200, 255).into();
+        for side in [0.0, 0.1, 0.25] {
+            assert_eq!(smooth(color, color, color, side), color);
+        }
+    }
+
+    #[test]
+    fn ramp_goes_through_the_colors() {
+        let colors: [LedPixel; 3] = [(0, 0, 255).into(), (0, 255, 0).into(), (255, 0, 0).into()];
+
+        assert_eq!(ramp(&colors, 0.0), colors[0]);
+        assert_eq!(ramp(&colors, 0.5), colors[1]);
+        assert_eq!(ramp(&colors, 1.0), colors[2]);
+        assert_eq!(ramp(&colors, 0.25), (0, 127, 127).into());
+    }
+
+    #[test]
+    fn ramp_clamps_the_value() {
+        let colors: [LedPixel; 2] = [(0, 0, 255).into(), (255, 0, 0).into()];
+
+        assert_eq!(ramp(&colors, -1.0), colors[0]);
+        assert_eq!(ramp(&colors, 2.0), colors[1]);
+        assert_eq!(ramp(&colors[..1], 0.7), colors[0]);
+        assert_eq!(ramp(&[], 0.7), LedPixel::default());
+    }
+
+    #[test]
+    fn dim_saturates() {
+        let white: LedPixel = (255, 255, 255).into();
+        assert_eq!(dim(white, 1.0), white);
+        assert_eq!(dim(white, 0.0), (0, 0, 0).into());
+        // float to int casts saturate, an overdriven level stays white
+        assert_eq!(dim(white, 1.5), white);
+    }
+
+    #[test]
+    fn lerp_ends_are_the_inputs() {
+        let a: LedPixel = (255, 0, 10).into();
+        let b: LedPixel = (0, 255, 10).into();
+        assert_eq!(lerp(a, b, 0.0), a);
+        assert_eq!(lerp(a, b, 1.0), b);
+        assert_eq!(lerp(a, b, 0.5), (127, 127, 10).into());
+    }
+
+    #[test]
+    fn hue_wheel_primaries() {
+        assert_eq!(hue_wheel(0.0), (255, 0, 0).into());
+        assert_eq!(hue_wheel(1.0 / 3.0), (0, 255, 0).into());
+        assert_eq!(hue_wheel(2.0 / 3.0), (0, 0, 255).into());
+    }
+
+    #[test]
+    fn hue_wheel_wraps() {
+        assert_eq!(hue_wheel(1.0), hue_wheel(0.0));
+        assert_eq!(hue_wheel(2.25), hue_wheel(0.25));
+        assert_eq!(hue_wheel(-0.25), hue_wheel(0.75));
+        // just before the wrap it's back to red, with a bit of blue
+        assert_eq!(hue_wheel(0.99), (255, 0, 15).into());
+    }
+}
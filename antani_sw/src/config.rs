@@ -0,0 +1,392 @@
+use core::cell::Cell;
+
+use embassy_futures::select::{select, Either};
+use embassy_rp::flash::{Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::boot_logo::{self, BootLogo};
+use crate::calibration::{self, Calibration};
+use crate::editor::SceneEditor;
+use crate::records::{
+    crc32, Config, RecordHeader, Ring, CONFIG_VERSION, LEGACY_CONFIG_SIZE, PAGE_SIZE,
+    RECORD_HEADER_SIZE,
+};
+use crate::{palette_table, reboot, recorder};
+use crate::{MegaSubscriber, TaskCommand};
+
+#[cfg(feature = "rp2040")]
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+#[cfg(feature = "rp2350")]
+pub const FLASH_SIZE: usize = 4 * 1024 * 1024;
+
+// the last 256K of the flash are reserved for user data, see memory.x
+// the old config uses the last sector, the user scenes the one before, then the old
+// calibration, the palette table, the sectors of the config store, the boot logo and the
+// sectors of the recordings
+const LEGACY_CONFIG_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+pub const USER_SCENES_OFFSET: u32 = LEGACY_CONFIG_OFFSET - ERASE_SIZE as u32;
+pub const CALIBRATION_OFFSET: u32 = USER_SCENES_OFFSET - ERASE_SIZE as u32;
+pub const PALETTE_TABLE_OFFSET: u32 = CALIBRATION_OFFSET - ERASE_SIZE as u32;
+const CONFIG_STORE_OFFSET: u32 = PALETTE_TABLE_OFFSET - (STORE_SECTORS * ERASE_SIZE) as u32;
+pub const BOOT_LOGO_OFFSET: u32 = CONFIG_STORE_OFFSET - ERASE_SIZE as u32;
+pub const RECORDINGS_OFFSET: u32 = BOOT_LOGO_OFFSET - (RECORDING_SECTORS * ERASE_SIZE) as u32;
+/// one recording in each, the newest takes the place of the oldest
+pub const RECORDING_SECTORS: usize = 4;
+
+// the config store is a ring of records, one per page of flash: every store writes the
+// page after the newest record, so a sector is erased once every slots_per_sector stores,
+// and the newest record with a good crc is the config, see records.rs for the records
+const STORE_SECTORS: usize = 4;
+const RING: Ring = Ring {
+    sectors: STORE_SECTORS,
+    slots_per_sector: ERASE_SIZE / PAGE_SIZE,
+};
+const SLOTS: usize = RING.slots();
+
+// a change is written once the config stayed the same for a while, so going through the
+// scenes or the brightness levels writes one record instead of one per press
+const COMMIT_DELAY: Duration = Duration::from_secs(5);
+// the longest a change waits for the others, so a stream of them doesn't hold it forever
+const MAX_COMMIT_DELAY: Duration = Duration::from_secs(60);
+
+pub type BadgeFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+/// the writes of the config store, logged with the stats
+#[derive(Clone, Copy, Default)]
+struct Wear {
+    /// since the boot
+    records: u32,
+    erases: u32,
+    /// configs the same as the newest record, not written
+    unchanged: u32,
+    /// records written in the life of the store, from the newest sequence number
+    lifetime: u32,
+}
+
+static WEAR: Mutex<CriticalSectionRawMutex, Cell<Wear>> = Mutex::new(Cell::new(Wear {
+    records: 0,
+    erases: 0,
+    unchanged: 0,
+    lifetime: 0,
+}));
+
+fn count(f: impl FnOnce(&mut Wear)) {
+    WEAR.lock(|cell| {
+        let mut wear = cell.get();
+        f(&mut wear);
+        cell.set(wear);
+    });
+}
+
+/// logs the writes of the config store, every sector is erased once every SLOTS records
+/// and lasts about 100000 erases
+pub fn log_wear() {
+    let wear = WEAR.lock(Cell::get);
+    log::info!(
+        "stats flash records {} erases {} unchanged {} since boot, {} records in all, {} erases per sector",
+        wear.records,
+        wear.erases,
+        wear.unchanged,
+        wear.lifetime,
+        wear.lifetime / SLOTS as u32,
+    );
+}
+
+fn slot_offset(slot: usize) -> u32 {
+    CONFIG_STORE_OFFSET + (slot * PAGE_SIZE) as u32
+}
+
+fn read_page(flash: &mut BadgeFlash, slot: usize) -> Option<[u8; PAGE_SIZE]> {
+    let mut page = [0; PAGE_SIZE];
+
+    match flash.blocking_read(slot_offset(slot), &mut page) {
+        Ok(()) => Some(page),
+        Err(e) => {
+            log::error!("Error reading config record {}: {:?}", slot, e);
+            None
+        }
+    }
+}
+
+/// sequence number and slot of the newest record, good or not, so a page is never
+/// written twice without an erase
+fn newest_slot(flash: &mut BadgeFlash) -> Option<(u32, usize)> {
+    let mut newest: Option<(u32, usize)> = None;
+
+    for slot in 0..SLOTS {
+        let mut header = [0; RECORD_HEADER_SIZE];
+        if flash.blocking_read(slot_offset(slot), &mut header).is_err() {
+            continue;
+        }
+
+        if let Some(header) = RecordHeader::parse(&header) {
+            if !newest.is_some_and(|(seq, _)| seq >= header.seq) {
+                newest = Some((header.seq, slot));
+            }
+        }
+    }
+    newest
+}
+
+impl Config {
+    fn load_legacy(flash: &mut BadgeFlash) -> Self {
+        let mut data = [0; LEGACY_CONFIG_SIZE];
+
+        if let Err(e) = flash.blocking_read(LEGACY_CONFIG_OFFSET, &mut data) {
+            log::error!("Error reading config: {:?}", e);
+        }
+
+        let mut config = Self::from_legacy_bytes(&data).unwrap_or_else(|| {
+            log::warn!("No valid config found, using defaults");
+            Self::default()
+        });
+        config.calibration = Self::load_legacy_calibration(flash);
+        config
+    }
+
+    fn load_legacy_calibration(flash: &mut BadgeFlash) -> Calibration {
+        let mut data = [0; calibration::LEGACY_SIZE];
+
+        if let Err(e) = flash.blocking_read(CALIBRATION_OFFSET, &mut data) {
+            log::error!("Error reading calibration: {:?}", e);
+            return Calibration::default();
+        }
+        Calibration::from_legacy_bytes(&data)
+    }
+
+    /// reads the newest good record of the store, the config of the older firmwares
+    /// before the first one is written
+    pub fn load(flash: &mut BadgeFlash) -> Self {
+        let mut newest: Option<(u32, Self)> = None;
+
+        for slot in 0..SLOTS {
+            let Some(page) = read_page(flash, slot) else {
+                continue;
+            };
+            let Some(header) = RecordHeader::parse(&page) else {
+                continue;
+            };
+            if newest.as_ref().is_some_and(|(seq, _)| *seq >= header.seq) {
+                continue;
+            }
+
+            let data = &page[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + header.len];
+            if crc32(data) != header.crc {
+                log::warn!("Config record {} is corrupted", slot);
+                continue;
+            }
+
+            match Self::decode(header.version, data) {
+                Some(config) => newest = Some((header.seq, config)),
+                None => log::warn!(
+                    "Config record {} has unknown version {}",
+                    slot,
+                    header.version
+                ),
+            }
+        }
+
+        match newest {
+            Some((seq, config)) => {
+                count(|wear| wear.lifetime = seq.wrapping_add(1));
+                config
+            }
+            None => {
+                log::info!("No config in the store, reading the old one");
+                Self::load_legacy(flash)
+            }
+        }
+    }
+
+    /// writes a new record after the newest one
+    pub fn store(&self, flash: &mut BadgeFlash) {
+        let mut page = [0xFF; PAGE_SIZE];
+
+        let len = match postcard::to_slice(self, &mut page[RECORD_HEADER_SIZE..]) {
+            Ok(encoded) => encoded.len(),
+            Err(e) => {
+                log::error!("Error encoding config: {:?}", e);
+                return;
+            }
+        };
+        let crc = crc32(&page[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + len]);
+
+        let newest = newest_slot(flash);
+
+        // the same config as the newest record, the flash is left alone
+        let unchanged = newest
+            .and_then(|(_, slot)| read_page(flash, slot))
+            .is_some_and(|p| {
+                RecordHeader::parse(&p).is_some_and(|header| {
+                    header.version == CONFIG_VERSION && header.crc == crc && header.len == len
+                }) && p[RECORD_HEADER_SIZE..][..len] == page[RECORD_HEADER_SIZE..][..len]
+            });
+        if unchanged {
+            log::info!("Config unchanged, not written");
+            count(|wear| wear.unchanged += 1);
+            return;
+        }
+
+        let (seq, slot) = RING.next(newest, |slot| {
+            read_page(flash, slot).is_some_and(|p| p.iter().all(|b| *b == 0xFF))
+        });
+
+        if RING.starts_sector(slot) {
+            let sector = slot_offset(slot);
+            if let Err(e) = flash.blocking_erase(sector, sector + ERASE_SIZE as u32) {
+                log::error!("Error erasing config sector: {:?}", e);
+                return;
+            }
+            count(|wear| wear.erases += 1);
+        }
+
+        RecordHeader {
+            seq,
+            crc,
+            len,
+            version: CONFIG_VERSION,
+        }
+        .write(&mut page);
+
+        if let Err(e) = flash.blocking_write(slot_offset(slot), &page[..RECORD_HEADER_SIZE + len]) {
+            log::error!("Error writing config: {:?}", e);
+            return;
+        }
+        count(|wear| {
+            wear.records += 1;
+            wear.lifetime = seq.wrapping_add(1);
+        });
+    }
+}
+
+#[embassy_executor::task]
+pub async fn config_tsk(mut flash: BadgeFlash, mut config: Config, mut subscriber: MegaSubscriber) {
+    // the editor lives here because saving a scene needs the flash
+    let mut editor = SceneEditor::default();
+    // the frames of a boot logo being uploaded, saved all at once
+    let mut boot_logo = BootLogo::default();
+    // when the config changed first and last, it's written after COMMIT_DELAY without other
+    // changes, or MAX_COMMIT_DELAY after the first one
+    let mut changed: Option<(Instant, Instant)> = None;
+
+    loop {
+        let message = match changed {
+            Some((first, last)) => {
+                let at = (last + COMMIT_DELAY).min(first + MAX_COMMIT_DELAY);
+                match select(subscriber.next_message_pure(), Timer::at(at)).await {
+                    Either::First(message) => message,
+                    Either::Second(()) => {
+                        log::info!("Saving config: {:?}", config);
+                        config.store(&mut flash);
+                        changed = None;
+                        continue;
+                    }
+                }
+            }
+            None => subscriber.next_message_pure().await,
+        };
+
+        match message {
+            TaskCommand::SetRandomBootScene(enabled) => config.random_boot_scene = enabled,
+            TaskCommand::SetPlayback(playback) => config.playback = playback,
+            TaskCommand::SetDemoMode(enabled) => config.demo_mode = enabled,
+            TaskCommand::SetDoNotDisturb(enabled) => config.do_not_disturb = enabled,
+            TaskCommand::SetFrameRate(fps) => config.fps = fps,
+            TaskCommand::SetMaxBrightness(percent) => config.max_brightness = percent,
+            TaskCommand::SetIrFilter(filter) => config.ir_filter = filter,
+            TaskCommand::EditScene(edit) => {
+                editor.apply(edit, &mut flash);
+                continue;
+            }
+            TaskCommand::Calibrate(edit) => config.calibration.apply(&edit),
+            TaskCommand::SaveState(brightness, scene) => {
+                config.brightness = brightness;
+                config.scene = scene;
+            }
+            TaskCommand::SetName(name) => config.name = name,
+            TaskCommand::SetInitials(initials) => config.initials = initials,
+            TaskCommand::SetQuietHours(quiet_hours) => config.quiet_hours = quiet_hours,
+            TaskCommand::SetStrip(strip) => config.strip = strip,
+            TaskCommand::SetChainedBadges(badges) => config.chained_badges = badges,
+            TaskCommand::FactoryReset => {
+                log::warn!("Factory reset");
+                // the calibration belongs to the badge, not to its owner
+                config = Config {
+                    calibration: config.calibration,
+                    ..Default::default()
+                };
+                config.store(&mut flash);
+
+                // an erased sector has no scenes, an empty palette table, no boot logo and no
+                // recording
+                for offset in [USER_SCENES_OFFSET, PALETTE_TABLE_OFFSET, BOOT_LOGO_OFFSET] {
+                    if let Err(e) = flash.blocking_erase(offset, offset + ERASE_SIZE as u32) {
+                        log::error!("Error erasing flash: {:?}", e);
+                    }
+                }
+                let recordings_end = RECORDINGS_OFFSET + (RECORDING_SECTORS * ERASE_SIZE) as u32;
+                if let Err(e) = flash.blocking_erase(RECORDINGS_OFFSET, recordings_end) {
+                    log::error!("Error erasing flash: {:?}", e);
+                }
+
+                // start over from the defaults
+                cortex_m::peripheral::SCB::sys_reset();
+            }
+            // here, so a config being saved is written before the restart
+            TaskCommand::Reboot(target) => {
+                if changed.is_some() {
+                    config.store(&mut flash);
+                }
+                // time for the main loop to turn the leds off
+                Timer::after_millis(100).await;
+                reboot::reboot(target);
+            }
+            TaskCommand::SetPaletteTable(table) => {
+                palette_table::store(&mut flash, &table);
+                continue;
+            }
+            TaskCommand::NewBootLogo => {
+                boot_logo = BootLogo::default();
+                continue;
+            }
+            TaskCommand::AddBootLogoFrame(frame) => {
+                if boot_logo.frames.push(frame).is_err() {
+                    log::warn!("Boot logo full, frame dropped");
+                }
+                continue;
+            }
+            TaskCommand::SaveBootLogo(frame_millis) => {
+                boot_logo.frame_millis = frame_millis;
+                log::info!("Saving boot logo, {} frames", boot_logo.frames.len());
+                boot_logo::store(&mut flash, &boot_logo);
+                boot_logo = BootLogo::default();
+                continue;
+            }
+            // the chip may lose power while asleep
+            TaskCommand::PowerOff => {
+                if changed.take().is_some() {
+                    config.store(&mut flash);
+                }
+                continue;
+            }
+            TaskCommand::SaveRecording => {
+                if let Some(recording) = recorder::FINISHED.try_take() {
+                    log::info!("Saving recording, {} frames", recording.frames.len());
+                    recorder::store(&mut flash, &recording);
+                }
+                continue;
+            }
+            TaskCommand::LogStats => {
+                log_wear();
+                continue;
+            }
+            _ => continue,
+        }
+
+        let now = Instant::now();
+        changed = Some((changed.map_or(now, |(first, _)| first), now));
+    }
+}
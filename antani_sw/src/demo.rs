@@ -0,0 +1,119 @@
+use embassy_time::{Duration, Instant};
+
+use crate::rgbeffects::{ColorPalette, Pattern, RenderCommand, RenderManager, RenderTarget};
+use crate::scenes::{ScenePlayer, Scenes};
+use crate::time::Time;
+use crate::{LedMatrix, OutputPower};
+
+const DEMO_SCENE_TIME: Duration = Duration::from_secs(10);
+const DEMO_FADE_TIME: Duration = Duration::from_secs(1);
+/// show the brightness and the firmware version every few scenes
+const DEMO_INDICATORS_EVERY: u32 = 6;
+const DEMO_INDICATOR_TIME: Duration = Duration::from_millis(1500);
+
+enum DemoPhase {
+    Scene,
+    Fade(usize, Instant), // next scene, fade start
+    Indicators(Instant),
+}
+
+/// attract mode for display stands, cycles all the scenes with crossfades
+pub struct Demo {
+    phase: DemoPhase,
+    scene_start: Instant,
+    scenes_shown: u32,
+}
+
+impl Default for Demo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Demo {
+    pub fn new() -> Self {
+        Self {
+            phase: DemoPhase::Scene,
+            scene_start: Instant::now(),
+            scenes_shown: 0,
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        renderman: &mut RenderManager<LedMatrix>,
+        scenes: &Scenes,
+        scene_id: &mut usize,
+        player: &mut ScenePlayer,
+        out_power: &OutputPower,
+        t: Time,
+    ) {
+        match self.phase {
+            DemoPhase::Scene => {
+                renderman.render(&scenes[*scene_id], t);
+
+                if self.scene_start.elapsed() > DEMO_SCENE_TIME {
+                    let next = player.next(*scene_id, scenes.len(), &mut renderman.rng);
+                    self.phase = DemoPhase::Fade(next, Instant::now());
+                }
+            }
+            DemoPhase::Fade(next, start) => {
+                let amount = start.elapsed().as_millis() as f32 / DEMO_FADE_TIME.as_millis() as f32;
+
+                renderman.render(&scenes[*scene_id], t);
+                let from = renderman.mtrx.raw_framebuffer;
+                renderman.mtrx.clear();
+                renderman.render(&scenes[next], t);
+                renderman.mtrx.fade_from(from, amount);
+
+                if amount >= 1.0 {
+                    *scene_id = next;
+                    self.scene_start = Instant::now();
+                    self.scenes_shown += 1;
+
+                    self.phase = if self.scenes_shown.is_multiple_of(DEMO_INDICATORS_EVERY) {
+                        DemoPhase::Indicators(Instant::now())
+                    } else {
+                        DemoPhase::Scene
+                    };
+                }
+            }
+            DemoPhase::Indicators(start) => {
+                let elapsed = start.elapsed();
+
+                // brightness first, then major, minor and patch version in red, green and blue
+                let version = [
+                    env!("CARGO_PKG_VERSION_MAJOR"),
+                    env!("CARGO_PKG_VERSION_MINOR"),
+                    env!("CARGO_PKG_VERSION_PATCH"),
+                ];
+                let version_colors = [(255, 0, 0), (0, 255, 0), (0, 0, 255)];
+
+                let step = (elapsed.as_millis() / DEMO_INDICATOR_TIME.as_millis()) as usize;
+
+                let command = match step {
+                    0 => RenderCommand {
+                        effect: Pattern::Simple(out_power.pattern()),
+                        ..Default::default()
+                    },
+                    1..=3 => {
+                        // one led per unit, an empty matrix is a zero
+                        let n = version[step - 1].parse::<u32>().unwrap_or(0).min(9);
+                        RenderCommand {
+                            effect: Pattern::Simple((1 << n) - 1),
+                            color: ColorPalette::Solid(version_colors[step - 1].into()),
+                            ..Default::default()
+                        }
+                    }
+                    _ => {
+                        self.scene_start = Instant::now();
+                        self.phase = DemoPhase::Scene;
+                        return renderman.render(&scenes[*scene_id], t);
+                    }
+                };
+
+                renderman.render(&[command], t);
+            }
+        }
+    }
+}
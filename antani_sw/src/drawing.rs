@@ -0,0 +1,80 @@
+// remote drawing: an event host with an ir blaster paints the badges one led at a time,
+// the drawing stays on top of the scenes until it's cleared
+//
+// a nec packet on DRAW_IR_ADDRESS, the high nibble of the command is the led, from 0 to 8
+// row by row from the top left, ALL_LEDS for every led or CLEAR to remove the drawing, the
+// low nibble is the color, one of COLORS
+
+use crate::{LedPixel, RawFramebuffer, LED_MATRIX_SIZE};
+
+pub const DRAW_IR_ADDRESS: u8 = 0x55;
+
+const ALL_LEDS: u8 = 0x9;
+const CLEAR: u8 = 0xF;
+
+// black turns a led off on top of the scene
+const COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (255, 0, 0),
+    (255, 96, 0),
+    (255, 200, 0),
+    (128, 255, 0),
+    (0, 255, 0),
+    (0, 255, 160),
+    (0, 200, 255),
+    (0, 0, 255),
+    (128, 0, 255),
+    (255, 0, 255),
+    (255, 64, 128),
+    (255, 255, 255),
+    (255, 160, 64),
+    (64, 0, 0),
+    (0, 0, 64),
+];
+
+#[derive(Default)]
+pub struct Drawing {
+    leds: [Option<LedPixel>; LED_MATRIX_SIZE],
+}
+
+impl Drawing {
+    /// a packet on DRAW_IR_ADDRESS
+    pub fn received(&mut self, cmd: u8) {
+        let color = Some(COLORS[(cmd & 0xF) as usize].into());
+
+        match cmd >> 4 {
+            CLEAR => self.leds = Default::default(),
+            ALL_LEDS => self.leds = [color; LED_MATRIX_SIZE],
+            led if (led as usize) < LED_MATRIX_SIZE => self.leds[led as usize] = color,
+            _ => log::warn!("Unknown drawing command {:#04x}", cmd),
+        }
+    }
+
+    pub fn draw(&self, fb: &mut RawFramebuffer) {
+        for (led, color) in self.leds.iter().enumerate() {
+            // x is the row from the bottom, like the bits of the patterns
+            if let Some(color) = color {
+                fb.set_pixel(2 - led / 3, led % 3, *color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners() {
+        // led, where it is drawn
+        for (led, x, y) in [(0, 2, 0), (2, 2, 2), (6, 0, 0), (8, 0, 2)] {
+            let mut drawing = Drawing::default();
+            drawing.received((led << 4) | 1);
+
+            let mut fb = RawFramebuffer::new();
+            drawing.draw(&mut fb);
+            assert_eq!(fb.get_pixel(x, y), COLORS[1].into());
+            assert_eq!(fb.framebuffer.iter().filter(|p| p.r > 0).count(), 1);
+        }
+    }
+}
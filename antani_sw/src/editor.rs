@@ -0,0 +1,127 @@
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use heapless::Vec;
+
+use crate::config::BadgeFlash;
+use crate::rgbeffects::{
+    ColorPalette, FragmentShader, LedPattern, RenderCommand, Sensor, MAX_PALETTE_COLORS,
+};
+use crate::scenes::Scene;
+use crate::user_scenes::{self, StoredLayer, StoredPattern, StoredScene};
+use crate::LedPixel;
+
+/// scene to show instead of the current one while editing, None when done
+pub static PREVIEW: Signal<CriticalSectionRawMutex, Option<Scene>> = Signal::new();
+
+#[derive(Clone, Debug)]
+pub enum SceneEdit {
+    New,
+    AddLayer,
+    SetPattern(LedPattern),
+    SetColor(LedPixel),
+    SetPalette(Vec<LedPixel, MAX_PALETTE_COLORS>, f32), // colors, speed
+    AddShader(FragmentShader, bool),                    // shader, screen shader
+    SetSensorPalette(Sensor, Vec<LedPixel, MAX_PALETTE_COLORS>),
+    SetIndexedPalette(f32),              // speed
+    SetDuotone(LedPixel, LedPixel, f32), // a, b, bias
+    Save,
+    Discard,
+}
+
+/// builds a scene one command at a time, then saves it with the user scenes
+#[derive(Default)]
+pub struct SceneEditor {
+    scene: Option<StoredScene>,
+}
+
+impl SceneEditor {
+    pub fn apply(&mut self, edit: SceneEdit, flash: &mut BadgeFlash) {
+        if let SceneEdit::New = edit {
+            let mut scene = StoredScene::new();
+            scene.push(StoredLayer::default()).ok();
+            self.scene = Some(scene);
+            self.show();
+            return;
+        }
+
+        let Some(scene) = &mut self.scene else {
+            log::warn!("No scene is being edited, start a new one first");
+            return;
+        };
+
+        match edit {
+            SceneEdit::AddLayer => {
+                if scene.push(StoredLayer::default()).is_err() {
+                    log::warn!("The scene already has the maximum number of layers");
+                }
+            }
+            SceneEdit::Save => {
+                let mut stored = user_scenes::read(flash);
+
+                if stored.push(scene.clone()).is_err() {
+                    log::warn!("No space left for user scenes");
+                    return;
+                }
+
+                user_scenes::store(flash, &stored);
+                log::info!("Scene saved, it will be available at the next boot");
+
+                self.scene = None;
+                PREVIEW.signal(None);
+                return;
+            }
+            SceneEdit::Discard => {
+                self.scene = None;
+                PREVIEW.signal(None);
+                return;
+            }
+            edit => {
+                // there is always at least one layer
+                let Some(layer) = scene.last_mut() else {
+                    return;
+                };
+
+                match edit {
+                    SceneEdit::SetPattern(pattern) => layer.effect = StoredPattern::Simple(pattern),
+                    SceneEdit::SetColor(color) => layer.color = ColorPalette::Solid(color),
+                    SceneEdit::SetPalette(colors, speed) => {
+                        layer.color = if colors.is_empty() {
+                            ColorPalette::Rainbow(speed)
+                        } else {
+                            ColorPalette::Custom(colors, speed)
+                        };
+                    }
+                    SceneEdit::SetIndexedPalette(speed) => {
+                        layer.color = ColorPalette::Indexed(speed);
+                    }
+                    SceneEdit::SetDuotone(a, b, bias) => {
+                        layer.color = ColorPalette::Duotone(a, b, bias);
+                    }
+                    SceneEdit::SetSensorPalette(sensor, colors) => {
+                        layer.color = ColorPalette::Sensor(sensor, colors);
+                    }
+                    SceneEdit::AddShader(shader, screen) => {
+                        let shaders = if screen {
+                            &mut layer.screen_shaders
+                        } else {
+                            &mut layer.pattern_shaders
+                        };
+
+                        if shaders.push(shader).is_err() {
+                            log::warn!("The layer already has the maximum number of shaders");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.show();
+    }
+
+    fn show(&self) {
+        if let Some(scene) = &self.scene {
+            PREVIEW.signal(Some(scene.iter().map(RenderCommand::from).collect()));
+        }
+    }
+}
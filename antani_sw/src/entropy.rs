@@ -0,0 +1,64 @@
+use embassy_rp::adc::{Adc, Async, Channel};
+use embassy_rp::clocks::RoscRng;
+use rand::RngCore;
+
+/// mixes a few weak sources of randomness into a seed
+pub struct Entropy {
+    state: u64,
+}
+
+impl Default for Entropy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Entropy {
+    pub fn new() -> Self {
+        Self {
+            state: 0x9E3779B97F4A7C15,
+        }
+    }
+
+    /// every bit of the input changes about half the bits of the state
+    pub fn mix(&mut self, value: u64) {
+        // splitmix64 finalizer
+        let mut z = (self.state ^ value).wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        self.state = z ^ (z >> 31);
+    }
+
+    /// the ring oscillator gives one random bit per read, from its jitter
+    pub fn collect_rosc(&mut self, words: usize) {
+        for _ in 0..words {
+            self.mix(RoscRng.next_u64());
+        }
+    }
+
+    /// only the lowest bit of the adc is noise, the others follow the temperature
+    pub fn collect_adc(
+        &mut self,
+        adc: &mut Adc<'_, Async>,
+        channel: &mut Channel<'_>,
+        bits: usize,
+    ) {
+        let mut word = 0u64;
+
+        for i in 0..bits {
+            if let Ok(sample) = adc.blocking_read(channel) {
+                word = (word << 1) | (sample & 1) as u64;
+            }
+
+            if i % 64 == 63 {
+                self.mix(word);
+            }
+        }
+
+        self.mix(word);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.state
+    }
+}
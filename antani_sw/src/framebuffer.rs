@@ -0,0 +1,145 @@
+// the pixels drawn by the effects, with no hardware behind them so the
+// simulator in minibadge-sim can use them too
+
+use serde::{Deserialize, Serialize};
+
+pub const LED_MATRIX_WIDTH: usize = crate::boards::MATRIX_WIDTH;
+pub const LED_MATRIX_HEIGHT: usize = crate::boards::MATRIX_HEIGHT;
+pub const LED_MATRIX_SIZE: usize = LED_MATRIX_WIDTH * LED_MATRIX_HEIGHT;
+/// set to true if RGBW leds, false if RGB
+pub const HAS_WHITE_LED: bool = false;
+/// badges in a daisy chain at most, the first one included: the data out pad of a
+/// matrix drives the leds of the next badge, so the chain is one long display
+pub const MAX_CHAINED_BADGES: usize = 8;
+pub const CHAIN_SIZE: usize = LED_MATRIX_SIZE * MAX_CHAINED_BADGES;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LedPixel {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+}
+
+impl LedPixel {
+    pub fn set_white(&mut self) {
+        // create white channel from rgb
+        if self.r == self.g && self.g == self.b {
+            self.w = self.r;
+            self.r = 0;
+            self.g = 0;
+            self.b = 0;
+        }
+    }
+}
+
+impl From<(u8, u8, u8)> for LedPixel {
+    fn from(rgb: (u8, u8, u8)) -> Self {
+        Self {
+            r: rgb.0,
+            g: rgb.1,
+            b: rgb.2,
+            w: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RawFramebuffer {
+    pub framebuffer: [LedPixel; LED_MATRIX_SIZE],
+}
+
+impl RawFramebuffer {
+    pub fn new() -> Self {
+        Self {
+            framebuffer: [LedPixel::default(); LED_MATRIX_SIZE],
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
+        if x < LED_MATRIX_WIDTH && y < LED_MATRIX_HEIGHT {
+            let color = LedPixel {
+                r: colour.r,
+                g: colour.g,
+                b: colour.b,
+                w: 0,
+            };
+            self.framebuffer[y * LED_MATRIX_WIDTH + x] = color;
+        }
+    }
+
+    /// rgb bytes of the leds row by row from the top left, like minibadge-cli
+    /// --frame-buffer, the missing leds stay black
+    pub fn from_rgb(data: &[u8]) -> Self {
+        let mut framebuffer = Self::new();
+        for (i, rgb) in data.chunks_exact(3).take(LED_MATRIX_SIZE).enumerate() {
+            framebuffer.set_pixel(
+                i % LED_MATRIX_WIDTH,
+                i / LED_MATRIX_WIDTH,
+                (rgb[0], rgb[1], rgb[2]).into(),
+            );
+        }
+        framebuffer
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
+        if x < LED_MATRIX_WIDTH && y < LED_MATRIX_HEIGHT {
+            self.framebuffer[y * LED_MATRIX_WIDTH + x]
+        } else {
+            LedPixel::default()
+        }
+    }
+
+    pub fn set_all(&mut self, rgb: LedPixel) {
+        self.framebuffer.iter_mut().for_each(|led| *led = rgb);
+    }
+
+    /// turns the image clockwise by 90 degrees for every quarter turn
+    pub fn rotate(&self, quarter_turns: u8) -> RawFramebuffer {
+        let mut ret = *self;
+        for _ in 0..quarter_turns % 4 {
+            let src = ret;
+            for y in 0..LED_MATRIX_HEIGHT {
+                for x in 0..LED_MATRIX_WIDTH {
+                    ret.set_pixel(x, y, src.get_pixel(y, LED_MATRIX_WIDTH - 1 - x));
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// the framebuffers of the badges of a chain side by side, the first one on the left,
+/// for the effects that keep every pixel of the longest chain
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ChainFramebuffer {
+    pub badges: [RawFramebuffer; MAX_CHAINED_BADGES],
+}
+
+impl ChainFramebuffer {
+    pub fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
+        if let Some(badge) = self.badges.get_mut(x / LED_MATRIX_WIDTH) {
+            badge.set_pixel(x % LED_MATRIX_WIDTH, y, colour);
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
+        match self.badges.get(x / LED_MATRIX_WIDTH) {
+            Some(badge) => badge.get_pixel(x % LED_MATRIX_WIDTH, y),
+            None => LedPixel::default(),
+        }
+    }
+}
+
+// same format accepted by minibadge-cli --frame-buffer
+impl core::fmt::Display for RawFramebuffer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, led) in self.framebuffer.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "#{:02x}{:02x}{:02x}", led.r, led.g, led.b)?;
+        }
+        Ok(())
+    }
+}
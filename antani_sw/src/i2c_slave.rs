@@ -0,0 +1,135 @@
+use embassy_rp::bind_interrupts;
+use embassy_rp::i2c::InterruptHandler;
+use embassy_rp::i2c_slave::{Command, Config, I2cSlave};
+use embassy_rp::peripherals::I2C1;
+
+use crate::{
+    LedPixel, MegaPublisher, OutputPower, RawFramebuffer, TaskCommand, HOST_FRAME_SIGNAL,
+    LED_MATRIX_SIZE,
+};
+
+// the badge works as a display module on I2C1, SDA on GPIO2 and SCL on GPIO3
+bind_interrupts!(pub struct Irqs {
+    I2C1_IRQ => InterruptHandler<I2C1>;
+});
+
+pub const ADDRESS: u16 = 0x42;
+
+// registers, the first byte of a write selects the register and the next ones
+// are written from there on, reads start from the selected register
+const REG_SCENE: usize = 0x00; // index of the scene to show
+const REG_GAIN: usize = 0x01; // 0 = night mode .. 3 = full brightness
+const REG_PIXELS: usize = 0x02; // r, g, b of the 9 leds, row by row from the top left
+const REG_COUNT: usize = REG_PIXELS + LED_MATRIX_SIZE * 3;
+
+/// the frame is shown when its last byte is written, so a full frame is a single transaction
+struct Registers {
+    data: [u8; REG_COUNT],
+}
+
+impl Registers {
+    fn new() -> Self {
+        let mut data = [0; REG_COUNT];
+        data[REG_GAIN] = 3;
+        Self { data }
+    }
+
+    /// returns which of scene, gain and frame have to be updated
+    fn write(&mut self, start: usize, bytes: &[u8]) -> [bool; 3] {
+        let mut touched = [false; 3];
+
+        for (i, byte) in bytes.iter().enumerate() {
+            let reg = start + i;
+            let Some(slot) = self.data.get_mut(reg) else {
+                break;
+            };
+            *slot = *byte;
+
+            match reg {
+                REG_SCENE => touched[0] = true,
+                REG_GAIN => touched[1] = true,
+                _ => touched[2] = reg == REG_COUNT - 1 || touched[2],
+            }
+        }
+
+        touched
+    }
+
+    fn framebuffer(&self) -> RawFramebuffer {
+        let mut fb = RawFramebuffer::new();
+        for (i, rgb) in self.data[REG_PIXELS..].chunks_exact(3).enumerate() {
+            fb.framebuffer[i] = LedPixel {
+                r: rgb[0],
+                g: rgb[1],
+                b: rgb[2],
+                w: 0,
+            };
+        }
+        fb
+    }
+}
+
+#[embassy_executor::task]
+pub async fn i2c_slave_tsk(mut dev: I2cSlave<'static, I2C1>, publisher: MegaPublisher) {
+    let mut registers = Registers::new();
+    // register pointer, set by the first byte of every write
+    let mut pointer = 0;
+
+    loop {
+        let mut buf = [0; REG_COUNT + 1];
+
+        let command = match dev.listen(&mut buf).await {
+            Ok(command) => command,
+            Err(e) => {
+                log::error!("I2C slave error: {:?}", e);
+                continue;
+            }
+        };
+
+        match command {
+            Command::Write(len) | Command::WriteRead(len) if len > 0 => {
+                pointer = buf[0] as usize;
+            }
+            _ => {}
+        }
+
+        let written: &[u8] = match command {
+            Command::Write(len) if len > 1 => &buf[1..len],
+            _ => &[],
+        };
+
+        let [scene, gain, pixels] = registers.write(pointer, written);
+        pointer += written.len();
+
+        if scene {
+            publisher
+                .publish(TaskCommand::SetScene(registers.data[REG_SCENE]))
+                .await;
+        }
+
+        if gain {
+            // 0 to 3 like the four levels the badge used to have
+            let power = OutputPower::scaled(registers.data[REG_GAIN], 3);
+            publisher.publish(TaskCommand::SetBrightness(power)).await;
+        }
+
+        if pixels {
+            // same path as the frames streamed over usb
+            HOST_FRAME_SIGNAL.signal(registers.framebuffer());
+        }
+
+        if let Command::Read | Command::WriteRead(_) = command {
+            let data = registers.data.get(pointer..).unwrap_or(&[]);
+
+            if let Err(e) = dev.respond_and_fill(data, 0).await {
+                log::error!("I2C slave error: {:?}", e);
+            }
+        }
+    }
+}
+
+pub fn config() -> Config {
+    let mut config = Config::default();
+    config.addr = ADDRESS;
+    config
+}
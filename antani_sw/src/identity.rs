@@ -0,0 +1,87 @@
+use core::fmt::Write;
+
+use embassy_sync::once_lock::OnceLock;
+use heapless::String;
+
+use crate::color::hue_wheel;
+use crate::config::BadgeFlash;
+use crate::LedPixel;
+
+// the nec address used to tell the other badges who we are, the command is our id
+pub const ID_IR_ADDRESS: u8 = 0x49;
+
+static IDENTITY: OnceLock<Identity> = OnceLock::new();
+
+/// derived from the unique id of the flash chip, so it's the same at every boot
+pub struct Identity {
+    hash: u64,
+    serial: String<16>,
+}
+
+/// reads the unique id of the flash, must be called once at boot before core1 is started
+#[cfg_attr(feature = "rp2350", allow(unused_variables))]
+pub fn init(flash: &mut BadgeFlash) {
+    let mut uid = [0; 8];
+    #[cfg(feature = "rp2040")]
+    if let Err(e) = flash.blocking_unique_id(&mut uid) {
+        log::warn!("Cannot read the flash unique id: {:?}", e);
+    }
+    // the rp2350 has a unique id of its own in the otp, used instead of the flash one
+    #[cfg(feature = "rp2350")]
+    match embassy_rp::otp::get_chipid() {
+        Ok(id) => uid = id.to_be_bytes(),
+        Err(e) => log::warn!("Cannot read the chip id: {:?}", e),
+    }
+
+    // fnv-1a, the ids of flash chips from the same batch are very similar
+    let hash = uid.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    let mut serial = String::new();
+    for byte in uid {
+        write!(serial, "{:02X}", byte).ok();
+    }
+
+    let identity = Identity { hash, serial };
+    log::info!("Badge id: {} ({})", identity.id(), identity.serial);
+
+    if IDENTITY.init(identity).is_err() {
+        log::warn!("Badge identity already initialized");
+    }
+}
+
+/// before init every badge looks the same
+pub fn get() -> &'static Identity {
+    static UNKNOWN: Identity = Identity {
+        hash: 0,
+        serial: String::new(),
+    };
+
+    IDENTITY.try_get().unwrap_or(&UNKNOWN)
+}
+
+impl Identity {
+    /// short id used by the games over IR
+    pub fn id(&self) -> u8 {
+        self.hash.to_le_bytes().iter().fold(0, |id, byte| id ^ byte)
+    }
+
+    /// every badge has its own color
+    pub fn hue(&self) -> f32 {
+        (self.hash >> 40) as u16 as f32 / 65536.0
+    }
+
+    pub fn color(&self) -> LedPixel {
+        hue_wheel(self.hue())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.hash
+    }
+
+    /// the flash unique id in hex, used as the usb serial number
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+}
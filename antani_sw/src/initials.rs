@@ -0,0 +1,107 @@
+// the initials of the owner, shown one letter at a time in place of the scene every few
+// seconds, so the badge works as a name badge too
+//
+// the letters are 3x3 glyphs, some of them only look like the letter in the company of the
+// others: the initials are set over usb and saved in the config, none = the scenes only
+
+use heapless::Vec;
+
+use crate::records::MAX_INITIALS;
+use crate::rgbeffects::{ColorPalette, LedPattern, Pattern, RenderCommand, RenderManager};
+use crate::time::Time;
+use crate::LedMatrix;
+
+/// seconds of the scene between the initials
+const SCENE_DURATION: f32 = 8.0;
+/// seconds of every letter, and of the blank after it, so a double letter shows as two
+const LETTER_DURATION: f32 = 0.8;
+const GAP_DURATION: f32 = 0.2;
+
+// a to z, rows from the top left like the other patterns
+static GLYPHS: [LedPattern; 26] = [
+    0b010_111_101, // a
+    0b110_111_110, // b
+    0b111_100_111, // c
+    0b110_101_110, // d
+    0b111_110_111, // e
+    0b111_110_100, // f
+    0b110_101_111, // g
+    0b101_111_101, // h
+    0b111_010_111, // i
+    0b001_101_111, // j
+    0b101_110_101, // k
+    0b100_100_111, // l
+    0b111_111_101, // m
+    0b110_101_101, // n
+    0b111_101_111, // o
+    0b111_111_100, // p
+    0b111_111_001, // q
+    0b110_110_101, // r
+    0b011_010_110, // s
+    0b111_010_010, // t
+    0b101_101_111, // u
+    0b101_101_010, // v
+    0b101_111_111, // w
+    0b101_010_101, // x
+    0b101_010_010, // y
+    0b110_010_011, // z
+];
+
+/// the letters of the initials out of any text, upper case, the rest is left out
+pub fn parse(text: &str) -> heapless::String<MAX_INITIALS> {
+    let mut initials = heapless::String::new();
+    for c in text.chars().filter(char::is_ascii_alphabetic) {
+        if initials.push(c.to_ascii_uppercase()).is_err() {
+            break;
+        }
+    }
+    initials
+}
+
+pub struct Initials {
+    glyphs: Vec<LedPattern, MAX_INITIALS>,
+}
+
+impl Initials {
+    pub fn new(initials: &str) -> Self {
+        Self {
+            glyphs: initials
+                .bytes()
+                .filter(u8::is_ascii_alphabetic)
+                .map(|c| GLYPHS[(c.to_ascii_lowercase() - b'a') as usize])
+                .take(MAX_INITIALS)
+                .collect(),
+        }
+    }
+
+    /// draws the letter of the time in place of the scene, false in the time of the scene
+    pub fn render(&self, renderman: &mut RenderManager<LedMatrix>, t: Time) -> bool {
+        if self.glyphs.is_empty() {
+            return false;
+        }
+
+        let letters = self.glyphs.len() as f32 * (LETTER_DURATION + GAP_DURATION);
+        let cycle = SCENE_DURATION + letters;
+        let position = t.phase(1.0 / cycle) * cycle - SCENE_DURATION;
+        if position < 0.0 {
+            return false;
+        }
+
+        let index = (position / (LETTER_DURATION + GAP_DURATION)) as usize;
+        let in_letter = position - index as f32 * (LETTER_DURATION + GAP_DURATION);
+        let pattern = match self.glyphs.get(index) {
+            Some(&glyph) if in_letter < LETTER_DURATION => glyph,
+            _ => 0,
+        };
+
+        renderman.render(
+            &[RenderCommand {
+                effect: Pattern::Simple(pattern),
+                color: ColorPalette::Solid((255, 255, 255).into()),
+                ..Default::default()
+            }],
+            t,
+        );
+        true
+    }
+}
@@ -0,0 +1,414 @@
+use core::cell::{Cell, RefCell};
+use core::ops::RangeInclusive;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embassy_futures::select::{select, Either};
+use embassy_rp::gpio::{self, Pull};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use heapless::Vec;
+use infrared::{protocol::Nec, protocol::SamsungNec, Receiver};
+use serde::{Deserialize, Serialize};
+
+use crate::rgbeffects::{ColorPalette, Pattern, RenderCommand};
+use crate::{
+    power, reboot, replay, scenes, LedPixel, MegaPublisher, OutputPower, TaskCommand, WorkingMode,
+};
+
+/// where an input event comes from, every source can be turned off on its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Button,
+    Ir,
+    Motion,
+    #[cfg(feature = "strobe-sync")]
+    Sync,
+}
+
+impl Source {
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InputEvent {
+    pub at: Instant,
+    pub source: Source,
+    pub command: TaskCommand,
+}
+
+static ENABLED: AtomicU8 = AtomicU8::new(0xFF);
+
+pub fn set_enabled(source: Source, enabled: bool) {
+    if enabled {
+        ENABLED.fetch_or(source.mask(), Ordering::Relaxed);
+    } else {
+        ENABLED.fetch_and(!source.mask(), Ordering::Relaxed);
+    }
+}
+
+pub fn is_enabled(source: Source) -> bool {
+    ENABLED.load(Ordering::Relaxed) & source.mask() != 0
+}
+
+// the nec address of the beacon that syncs the strobes, with any command
+pub const STROBE_SYNC_IR_ADDRESS: u8 = 0x53;
+
+/// what the ir receiver picked up, for the traffic overlay
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrTraffic {
+    Edge,
+    Nec,
+    Samsung,
+}
+
+impl IrTraffic {
+    /// the color of the overlay led and for how many seconds it stays on
+    pub fn overlay(self) -> (LedPixel, f32) {
+        match self {
+            IrTraffic::Edge => ((32, 32, 32).into(), 0.02),
+            IrTraffic::Nec => ((0, 255, 0).into(), 0.2),
+            IrTraffic::Samsung => ((0, 0, 255).into(), 0.2),
+        }
+    }
+}
+
+/// the last ir traffic, a packet isn't replaced by the edges after it until it's taken
+pub static IR_TRAFFIC: Signal<CriticalSectionRawMutex, IrTraffic> = Signal::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IrProtocol {
+    Nec,
+    Samsung,
+}
+
+pub const MAX_IR_REMOTES: usize = 4;
+
+// the badges talk to each other with nec packets on these addresses, see identity.rs,
+// party.rs and apps.rs, they get through the filter so the games keep working, the
+// reboot packets of the flashing stations get through too
+const BADGE_IR_ADDRESSES: RangeInclusive<u8> = 0x49..=0x54;
+
+/// the remotes the badge listens to, by protocol and address, so in a crowd only the
+/// remote of the event controls the badges, no remotes = every remote
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrFilter {
+    pub remotes: [Option<(IrProtocol, u8)>; MAX_IR_REMOTES],
+}
+
+impl IrFilter {
+    pub const fn new() -> Self {
+        Self {
+            remotes: [None; MAX_IR_REMOTES],
+        }
+    }
+
+    pub fn accepts(&self, protocol: IrProtocol, addr: u8) -> bool {
+        (protocol == IrProtocol::Nec
+            && (BADGE_IR_ADDRESSES.contains(&addr) || addr == reboot::REBOOT_IR_ADDRESS))
+            || self.remotes.iter().all(Option::is_none)
+            || self.remotes.contains(&Some((protocol, addr)))
+    }
+}
+
+static IR_FILTER: Mutex<CriticalSectionRawMutex, Cell<IrFilter>> =
+    Mutex::new(Cell::new(IrFilter::new()));
+
+pub fn set_ir_filter(filter: IrFilter) {
+    IR_FILTER.lock(|f| f.set(filter));
+}
+
+// the recording stops by itself when full
+const MAX_RECORDED: usize = 64;
+
+struct Recorder {
+    recording: bool,
+    events: Vec<InputEvent, MAX_RECORDED>,
+}
+
+static RECORDER: Mutex<CriticalSectionRawMutex, RefCell<Recorder>> =
+    Mutex::new(RefCell::new(Recorder {
+        recording: false,
+        events: Vec::new(),
+    }));
+
+static REPLAY_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// throws away the last recording and records the events from now on
+pub fn start_recording() {
+    RECORDER.lock(|recorder| {
+        let mut recorder = recorder.borrow_mut();
+        recorder.events.clear();
+        recorder.recording = true;
+    });
+}
+
+pub fn stop_recording() {
+    let count = RECORDER.lock(|recorder| {
+        let mut recorder = recorder.borrow_mut();
+        recorder.recording = false;
+        recorder.events.len()
+    });
+    log::info!("Recorded {} input events", count);
+}
+
+/// sends the recorded events again, with the same timing
+pub fn replay() {
+    REPLAY_SIGNAL.signal(());
+}
+
+fn record(event: &InputEvent) {
+    RECORDER.lock(|recorder| {
+        let mut recorder = recorder.borrow_mut();
+        if recorder.recording && recorder.events.push(event.clone()).is_err() {
+            log::warn!("Input recording full");
+            recorder.recording = false;
+        }
+    });
+}
+
+/// the only way the input tasks send their events
+pub struct Input {
+    source: Source,
+    publisher: MegaPublisher,
+}
+
+impl Input {
+    pub fn new(source: Source, publisher: MegaPublisher) -> Self {
+        Self { source, publisher }
+    }
+
+    fn event(&self, command: TaskCommand) -> Option<InputEvent> {
+        if !is_enabled(self.source) {
+            return None;
+        }
+
+        let event = InputEvent {
+            at: Instant::now(),
+            source: self.source,
+            command,
+        };
+        record(&event);
+        Some(event)
+    }
+
+    /// waits if the channel is full
+    pub async fn send(&self, command: TaskCommand) {
+        if let Some(event) = self.event(command) {
+            self.publisher.publish(event.command).await;
+        }
+    }
+
+    /// drops the event if the channel is full, for the sources that can't wait
+    pub fn try_send(&self, command: TaskCommand) {
+        if let Some(event) = self.event(command) {
+            if self.publisher.try_publish(event.command).is_err() {
+                log::warn!("Channel full, dropping {:?} event", self.source);
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+pub async fn replay_tsk(publisher: MegaPublisher) {
+    loop {
+        REPLAY_SIGNAL.wait().await;
+
+        let events = RECORDER.lock(|recorder| recorder.borrow().events.clone());
+        if events.is_empty() {
+            log::warn!("No input events to replay");
+            continue;
+        }
+
+        log::info!("Replaying {} input events", events.len());
+        for (at, event) in replay::schedule(&events, |event| event.at, Instant::now()) {
+            Timer::at(at).await;
+            if is_enabled(event.source) {
+                publisher.publish(event.command.clone()).await;
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+pub async fn button_tsk(mut button: gpio::Input<'static>, ir_sensor: u8, input: Input) {
+    // if we start with the button pressed, function as a torch light
+    if button.is_low() {
+        Timer::after_millis(100).await;
+
+        let torch = RenderCommand {
+            effect: Pattern::Simple(scenes::PATTERNS.get().all_on),
+            color: ColorPalette::Solid((255, 255, 255).into()),
+            ..Default::default()
+        };
+        input
+            .send(TaskCommand::SetWorkingMode(WorkingMode::Special(
+                torch.clone(),
+            )))
+            .await;
+
+        input
+            .send(TaskCommand::SetBrightness(OutputPower::FULL))
+            .await;
+
+        // keep holding through the countdown to erase the settings and the scenes of
+        // the owner, releasing the button before the end goes back to the torch
+        if with_timeout(Duration::from_millis(2000), button.wait_for_high())
+            .await
+            .is_err()
+        {
+            // the countdown starts from its first frame
+            input.send(TaskCommand::ResetTime).await;
+            input
+                .send(TaskCommand::SetWorkingMode(WorkingMode::Special(
+                    scenes::factory_reset_countdown(),
+                )))
+                .await;
+
+            let countdown =
+                Duration::from_millis((scenes::FACTORY_RESET_COUNTDOWN * 1000.0) as u64);
+            if with_timeout(countdown, button.wait_for_high())
+                .await
+                .is_err()
+            {
+                input.send(TaskCommand::FactoryReset).await;
+            } else {
+                input
+                    .send(TaskCommand::SetWorkingMode(WorkingMode::Special(torch)))
+                    .await;
+            }
+        }
+
+        button.wait_for_high().await;
+    }
+
+    let mut press_start;
+    // consecutive short presses, a press is only published when no other one follows
+    let mut presses = 0;
+
+    loop {
+        match select(button.wait_for_low(), power::DORMANT_SIGNAL.wait()).await {
+            Either::First(_) => {}
+            Either::Second(_) => {
+                power::enter_dormant(&mut button, ir_sensor).await;
+                input.send(TaskCommand::WakeUp).await;
+                continue;
+            }
+        }
+        press_start = Instant::now();
+
+        match with_timeout(Duration::from_millis(1000), button.wait_for_high()).await {
+            // no timeout
+            Ok(_) => {}
+            // timeout
+            Err(_) => {
+                presses = 0;
+
+                // keep holding to turn the badge off, the long press is only sent on a
+                // release before that so turning off doesn't act on the scene first
+                if with_timeout(Duration::from_millis(3000), button.wait_for_high())
+                    .await
+                    .is_ok()
+                {
+                    input.send(TaskCommand::LongButtonPress).await;
+                } else {
+                    input.send(TaskCommand::PowerOff).await;
+                    button.wait_for_high().await;
+                }
+            }
+        }
+
+        let press_duration = Instant::now() - press_start;
+
+        if press_duration >= Duration::from_millis(50)
+            && press_duration < Duration::from_millis(1000)
+        {
+            presses += 1;
+        }
+
+        if presses > 0 {
+            // wait a bit to see if the user is going to press again
+            if with_timeout(Duration::from_millis(300), button.wait_for_low())
+                .await
+                .is_ok()
+            {
+                continue;
+            }
+
+            if presses == 1 {
+                input.send(TaskCommand::ShortButtonPress).await;
+            } else {
+                input.send(TaskCommand::MultiButtonPress(presses)).await;
+            }
+            presses = 0;
+        }
+    }
+}
+
+/// sync pulses for the strobe shader from a lighting desk or another badge, on the rising edge
+#[cfg(feature = "strobe-sync")]
+#[embassy_executor::task]
+pub async fn strobe_sync_tsk(mut pin: gpio::Input<'static>, input: Input) {
+    loop {
+        pin.wait_for_rising_edge().await;
+        // a late flash is worse than a missed one
+        input.try_send(TaskCommand::StrobeSync);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn ir_receiver(ir_sensor: u8, input: Input) {
+    // this is a mega hack to support the reception of two different IR protocols
+    // we unsafely use the same pin for both receivers
+
+    let mut nec_receiver: Receiver<Nec, gpio::Input> = Receiver::builder()
+        .rc5()
+        .frequency(1_000_000)
+        .pin(gpio::Input::new(
+            unsafe { gpio::AnyPin::steal(ir_sensor) },
+            Pull::None,
+        ))
+        .protocol()
+        .build();
+
+    let mut samsung_receiver: Receiver<SamsungNec, gpio::Input> = Receiver::builder()
+        .rc5()
+        .frequency(1_000_000)
+        .pin(gpio::Input::new(
+            unsafe { gpio::AnyPin::steal(ir_sensor) },
+            Pull::None,
+        ))
+        .protocol()
+        .build();
+
+    loop {
+        samsung_receiver.pin_mut().wait_for_any_edge().await;
+        let now = Instant::now().as_ticks() as u32;
+
+        if !IR_TRAFFIC.signaled() {
+            IR_TRAFFIC.signal(IrTraffic::Edge);
+        }
+
+        // the overlay shows the packets of the other remotes too, to find out which one
+        // is pointed at the badge
+        let filter = IR_FILTER.lock(Cell::get);
+
+        // waiting for the channel would make us miss the edges of the next command
+        if let Ok(Some(cmd)) = samsung_receiver.event_instant(now) {
+            IR_TRAFFIC.signal(IrTraffic::Samsung);
+            if filter.accepts(IrProtocol::Samsung, cmd.addr) {
+                input.try_send(TaskCommand::ReceivedIrNec(cmd.addr, cmd.cmd, cmd.repeat));
+            }
+        }
+
+        if let Ok(Some(cmd)) = nec_receiver.event_instant(now) {
+            IR_TRAFFIC.signal(IrTraffic::Nec);
+            if filter.accepts(IrProtocol::Nec, cmd.addr) {
+                input.try_send(TaskCommand::ReceivedIrNec(cmd.addr, cmd.cmd, cmd.repeat));
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#[cfg(not(feature = "std"))]
+use num_traits::real::Real;
+
+// first quarter of a sine wave, 0 to 1 as 0 to 65535, with the last point
+// so the interpolation never reads past the end
+const QUARTER_SINE: [u16; 65] = [
+    0, 1608, 3216, 4821, 6424, 8022, 9616, 11204, 12785, 14359, 15924, 17479, 19024, 20557, 22078,
+    23586, 25079, 26557, 28020, 29465, 30893, 32302, 33692, 35061, 36409, 37736, 39039, 40319,
+    41575, 42806, 44011, 45189, 46340, 47464, 48558, 49624, 50659, 51664, 52638, 53580, 54490,
+    55367, 56211, 57021, 57797, 58537, 59243, 59913, 60546, 61144, 61704, 62227, 62713, 63161,
+    63571, 63943, 64276, 64570, 64826, 65042, 65219, 65357, 65456, 65515, 65535,
+];
+
+/// sine of an angle in turns (1.0 = 360 degrees), from a table instead of libm
+pub fn sin(turns: f32) -> f32 {
+    // 16 bits per turn, 4 quadrants of 64 steps of 256
+    let phase = ((turns - turns.floor()) * 65536.0) as u32 & 0xFFFF;
+    let quadrant = phase >> 14;
+    let step = (phase >> 8) as usize & 63;
+    let fraction = phase & 0xFF;
+
+    // the second and fourth quarters are the first one mirrored
+    let (a, b) = match quadrant {
+        0 | 2 => (QUARTER_SINE[step], QUARTER_SINE[step + 1]),
+        _ => (QUARTER_SINE[64 - step], QUARTER_SINE[63 - step]),
+    };
+    let value = (a as u32 * (256 - fraction) + b as u32 * fraction) >> 8;
+
+    let value = value as f32 / 65535.0;
+    if quadrant < 2 {
+        value
+    } else {
+        -value
+    }
+}
@@ -1,9 +1,6 @@
 #![no_std]
 #![no_main]
 
-use core::f64;
-
-use defmt::println;
 use defmt::unwrap;
 use embassy_executor::Executor;
 use embassy_rp::adc;
@@ -16,28 +13,86 @@ use embassy_rp::multicore::Stack;
 use embassy_sync::pubsub::PubSubChannel;
 use embassy_sync::pubsub::Publisher;
 use embassy_sync::signal::Signal;
-use log::{info, warn};
+use embassy_sync::zerocopy_channel::Channel;
+use log::info;
 
 use embassy_rp::peripherals::PIO0;
 use embassy_rp::pio::{InterruptHandler, Pio};
 use embassy_rp::pwm;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 
-use embassy_time::with_timeout;
-use embassy_time::Instant;
 use embassy_time::{Duration, Ticker, Timer};
 
 use embassy_rp::bind_interrupts;
-use heapless::Vec;
-use infrared::{protocol::Nec, protocol::SamsungNec, Receiver};
+use infrared::protocol::Nec;
 use panic_probe as _;
 
+mod app;
+mod apps;
+mod audio;
+mod binary_info;
+mod boards;
+mod boot_logo;
+mod buzzer;
+mod calibration;
 mod capnp;
+mod clock;
+mod color;
+mod config;
+mod demo;
+mod drawing;
+mod editor;
+mod entropy;
+mod framebuffer;
+#[cfg(feature = "i2c-slave")]
+mod i2c_slave;
+mod identity;
+mod initials;
+mod input;
+mod lut;
+mod matrix;
+mod midi;
+mod mixer;
+#[cfg_attr(not(feature = "accel"), allow(dead_code))]
+mod motion;
+mod notifications;
+mod palette_table;
+mod party;
+mod power;
+mod reboot;
+mod recorder;
+mod records;
+mod remote;
+mod replay;
 mod rgbeffects;
 mod scenes;
+mod settings;
+#[cfg(feature = "spi-slave")]
+mod spi_slave;
+mod stats;
+mod strip;
+mod thermal;
+mod time;
+mod timesync;
+#[cfg(feature = "uart")]
+mod uart;
 mod usb;
+mod user_scenes;
 mod ws2812;
 
+#[cfg(all(feature = "rp2040", feature = "rp2350"))]
+compile_error!(
+    "build for one chip, the rp2350 with --no-default-features --features rp2350,board-minibadge"
+);
+#[cfg(not(any(feature = "rp2040", feature = "rp2350")))]
+compile_error!("build with the feature of the chip, rp2040 or rp2350");
+
+// the boot rom of the rp2350 starts the firmware it describes, see memory-rp2350.x
+#[cfg(feature = "rp2350")]
+#[link_section = ".start_block"]
+#[used]
+pub static IMAGE_DEF: embassy_rp::block::ImageDef = embassy_rp::block::ImageDef::secure_exe();
+
 pub mod usb_messages_capnp {
     include!(concat!(env!("OUT_DIR"), "/usb_messages_capnp.rs"));
 }
@@ -47,245 +102,169 @@ bind_interrupts!(struct Irqs {
     ADC_IRQ_FIFO => adc::InterruptHandler;
 });
 
-use rand::rngs::SmallRng;
-use rand::SeedableRng;
-use rgbeffects::ColorPalette;
-use rgbeffects::FragmentShader;
-use rgbeffects::Pattern;
+use framebuffer::{
+    ChainFramebuffer, LedPixel, RawFramebuffer, CHAIN_SIZE, HAS_WHITE_LED, LED_MATRIX_SIZE,
+};
+use matrix::LedMatrix;
 use rgbeffects::RenderCommand;
-use rgbeffects::RenderManager;
 use scenes::Scenes;
 use static_cell::StaticCell;
+use time::Time;
 use ws2812::Ws2812;
 
-// global constants
-const LED_MATRIX_WIDTH: usize = 3;
-const LED_MATRIX_HEIGHT: usize = 3;
-const LED_MATRIX_SIZE: usize = LED_MATRIX_WIDTH * LED_MATRIX_HEIGHT;
-/// set to true if RGBW leds, false if RGB
-pub const HAS_WHITE_LED: bool = false;
-
-#[derive(Clone, Copy, Default, Debug, PartialEq)]
-struct LedPixel {
-    r: u8,
-    g: u8,
-    b: u8,
-    w: u8,
-}
-
-impl LedPixel {
-    fn set_white(&mut self) {
-        // create white channel from rgb
-        if self.r == self.g && self.g == self.b {
-            self.w = self.r;
-            self.r = 0;
-            self.g = 0;
-            self.b = 0;
-        }
-    }
-}
-
-impl From<(u8, u8, u8)> for LedPixel {
-    fn from(rgb: (u8, u8, u8)) -> Self {
-        Self {
-            r: rgb.0,
-            g: rgb.1,
-            b: rgb.2,
-            w: 0,
-        }
-    }
-}
-
-#[derive(Clone, Copy, Default, Debug)]
-struct RawFramebuffer {
-    framebuffer: [LedPixel; LED_MATRIX_SIZE],
-}
-
-impl RawFramebuffer {
-    fn new() -> Self {
-        Self {
-            framebuffer: [LedPixel::default(); LED_MATRIX_SIZE],
-        }
-    }
-
-    fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
-        if x < LED_MATRIX_WIDTH && y < LED_MATRIX_HEIGHT {
-            let color = LedPixel {
-                r: colour.r,
-                g: colour.g,
-                b: colour.b,
-                w: 0,
-            };
-            self.framebuffer[y * LED_MATRIX_WIDTH + x] = color;
-        }
-    }
-
-    fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
-        if x < LED_MATRIX_WIDTH && y < LED_MATRIX_HEIGHT {
-            self.framebuffer[y * LED_MATRIX_WIDTH + x]
-        } else {
-            LedPixel::default()
-        }
-    }
-
-    fn set_all(&mut self, rgb: LedPixel) {
-        self.framebuffer.iter_mut().for_each(|led| *led = rgb);
-    }
-    fn update_rgbw(&mut self) {
-        self.framebuffer.iter_mut().for_each(|led| led.set_white());
-    }
-
-    fn get_raw(&self) -> &[LedPixel; LED_MATRIX_SIZE] {
-        &self.framebuffer
-    }
-}
-
-struct LedMatrix {
-    raw_framebuffer: RawFramebuffer,
-    gamma_corrected_framebuffer: RawFramebuffer,
-    corrected_gain: f32,
-    raw_gain: f32,
-}
-
-impl LedMatrix {
-    fn new() -> Self {
-        Self {
-            raw_framebuffer: RawFramebuffer::new(),
-            gamma_corrected_framebuffer: RawFramebuffer::new(),
-            corrected_gain: 1.0,
-            raw_gain: 1.0,
-        }
-    }
-
-    fn set_gain(&mut self, gain: f32) {
-        self.corrected_gain = gain;
-    }
-
-    fn set_raw_gain(&mut self, gain: f32) {
-        self.raw_gain = gain;
-    }
-
-    fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
-        self.raw_framebuffer.get_pixel(x, y)
-    }
-
-    fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
-        self.raw_framebuffer.set_pixel(x, y, colour);
-    }
-
-    fn update_gamma_correction_and_gain(&mut self) {
-        static GAMMA_CORRECTION: [u8; 256] = [
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4,
-            4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11,
-            12, 12, 13, 13, 13, 14, 14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22,
-            22, 23, 24, 24, 25, 25, 26, 27, 27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37,
-            38, 39, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58,
-            59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72, 73, 74, 75, 77, 78, 79, 81, 82, 83, 85,
-            86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104, 105, 107, 109, 110, 112, 114,
-            115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137, 138, 140, 142, 144,
-            146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175, 177, 180,
-            182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
-            223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
-        ];
-
-        for i in 0..LED_MATRIX_SIZE {
-            let colour = self.raw_framebuffer.framebuffer[i];
-
-            let colour = LedPixel {
-                r: (GAMMA_CORRECTION[(colour.r as f32 * self.corrected_gain) as usize] as f32
-                    * self.raw_gain) as u8,
-                g: (GAMMA_CORRECTION[(colour.g as f32 * self.corrected_gain) as usize] as f32
-                    * self.raw_gain) as u8,
-                b: (GAMMA_CORRECTION[(colour.b as f32 * self.corrected_gain) as usize] as f32
-                    * self.raw_gain) as u8,
-                w: (GAMMA_CORRECTION[(colour.w as f32 * self.corrected_gain) as usize] as f32
-                    * self.raw_gain) as u8,
-            };
-
-            self.gamma_corrected_framebuffer.framebuffer[i] = colour;
-        }
-    }
-
-    fn set_all(&mut self, rgb: LedPixel) {
-        self.raw_framebuffer.set_all(rgb);
-    }
-
-    fn get_gamma_corrected(&mut self) -> &[LedPixel; LED_MATRIX_SIZE] {
-        self.update_gamma_correction_and_gain();
-
-        if HAS_WHITE_LED {
-            self.gamma_corrected_framebuffer.update_rgbw();
-        }
-        self.gamma_corrected_framebuffer.get_raw()
-    }
-
-    fn clear(&mut self) {
-        self.set_all((0, 0, 0).into());
-    }
-}
-
 #[derive(Clone, Debug)]
 enum TaskCommand {
-    ThermalThrottleMultiplier(f32), // 1.0 = no throttle, 0.0 = full throttle
-    ReceivedIrNec(u8, u8, bool),    // add, cmd, repeat
+    ReceivedIrNec(u8, u8, bool), // add, cmd, repeat
     ShortButtonPress,
     LongButtonPress,
+    MultiButtonPress(u8),         // number of presses
     MidiSetPixel(u8, u8, u8, u8), // x y channel (0=r 1=g 2=b) value
+    MidiControlChange(u8, u8),    // controller, value
     SetWorkingMode(WorkingMode),
     SendIrNec(u8, u8, bool),
     IrTxDone,
     NextPattern,
+    SetScene(u8),
     IncreaseBrightness,
     DecreaseBrightness,
+    CycleBrightness, // the few levels of the button, wrapping around
     SetBrightness(OutputPower),
     ResetTime,
     UsbActivity,
     SendHidKeyboard(usbd_hid::descriptor::KeyboardUsage),
+    PowerOff,
+    WakeUp,
+    SleepTimer,
+    SetTime(u8, u8, u8), // hour minute second
+    Notify(scenes::Notification),
+    SetRandomBootScene(bool),
+    SetPlayback(scenes::Playback),
+    SetDemoMode(bool),
+    SetDoNotDisturb(bool),
+    EditScene(editor::SceneEdit),
+    SetFrameCapture(bool),
+    SetFrameRate(u8),                         // frames per second, already clamped
+    SetMaxBrightness(u8),                     // percent, already clamped
+    SetQuietHours(Option<clock::QuietHours>), // None = no quiet hours
+    SetMixScene(u8),   // the second scene of the mix, past the last one stops the mix
+    SetMixAmount(u8),  // percent of the second scene in the mix, already clamped
+    SaveState(u8, u8), // brightness level, scene, to start from them at the next boot
+    SetName(heapless::String<{ records::MAX_NAME_LEN }>),
+    SetInitials(heapless::String<{ records::MAX_INITIALS }>), // upper case letters only
+    FactoryReset, // back to the defaults, the calibration is kept
+    Reboot(reboot::Target),
+    LogStats,
+    RecordInput(bool), // true starts a new recording, false stops it
+    ReplayInput,
+    RecordFrames(bool), // true starts a new recording, false stops it and saves it
+    SaveRecording,      // the recording finished is in recorder::FINISHED
+    Calibrate(calibration::CalibrationEdit),
+    StreamFrame(RawFramebuffer),
+    SetPaletteTable(rgbeffects::PaletteTable),
+    NewBootLogo, // drops the frames sent before
+    AddBootLogoFrame(rgbeffects::ColorFrame),
+    SaveBootLogo(u16), // milliseconds of every frame, the frames sent are saved and cleared
+    SetIrOverlay(bool),
+    SetIrFilter(input::IrFilter),
+    SetStrip(strip::Strip),
+    SetChainedBadges(u8), // this one included, already clamped
+    SetPartyRole(party::Role),
+    Beat,
+    StrobeSync, // the strobe flashes start over
+    Shake,
+    Swing(bool), // towards the right
+    Tap,
+    Orientation(motion::Orientation),
     Error,
     None,
 }
 
-static MEGA_CHANNEL: PubSubChannel<CriticalSectionRawMutex, TaskCommand, 8, 8, 8> =
+// when the channel is full the publishers wait, except the IR receiver that drops
+// what it receives (the remotes and the other badges repeat their commands) so it
+// keeps decoding, and the beats that replace the oldest message
+//
+// 11 publishers with all the features on, the app included, and one to spare
+static MEGA_CHANNEL: PubSubChannel<CriticalSectionRawMutex, TaskCommand, 8, 8, 12> =
     PubSubChannel::new();
-type MegaPublisher = Publisher<'static, CriticalSectionRawMutex, TaskCommand, 8, 8, 8>;
+type MegaPublisher = Publisher<'static, CriticalSectionRawMutex, TaskCommand, 8, 8, 12>;
 type MegaSubscriber =
-    embassy_sync::pubsub::Subscriber<'static, CriticalSectionRawMutex, TaskCommand, 8, 8, 8>;
+    embassy_sync::pubsub::Subscriber<'static, CriticalSectionRawMutex, TaskCommand, 8, 8, 12>;
 
 // if we need to override the normal rendering with a special effect, we use this enum
 #[derive(Clone, Debug)]
 enum WorkingMode {
-    Normal,                             // normal rendering, user selecting the patterns etc
+    Normal,                              // normal rendering, user selecting the patterns etc
     Special(RenderCommand), // override normal rendering until the user presses the button
-    SpecialTimeout(RenderCommand, f64), // override normal rendering until the timeout
+    SpecialTimeout(RenderCommand, Time), // override normal rendering until the timeout
     RawFramebuffer(RawFramebuffer),
+    App(apps::App),     // the app handles the button presses
+    Calibration(usize), // one led at a time in white, the button moves to the next
+    Settings(settings::Menu),
 }
-#[derive(Clone, Debug)]
-enum OutputPower {
-    High,
-    Medium,
-    Low,
-    NighMode,
-}
+// brightness chosen by the user, in perceptually even steps since the gain is applied
+// before the gamma correction, level 0 is night mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct OutputPower(u8);
+
+const BRIGHTNESS_LEVELS: u8 = 16;
+// gain of the night mode, the brightest level has a gain of 1.0
+const NIGHT_GAIN: f32 = 0.25;
+// the button steps through a few levels only, the remote and the host can set any of them
+const BUTTON_BRIGHTNESS_STEP: u8 = 5;
 
 impl OutputPower {
-    fn increase(&self) -> Self {
-        match self {
-            OutputPower::High => OutputPower::NighMode,
-            OutputPower::Medium => OutputPower::High,
-            OutputPower::Low => OutputPower::Medium,
-            OutputPower::NighMode => OutputPower::Low,
-        }
+    const FULL: Self = Self(BRIGHTNESS_LEVELS - 1);
+
+    /// value from 0 to max spread over the levels, for the controls with a different range
+    fn scaled(value: u8, max: u8) -> Self {
+        let value = value.min(max) as u32;
+        Self((value * (BRIGHTNESS_LEVELS - 1) as u32 / max.max(1) as u32) as u8)
     }
 
-    fn decrease(&self) -> Self {
-        match self {
-            OutputPower::High => OutputPower::Medium,
-            OutputPower::Medium => OutputPower::Low,
-            OutputPower::Low => OutputPower::NighMode,
-            OutputPower::NighMode => OutputPower::High,
+    fn level(self) -> u8 {
+        self.0
+    }
+
+    fn gain(self) -> f32 {
+        NIGHT_GAIN + (1.0 - NIGHT_GAIN) * self.0 as f32 / (BRIGHTNESS_LEVELS - 1) as f32
+    }
+
+    fn increase(self) -> Self {
+        Self((self.0 + 1).min(BRIGHTNESS_LEVELS - 1))
+    }
+
+    /// one level up, not above ceiling, a level already above it stays
+    fn increase_up_to(self, ceiling: Self) -> Self {
+        Self(self.increase().0.min(ceiling.0.max(self.0)))
+    }
+
+    /// the highest level not brighter than gain, at least night mode
+    fn at_most(gain: f32) -> Self {
+        let top = (BRIGHTNESS_LEVELS - 1) as f32;
+        let level = (gain - NIGHT_GAIN) / (1.0 - NIGHT_GAIN) * top;
+        // so a gain of exactly a level isn't rounded below it
+        Self((level + 0.001).clamp(0.0, top) as u8)
+    }
+
+    fn decrease(self) -> Self {
+        Self(self.0.saturating_sub(1))
+    }
+
+    /// the next level of the button, from night mode back to full
+    fn cycle(self) -> Self {
+        if self.0 == 0 {
+            Self::FULL
+        } else {
+            // levels set by the remote go to the step of the button below them
+            Self((self.0 - 1) / BUTTON_BRIGHTNESS_STEP * BUTTON_BRIGHTNESS_STEP)
         }
     }
+
+    /// from one led in night mode to all of them at full brightness
+    fn pattern(self) -> rgbeffects::LedPattern {
+        let leds = 1 + self.0 as u32 * 8 / (BRIGHTNESS_LEVELS - 1) as u32;
+        (1 << leds) - 1
+    }
 }
 
 enum WhiteLedCommand {
@@ -295,510 +274,288 @@ enum WhiteLedCommand {
 
 static WHITE_LED_SIGNAL: Signal<CriticalSectionRawMutex, WhiteLedCommand> = Signal::new();
 
+// frames streamed by the host skip the channel, only the latest one matters
+static HOST_FRAME_SIGNAL: Signal<CriticalSectionRawMutex, RawFramebuffer> = Signal::new();
+
 static mut CORE1_STACK: Stack<8192> = Stack::new();
 static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
 static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
 
+// the boot logo lives as long as the boot animation made of it
+static BOOT_LOGO: StaticCell<boot_logo::BootLogo> = StaticCell::new();
+
+// the recordings live as long as the scenes made of them
+static RECORDINGS: StaticCell<recorder::Recordings> = StaticCell::new();
+
+// double buffer between the rendering and the leds, both on core1
+static FRAMES: StaticCell<[ws2812::Frame; 2]> = StaticCell::new();
+static FRAME_CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, ws2812::Frame>> =
+    StaticCell::new();
+
 #[cortex_m_rt::entry]
 fn main() -> ! {
     let p = embassy_rp::init(Default::default());
 
     let executor0 = EXECUTOR0.init(Executor::new());
 
+    // the revision of the pcb, from its strap resistors
+    let revision = boards::Revision::detect(
+        &Input::new(boards::pin!(p.strap_0), Pull::Up),
+        &Input::new(boards::pin!(p.strap_1), Pull::Up),
+    );
+    boards::log(revision);
+
+    // settings saved in flash
+    let mut flash = config::BadgeFlash::new_blocking(p.FLASH);
+    identity::init(&mut flash);
+    let config = records::Config::load(&mut flash);
+    info!("Loaded config: {:?}", config);
+    let palette_table = palette_table::load(&mut flash);
+    let boot_logo = &*BOOT_LOGO.init(boot_logo::load(&mut flash));
+
     // ADC / temperature sensor
-    let adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
-    let ts = adc::Channel::new_temp_sensor(p.ADC_TEMP_SENSOR);
+    let mut adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
+    let mut ts = adc::Channel::new_temp_sensor(p.ADC_TEMP_SENSOR);
+
+    // different random effects at every boot, the badge id keeps two badges
+    // booted at the same time from getting the same seed
+    let mut entropy = entropy::Entropy::new();
+    entropy.mix(identity::get().seed());
+    entropy.collect_rosc(8);
+    entropy.collect_adc(&mut adc, &mut ts, 256);
+    let seed = entropy.seed();
+
+    // microphone on the spare adc pin, sampled by the temperature task
+    #[cfg(feature = "mic")]
+    let mic = Some(adc::Channel::new_pin(boards::pin!(p.mic), Pull::None));
+    #[cfg(not(feature = "mic"))]
+    let mic = None;
+
+    // real time clock, the watchdog is only used for its scratch registers
+    #[cfg(feature = "rp2040")]
+    let rtc = embassy_rp::rtc::Rtc::new(p.RTC);
+    #[cfg(feature = "rp2350")]
+    let rtc = clock::Rtc::default();
+    let watchdog = embassy_rp::watchdog::Watchdog::new(p.WATCHDOG);
 
     // button
 
-    let user_btn = Input::new(p.PIN_8, Pull::Up);
+    let user_btn = Input::new(boards::pin!(p.button), Pull::Up);
 
     // white led
-    let white_led = Output::new(p.PIN_20, embassy_rp::gpio::Level::Low);
+    let white_led = Output::new(boards::pin!(p.white_led), embassy_rp::gpio::Level::Low);
 
     // infrared stuff
-    let _ir_sens_0 = Input::new(p.PIN_9, Pull::None);
+    let _ir_sens_0 = Input::new(boards::pin!(p.ir_sensor_2), Pull::None);
+    let ir_sensor = boards::pin!(p.ir_sensor).pin();
 
     let mut pwm_cfg: pwm::Config = Default::default();
     pwm_cfg.enable = false;
-    let ir_blaster = pwm::Pwm::new_output_b(p.PWM_SLICE5, p.PIN_11, pwm_cfg);
+    let ir_blaster = pwm::Pwm::new_output_b(
+        boards::pin!(p.ir_blaster_pwm),
+        boards::pin!(p.ir_blaster),
+        pwm_cfg,
+    );
 
     // leds
     let Pio {
-        mut common, sm0, ..
+        mut common,
+        sm0,
+        #[cfg(feature = "strip")]
+        sm1,
+        ..
     } = Pio::new(p.PIO0, Irqs);
 
-    let ws2812: Ws2812<'_, PIO0, 0, 9> = Ws2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_19);
+    let ws2812: Ws2812<'_, PIO0, 0, CHAIN_SIZE> = match revision.leds_pin {
+        boards::LedsPin::Main => Ws2812::new(
+            &mut common,
+            sm0,
+            p.DMA_CH0,
+            boards::pin!(p.leds),
+            LED_MATRIX_SIZE,
+        ),
+        boards::LedsPin::Alt => Ws2812::new(
+            &mut common,
+            sm0,
+            p.DMA_CH0,
+            boards::pin!(p.leds_alt),
+            LED_MATRIX_SIZE,
+        ),
+    };
+
+    // external strip on the breakout pad
+    #[cfg(feature = "strip")]
+    let strip_leds = Some(Ws2812::new(
+        &mut common,
+        sm1,
+        p.DMA_CH1,
+        boards::pin!(p.strip),
+        strip::MAX_LEDS,
+    ));
+    #[cfg(not(feature = "strip"))]
+    let strip_leds = None;
+
+    // sync pulses for the strobes
+    #[cfg(feature = "strobe-sync")]
+    let strobe_sync = Input::new(boards::pin!(p.strobe_sync), Pull::Down);
+
+    // display module mode, controlled by another microcontroller
+    #[cfg(feature = "i2c-slave")]
+    let i2c_slave = embassy_rp::i2c_slave::I2cSlave::new(
+        p.I2C1,
+        boards::pin!(p.i2c_slave_scl),
+        boards::pin!(p.i2c_slave_sda),
+        i2c_slave::Irqs,
+        i2c_slave::config(),
+    );
+
+    // control messages from another microcontroller
+    #[cfg(feature = "uart")]
+    let uart_rx = {
+        static RX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
+        let mut config = embassy_rp::uart::Config::default();
+        config.baudrate = 115_200;
+        embassy_rp::uart::BufferedUartRx::new(
+            p.UART0,
+            uart::Irqs,
+            boards::pin!(p.uart_rx),
+            RX_BUF.init([0; 256]),
+            config,
+        )
+    };
+
+    // frames rendered by another microcontroller
+    #[cfg(feature = "spi-slave")]
+    let spi_slave = {
+        let Pio {
+            mut common, sm0, ..
+        } = Pio::new(p.PIO1, spi_slave::Irqs);
+        spi_slave::SpiSlave::new(
+            &mut common,
+            sm0,
+            boards::pin!(p.spi_mosi),
+            boards::pin!(p.spi_sck),
+            boards::pin!(p.spi_cs),
+        )
+    };
+
+    // piezo buzzer
+    #[cfg(feature = "buzzer")]
+    let buzzer = pwm::Pwm::new_output_b(
+        boards::pin!(p.buzzer_pwm),
+        boards::pin!(p.buzzer),
+        Default::default(),
+    );
+
+    // accelerometer add-on
+    #[cfg(feature = "accel")]
+    let accel_i2c = embassy_rp::i2c::I2c::new_async(
+        p.I2C0,
+        boards::pin!(p.accel_scl),
+        boards::pin!(p.accel_sda),
+        motion::Irqs,
+        embassy_rp::i2c::Config::default(),
+    );
 
-    // scenes
-    let scenes = scenes::scenes();
+    // scenes, the built-in ones followed by the ones saved in flash and the recordings
+    let mut scenes = scenes::scenes();
+    user_scenes::load(&mut flash, &mut scenes);
+    recorder::append(RECORDINGS.init(recorder::load(&mut flash)), &mut scenes);
     // this is safe because this thread will always be running
     // it's still an hack and it should be changed in some way
     // the problem is that the scene array is GIANT and it's difficult to process in a task
     let scenes = unsafe { core::mem::transmute::<&Scenes, &'static Scenes>(&scenes) };
 
+    let app_config = config.clone();
     spawn_core1(
         p.CORE1,
         unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK) },
         move || {
+            let frames = FRAMES.init([[LedPixel::default(); CHAIN_SIZE]; 2]);
+            let (sender, receiver) = FRAME_CHANNEL.init(Channel::new(frames)).split();
+
             let executor1 = EXECUTOR1.init(Executor::new());
-            executor1.run(|spawner| unwrap!(spawner.spawn(main_tsk(ws2812, scenes))));
+            executor1.run(|spawner| {
+                unwrap!(spawner.spawn(ws2812::ws2812_tsk(ws2812, strip_leds, receiver)));
+                unwrap!(spawner.spawn(app::main_tsk(
+                    sender,
+                    scenes,
+                    app_config,
+                    seed,
+                    palette_table,
+                    boot_logo
+                )))
+            });
         },
     );
 
     executor0.run(|spawner| {
-        unwrap!(spawner.spawn(temperature(adc, ts, MEGA_CHANNEL.publisher().unwrap())));
+        unwrap!(spawner.spawn(thermal::temperature(
+            adc,
+            ts,
+            mic,
+            MEGA_CHANNEL.publisher().unwrap()
+        )));
         unwrap!(spawner.spawn(usb::usb_main(
             p.USB,
             MEGA_CHANNEL.publisher().unwrap(),
             MEGA_CHANNEL.subscriber().unwrap()
         )));
-        unwrap!(spawner.spawn(button_tsk(user_btn, MEGA_CHANNEL.publisher().unwrap())));
+        unwrap!(spawner.spawn(input::button_tsk(
+            user_btn,
+            ir_sensor,
+            input::Input::new(input::Source::Button, MEGA_CHANNEL.publisher().unwrap())
+        )));
         unwrap!(spawner.spawn(white_led_task(white_led)));
-        unwrap!(spawner.spawn(ir_receiver(
-            p.PIN_10.pin(),
-            MEGA_CHANNEL.publisher().unwrap()
+        unwrap!(spawner.spawn(config::config_tsk(
+            flash,
+            config,
+            MEGA_CHANNEL.subscriber().unwrap()
+        )));
+        unwrap!(spawner.spawn(clock::rtc_tsk(
+            rtc,
+            watchdog,
+            MEGA_CHANNEL.subscriber().unwrap()
+        )));
+        unwrap!(spawner.spawn(input::ir_receiver(
+            ir_sensor,
+            input::Input::new(input::Source::Ir, MEGA_CHANNEL.publisher().unwrap())
         )));
+        unwrap!(spawner.spawn(input::replay_tsk(MEGA_CHANNEL.publisher().unwrap())));
 
         unwrap!(spawner.spawn(ir_blaster_tsk(
             ir_blaster,
             MEGA_CHANNEL.subscriber().unwrap(),
             MEGA_CHANNEL.publisher().unwrap()
         )));
-    });
-}
-
-#[embassy_executor::task]
-async fn main_tsk(mut ws2812: Ws2812<'static, PIO0, 0, 9>, scenes: &'static Scenes) {
-    info!("Program start");
-    println!("Program start");
-
-    let mut midi_framebuffer = RawFramebuffer::new();
-
-    let mut renderman = RenderManager {
-        mtrx: LedMatrix::new(),
-        rng: SmallRng::seed_from_u64(69420),
-        persistent_data: Default::default(),
-    };
-
-    let patterns = scenes::PATTERNS.get();
-
-    let boot_animation = RenderCommand {
-        effect: Pattern::Animation(
-            patterns.boot_animation,
-            (patterns.boot_animation.len() as f32) * 2.0,
-        ),
-        color: ColorPalette::Rainbow(1.0),
-        pattern_shaders: Vec::from_slice(&[FragmentShader::LowPassWithPeak(50.0)]).unwrap(),
-        ..Default::default()
-    };
-    // override normal rendering with a special effect, if needed
-    let mut working_mode = WorkingMode::SpecialTimeout(boot_animation.clone(), 0.5);
-
-    let mut scene_id = 0;
-    let mut out_power = OutputPower::High;
-
-    let mut is_transmitting = false;
-
-    let mega_publisher = match MEGA_CHANNEL.publisher() {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Error getting publisher: {:?}", e);
-            panic!("Error getting publisher");
-        }
-    };
-
-    let mut mega_subscriber = match MEGA_CHANNEL.subscriber() {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Error getting subscriber: {:?}", e);
-            panic!("Error getting subscriber");
-        }
-    };
-
-    info!("Starting loop");
-    mega_publisher
-        .publish(TaskCommand::SendIrNec(0, 66, false))
-        .await;
-
-    let mut ticker = Ticker::every(Duration::from_hz(100));
-
-    let mut timer_offset = 0.0;
-    loop {
-        let t = Instant::now().as_micros() as f64 / 1_000_000.0 - timer_offset;
-
-        match out_power {
-            OutputPower::High => renderman.mtrx.set_gain(1.0),
-            OutputPower::Medium => renderman.mtrx.set_gain(0.7),
-            OutputPower::Low => renderman.mtrx.set_gain(0.5),
-            OutputPower::NighMode => renderman.mtrx.set_gain(0.25),
-        }
-
-        if let Some(message) = mega_subscriber.try_next_message_pure() {
-            info!("Handling message: {:?}", message);
-            match message {
-                TaskCommand::ThermalThrottleMultiplier(gain) => {
-                    renderman.mtrx.set_raw_gain(gain);
-                    if gain < 1.0 {
-                        warn!("Thermal throttling! {}", gain);
-                    }
-                }
-                TaskCommand::ReceivedIrNec(addr, cmd, repeat) => {
-                    if is_transmitting {
-                        warn!("Ignoring IR command, we are transmitting");
-                        continue;
-                    }
-
-                    match (addr, cmd, repeat) {
-                        // all those are commands of the chinese ir rgb remote
-                        (0, 70, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::DecreaseBrightness)
-                                .await;
-                        }
-                        (0, 69, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::IncreaseBrightness)
-                                .await;
-                        }
-
-                        (0, 71, false) => { // off
-                        }
-
-                        (0, 67, false) => {
-                            // on
-                            // this is used to sync clocks between multiple devices
-                            mega_publisher.publish(TaskCommand::ResetTime).await;
-                        }
-
-                        (0, 68, false) => {
-                            // animations
-                            mega_publisher.publish(TaskCommand::NextPattern).await;
-                        }
-                        // END of ir command from the chinese remote
-
-                        // startup ir command sent by another badge
-                        // say hi to the other badge
-                        (0, 66, false) => {
-                            // we do this so the animation starts in the correct time
-                            mega_publisher.publish(TaskCommand::ResetTime).await;
-
-                            mega_publisher
-                                .publish(TaskCommand::SetWorkingMode(WorkingMode::SpecialTimeout(
-                                    boot_animation.clone(),
-                                    0.5,
-                                )))
-                                .await;
-                        }
-
-                        // samsung tv remote
-                        // volume up
-                        (7, 7, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardVolumeUp,
-                                ))
-                                .await;
-                        }
-                        // volume down
-                        (7, 11, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardVolumeDown,
-                                ))
-                                .await;
-                        }
-                        //arrow right
-                        (7, 98, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardRightArrow,
-                                ))
-                                .await;
-                        }
-                        // left
-                        (7, 101, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardLeftArrow,
-                                ))
-                                .await;
-                        }
-                        // up
-                        (7, 96, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardUpArrow,
-                                ))
-                                .await;
-                        }
-                        // down
-                        (7, 97, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardDownArrow,
-                                ))
-                                .await;
-                        }
-                        // exit
-                        (7, 102, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardEscape,
-                                ))
-                                .await;
-                        }
-                        // enter
-                        (7, 104, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardEnter,
-                                ))
-                                .await;
-                        }
-                        // 1
-                        (7, 4, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard1Exclamation,
-                                ))
-                                .await;
-                        }
-                        // 2
-                        (7, 5, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard2At,
-                                ))
-                                .await;
-                        }
-                        // 3
-                        (7, 6, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard3Hash,
-                                ))
-                                .await;
-                        }
-                        // 4
-                        (7, 8, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard4Dollar,
-                                ))
-                                .await;
-                        }
-                        // 5
-                        (7, 9, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard5Percent,
-                                ))
-                                .await;
-                        }
-                        // 6
-                        (7, 10, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard6Caret,
-                                ))
-                                .await;
-                        }
-                        // 7
-                        (7, 12, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard7Ampersand,
-                                ))
-                                .await;
-                        }
-                        // 8
-                        (7, 13, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard8Asterisk,
-                                ))
-                                .await;
-                        }
-                        // 9
-                        (7, 14, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::Keyboard9OpenParens,
-                                ))
-                                .await;
-                        }
-                        // mute
-                        (7, 15, false) => {
-                            mega_publisher
-                                .publish(TaskCommand::SendHidKeyboard(
-                                    usbd_hid::descriptor::KeyboardUsage::KeyboardMute,
-                                ))
-                                .await;
-                        }
-
-                        _ => {}
-                    }
-                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
-                }
-                TaskCommand::ShortButtonPress => {
-                    mega_publisher.publish(TaskCommand::NextPattern).await;
-                }
-                TaskCommand::LongButtonPress => {
-                    mega_publisher
-                        .publish(TaskCommand::DecreaseBrightness)
-                        .await;
-                }
-
-                TaskCommand::MidiSetPixel(x, y, channel, value) => {
-                    let px = midi_framebuffer.get_pixel(x as usize, y as usize);
-
-                    let rgb = match channel {
-                        0 => (value, px.g, px.b).into(),
-                        1 => (px.r, value, px.b).into(),
-                        2 => (px.r, px.g, value).into(),
-                        _ => px,
-                    };
-
-                    midi_framebuffer.set_pixel(x as usize, y as usize, rgb);
-
-                    working_mode = WorkingMode::RawFramebuffer(midi_framebuffer);
-                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
-                }
-
-                TaskCommand::SendIrNec(_, _, _) => {
-                    is_transmitting = true;
-                }
-
-                TaskCommand::IrTxDone => {
-                    is_transmitting = false;
-                }
-
-                TaskCommand::NextPattern => {
-                    if let WorkingMode::Normal = working_mode {
-                        scene_id = (scene_id + 1) % scenes.len();
-                    } else {
-                        working_mode = WorkingMode::Normal;
-                    }
-                }
-
-                TaskCommand::IncreaseBrightness | TaskCommand::DecreaseBrightness => {
-                    if let TaskCommand::DecreaseBrightness = message {
-                        out_power = out_power.decrease();
-                    } else {
-                        out_power = out_power.increase();
-                    }
-
-                    let patt = match out_power {
-                        OutputPower::High => patterns.power_100,
-                        OutputPower::Medium => patterns.power_75,
-                        OutputPower::Low => patterns.power_50,
-                        OutputPower::NighMode => patterns.power_25,
-                    };
-
-                    // do not ruin the midi framebuffer
-                    if !matches!(working_mode, WorkingMode::RawFramebuffer(_)) {
-                        working_mode = WorkingMode::SpecialTimeout(
-                            RenderCommand {
-                                effect: Pattern::Simple(patt),
-                                color: ColorPalette::Solid((255, 255, 255).into()),
-                                ..Default::default()
-                            },
-                            t + 1.0,
-                        );
-                    }
-                }
-
-                TaskCommand::SetWorkingMode(wm) => {
-                    working_mode = wm;
-                }
-
-                TaskCommand::ResetTime => {
-                    timer_offset = Instant::now().as_micros() as f64 / 1_000_000.0;
-                }
 
-                TaskCommand::SetBrightness(b) => {
-                    out_power = b;
-                }
-
-                TaskCommand::UsbActivity => {
-                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Communication);
-                }
-
-                TaskCommand::Error => {
-                    WHITE_LED_SIGNAL.signal(WhiteLedCommand::Error);
-                }
-
-                TaskCommand::None | TaskCommand::SendHidKeyboard(_) => {}
-            }
-        }
-
-        match &working_mode {
-            WorkingMode::Normal => {
-                renderman.render(&scenes[scene_id], t);
-            }
-            WorkingMode::SpecialTimeout(scene, timeout) => {
-                renderman.render(&[scene.clone()], t);
-
-                if t > *timeout {
-                    working_mode = WorkingMode::Normal;
-                }
-            }
-            WorkingMode::Special(scene) => {
-                renderman.render(&[scene.clone()], t);
-            }
-            WorkingMode::RawFramebuffer(fb) => {
-                renderman.mtrx.raw_framebuffer = *fb;
-            }
-        }
+        #[cfg(feature = "i2c-slave")]
+        unwrap!(spawner.spawn(i2c_slave::i2c_slave_tsk(
+            i2c_slave,
+            MEGA_CHANNEL.publisher().unwrap()
+        )));
 
-        ws2812.write(renderman.mtrx.get_gamma_corrected()).await;
-        ticker.next().await;
-        renderman.mtrx.clear();
-    }
-}
+        #[cfg(feature = "uart")]
+        unwrap!(spawner.spawn(uart::uart_tsk(uart_rx, MEGA_CHANNEL.publisher().unwrap())));
 
-#[embassy_executor::task]
-async fn ir_receiver(ir_sensor: u8, publisher: MegaPublisher) {
-    // this is a mega hack to support the reception of two different IR protocols
-    // we unsafely use the same pin for both receivers
-
-    let mut nec_receiver: Receiver<Nec, embassy_rp::gpio::Input> = Receiver::builder()
-        .rc5()
-        .frequency(1_000_000)
-        .pin(Input::new(
-            unsafe { embassy_rp::gpio::AnyPin::steal(ir_sensor) },
-            Pull::None,
-        ))
-        .protocol()
-        .build();
-
-    let mut samsung_receiver: Receiver<SamsungNec, embassy_rp::gpio::Input> = Receiver::builder()
-        .rc5()
-        .frequency(1_000_000)
-        .pin(Input::new(
-            unsafe { embassy_rp::gpio::AnyPin::steal(ir_sensor) },
-            Pull::None,
-        ))
-        .protocol()
-        .build();
+        #[cfg(feature = "spi-slave")]
+        unwrap!(spawner.spawn(spi_slave::spi_slave_tsk(spi_slave)));
 
-    loop {
-        samsung_receiver.pin_mut().wait_for_any_edge().await;
-        let now = Instant::now().as_ticks() as u32;
+        #[cfg(feature = "buzzer")]
+        unwrap!(spawner.spawn(buzzer::buzzer_tsk(buzzer)));
 
-        if let Ok(Some(cmd)) = samsung_receiver.event_instant(now) {
-            publisher
-                .publish(TaskCommand::ReceivedIrNec(cmd.addr, cmd.cmd, cmd.repeat))
-                .await;
-        }
+        #[cfg(feature = "strobe-sync")]
+        unwrap!(spawner.spawn(input::strobe_sync_tsk(
+            strobe_sync,
+            input::Input::new(input::Source::Sync, MEGA_CHANNEL.publisher().unwrap())
+        )));
 
-        if let Ok(Some(cmd)) = nec_receiver.event_instant(now) {
-            publisher
-                .publish(TaskCommand::ReceivedIrNec(cmd.addr, cmd.cmd, cmd.repeat))
-                .await;
-        }
-    }
+        #[cfg(feature = "accel")]
+        unwrap!(spawner.spawn(motion::motion_tsk(
+            accel_i2c,
+            input::Input::new(input::Source::Motion, MEGA_CHANNEL.publisher().unwrap())
+        )));
+    });
 }
 
 #[embassy_executor::task]
@@ -813,10 +570,12 @@ async fn ir_blaster_tsk(
         pwm_cfg.enable = enable;
         pwm.set_config(pwm_cfg);
 
-        // why the hell does the pwm pin stay high when we disable the pwm?
-        unsafe {
-            *((0x40014000 + 11 * 8 + 0x04) as *mut u32) = if enable { 4 } else { 0x1f };
-        }
+        // why the hell does the pwm pin stay high when we disable the pwm? the ir blaster
+        // pin goes back to the pwm (4) or to no function (0x1f), from the registers of the chip
+        embassy_rp::pac::IO_BANK0
+            .gpio(11)
+            .ctrl()
+            .write(|w| w.set_funcsel(if enable { 4 } else { 0x1f }));
     }
 
     loop {
@@ -832,9 +591,9 @@ async fn ir_blaster_tsk(
 
             let mut pwm_cfg: pwm::Config = Default::default();
             pwm_cfg.enable = false;
-            // system clock is 125MHz
-            // we need to do 38khz, so 125_000_000 / 38_000 = 3289
-            pwm_cfg.top = (125_000_000 / 38_000) as u16;
+            // we need to do 38khz, 125_000_000 / 38_000 = 3289 on the rp2040 and
+            // 150_000_000 / 38_000 = 3947 on the rp2350
+            pwm_cfg.top = (embassy_rp::clocks::clk_sys_freq() / 38_000) as u16;
             pwm_cfg.compare_b = pwm_cfg.top / 2;
 
             let mut ticker = Ticker::every(Duration::from_hz(FREQUENCY as u64));
@@ -890,86 +649,3 @@ async fn white_led_task(mut white_led: Output<'static>) {
         }
     }
 }
-
-#[embassy_executor::task]
-async fn temperature(
-    mut adc: adc::Adc<'static, adc::Async>,
-    mut ts: adc::Channel<'static>,
-    publisher: MegaPublisher,
-) {
-    let mut ticker = Ticker::every(Duration::from_secs(1));
-
-    loop {
-        let temp = match adc.read(&mut ts).await {
-            Ok(v) => v,
-            Err(e) => {
-                log::error!("Error reading temperature: {:?}", e);
-                continue;
-            }
-        };
-
-        // TODO: yeah let's waste precious CPU cycles to calculate the temperature before checking if we need to throttle
-        let adc_voltage = (3.3 / 4096.0) * temp as f64;
-        let temp_degrees_c = 27.0 - (adc_voltage - 0.706) / 0.001721;
-
-        if temp_degrees_c > 50.0 {
-            // lerp from 55 to 65 degrees maps to gain from 1.0 to 0.1
-            let gain: f64 = 1.0 - (temp_degrees_c - 55.0) / 10.0;
-            let gain = gain.clamp(0.0, 1.0);
-            publisher
-                .publish(TaskCommand::ThermalThrottleMultiplier(gain as f32))
-                .await;
-        }
-
-        ticker.next().await;
-    }
-}
-
-#[embassy_executor::task]
-async fn button_tsk(mut button: Input<'static>, publisher: MegaPublisher) {
-    // if we start with the button pressed, function as a torch light
-    if button.is_low() {
-        Timer::after_millis(100).await;
-
-        publisher
-            .publish(TaskCommand::SetWorkingMode(WorkingMode::Special(
-                RenderCommand {
-                    effect: Pattern::Simple(scenes::PATTERNS.get().all_on),
-                    color: ColorPalette::Solid((255, 255, 255).into()),
-                    ..Default::default()
-                },
-            )))
-            .await;
-
-        publisher
-            .publish(TaskCommand::SetBrightness(OutputPower::High))
-            .await;
-
-        button.wait_for_high().await;
-    }
-
-    let mut press_start;
-
-    loop {
-        button.wait_for_low().await;
-        press_start = Instant::now();
-
-        match with_timeout(Duration::from_millis(1000), button.wait_for_high()).await {
-            // no timeout
-            Ok(_) => {}
-            // timeout
-            Err(_) => {
-                publisher.publish(TaskCommand::LongButtonPress).await;
-                button.wait_for_high().await;
-            }
-        }
-
-        let press_duration = Instant::now() - press_start;
-
-        if press_duration >= Duration::from_millis(50)
-            && press_duration < Duration::from_millis(1000)
-        {
-            publisher.publish(TaskCommand::ShortButtonPress).await;
-        }
-    }
-}
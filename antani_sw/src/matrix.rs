@@ -0,0 +1,256 @@
+use crate::calibration;
+use crate::color::{self, GammaTable};
+use crate::framebuffer::{
+    LedPixel, RawFramebuffer, CHAIN_SIZE, HAS_WHITE_LED, LED_MATRIX_HEIGHT, LED_MATRIX_SIZE,
+    LED_MATRIX_WIDTH, MAX_CHAINED_BADGES,
+};
+use crate::records::DEFAULT_FPS;
+use crate::rgbeffects::RenderTarget;
+
+// seconds for the gains to go from 0.0 to 1.0, a change of brightness would pop if instant
+const GAIN_SLEW_TIME: f32 = 0.2;
+
+/// 16 bits per channel, r g b w, used between the gamma correction and the leds
+type HdrPixel = [u16; 4];
+
+#[derive(Clone, Copy, Default, Debug)]
+struct HdrFramebuffer {
+    framebuffer: [HdrPixel; LED_MATRIX_SIZE],
+}
+
+impl HdrFramebuffer {
+    /// mix two framebuffers, amount 0.0 = all self, 1.0 = all other
+    fn blend(&self, other: &HdrFramebuffer, amount: f32) -> HdrFramebuffer {
+        let amount = color::gain(amount);
+
+        let mut ret = HdrFramebuffer::default();
+        for (i, (a, b)) in self
+            .framebuffer
+            .iter()
+            .zip(other.framebuffer.iter())
+            .enumerate()
+        {
+            ret.framebuffer[i] = core::array::from_fn(|c| color::mix(a[c], b[c], amount));
+        }
+        ret
+    }
+}
+
+pub struct LedMatrix {
+    pub raw_framebuffer: RawFramebuffer,
+    // the badges after this one in a daisy chain, on its right, see set_chained_badges
+    chained: [RawFramebuffer; MAX_CHAINED_BADGES - 1],
+    chained_badges: usize,
+    // the leds of the chain in the order they are sent, badge after badge
+    gamma_corrected: [LedPixel; CHAIN_SIZE],
+    corrected_gain: f32,
+    raw_gain: f32,
+    // the gains asked for, the ones above move toward them every frame
+    corrected_gain_target: f32,
+    raw_gain_target: f32,
+    // largest change of a gain in one frame
+    gain_step: f32,
+    // no channel goes above this after all the gains, 65535 = no ceiling
+    max_brightness: u16,
+    pub calibration: calibration::Calibration,
+    // built from the gamma of the calibration
+    gamma: GammaTable,
+    // clockwise quarter turns applied when sending the frame to the leds
+    rotation: u8,
+    // what was lost rounding the last frames to 8 bits, r g b w of every led
+    dither_error: [[u8; 4]; CHAIN_SIZE],
+    // the frame to fade from and how far the fade is, only for the next frame
+    fade_from: Option<(RawFramebuffer, f32)>,
+}
+
+impl LedMatrix {
+    pub fn new() -> Self {
+        Self {
+            raw_framebuffer: RawFramebuffer::new(),
+            chained: [RawFramebuffer::new(); MAX_CHAINED_BADGES - 1],
+            chained_badges: 1,
+            gamma_corrected: [LedPixel::default(); CHAIN_SIZE],
+            corrected_gain: 1.0,
+            raw_gain: 1.0,
+            corrected_gain_target: 1.0,
+            raw_gain_target: 1.0,
+            gain_step: 1.0 / (GAIN_SLEW_TIME * DEFAULT_FPS as f32),
+            max_brightness: u16::MAX,
+            calibration: Default::default(),
+            gamma: GammaTable::new(calibration::DEFAULT_GAMMA),
+            rotation: 0,
+            dither_error: [[0; 4]; CHAIN_SIZE],
+            fade_from: None,
+        }
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.corrected_gain_target = gain.clamp(0.0, 1.0);
+    }
+
+    pub fn set_raw_gain(&mut self, gain: f32) {
+        self.raw_gain_target = gain.clamp(0.0, 1.0);
+    }
+
+    /// ceiling of the leds in percent, whatever the gains
+    pub fn set_max_brightness(&mut self, percent: u8) {
+        self.max_brightness = (percent.min(100) as u32 * 65535 / 100) as u16;
+    }
+
+    /// the gains move a step every frame, so it takes the frame rate to know how big
+    pub fn set_frame_rate(&mut self, fps: u8) {
+        self.gain_step = 1.0 / (GAIN_SLEW_TIME * fps as f32);
+    }
+
+    pub fn set_calibration(&mut self, calibration: calibration::Calibration) {
+        // building the tables takes a while, only do it when needed
+        if calibration.gamma != self.calibration.gamma {
+            self.gamma = GammaTable::new(calibration.gamma);
+        }
+        self.calibration = calibration;
+    }
+
+    pub fn set_rotation(&mut self, quarter_turns: u8) {
+        self.rotation = quarter_turns % 4;
+    }
+
+    /// the badges of the daisy chain, this one included, the effects are drawn on all of
+    /// them side by side as one display
+    pub fn set_chained_badges(&mut self, badges: u8) {
+        self.chained_badges = (badges as usize).clamp(1, MAX_CHAINED_BADGES);
+    }
+
+    /// the frame is shown mixed with from, amount 0.0 = all from, 1.0 = all the new frame
+    pub fn fade_from(&mut self, from: RawFramebuffer, amount: f32) {
+        self.fade_from = Some((from, amount));
+    }
+
+    /// everything from the gamma correction on is done with 16 bits per channel,
+    /// so the gains don't add up their rounding errors
+    ///
+    /// the calibration is the one of this badge, the other badges of a chain get none,
+    /// and a chain isn't turned
+    fn to_hdr(&self, framebuffer: &RawFramebuffer, own: bool) -> HdrFramebuffer {
+        let mut framebuffer = *framebuffer;
+        if own {
+            if self.chained_badges == 1 {
+                framebuffer = framebuffer.rotate(self.rotation);
+            }
+            self.calibration.remap(&mut framebuffer.framebuffer);
+        }
+
+        let corrected_gain = color::gain(self.corrected_gain);
+        let raw_gain = color::gain(self.raw_gain);
+
+        let mut ret = HdrFramebuffer::default();
+        for (i, colour) in framebuffer.framebuffer.iter().enumerate() {
+            // leds from different bins have different whites and brightness
+            let [r, g, b] = if own {
+                self.calibration.scale(i)
+            } else {
+                [255; 3]
+            };
+            let channels = [(colour.r, r), (colour.g, g), (colour.b, b), (colour.w, 255)];
+
+            for (channel, (c, scale)) in channels.into_iter().enumerate() {
+                let c = color::apply_gain(color::expand(c), corrected_gain);
+                let c = color::apply_gain(self.gamma.correct(channel, c), raw_gain);
+                ret.framebuffer[i][channel] = color::scale(c, scale);
+            }
+            ret.framebuffer[i] = color::limit(ret.framebuffer[i], self.max_brightness);
+        }
+        ret
+    }
+
+    fn update_gamma_correction_and_gain(&mut self) {
+        let slew =
+            |gain: f32, target: f32| gain + (target - gain).clamp(-self.gain_step, self.gain_step);
+        self.corrected_gain = slew(self.corrected_gain, self.corrected_gain_target);
+        self.raw_gain = slew(self.raw_gain, self.raw_gain_target);
+
+        let mut hdr = self.to_hdr(&self.raw_framebuffer, true);
+        if let Some((from, amount)) = self.fade_from.take() {
+            // mixed after the gamma correction, in linear light, so the fade has no dark dip
+            hdr = self.to_hdr(&from, true).blend(&hdr, amount);
+        }
+
+        let mut chain = [HdrFramebuffer::default(); MAX_CHAINED_BADGES];
+        chain[0] = hdr;
+        for (badge, framebuffer) in self.chained[..self.chained_badges - 1].iter().enumerate() {
+            chain[badge + 1] = self.to_hdr(framebuffer, false);
+        }
+
+        // the badges after the chain stay black, so the ones taken off it are turned off
+        let leds = chain.iter().flat_map(|hdr| hdr.framebuffer.iter());
+        for (i, colour) in leds.enumerate() {
+            let mut out = [0u8; 4];
+            for (channel, c) in colour.iter().enumerate() {
+                // temporal dithering: the low byte that is rounded away is carried to the
+                // next frames, so at low gain a dim led alternates between two levels
+                // instead of stepping
+                let wanted = *c as u32 + self.dither_error[i][channel] as u32;
+                out[channel] = (wanted >> 8).min(255) as u8;
+                self.dither_error[i][channel] = if *c > 0 && wanted < 0x10000 {
+                    wanted as u8
+                } else {
+                    // leds that are off must stay off
+                    0
+                };
+            }
+
+            self.gamma_corrected[i] = LedPixel {
+                r: out[0],
+                g: out[1],
+                b: out[2],
+                w: out[3],
+            };
+        }
+    }
+
+    pub fn set_all(&mut self, rgb: LedPixel) {
+        self.raw_framebuffer.set_all(rgb);
+        self.chained.iter_mut().for_each(|badge| badge.set_all(rgb));
+    }
+
+    /// the leds of the whole chain, the ones after the last badge are off
+    pub fn get_gamma_corrected(&mut self) -> &[LedPixel; CHAIN_SIZE] {
+        self.update_gamma_correction_and_gain();
+
+        if HAS_WHITE_LED {
+            self.gamma_corrected
+                .iter_mut()
+                .for_each(LedPixel::set_white);
+        }
+        &self.gamma_corrected
+    }
+}
+
+impl RenderTarget for LedMatrix {
+    fn size(&self) -> (usize, usize) {
+        (LED_MATRIX_WIDTH * self.chained_badges, LED_MATRIX_HEIGHT)
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
+        match x / LED_MATRIX_WIDTH {
+            0 => self.raw_framebuffer.set_pixel(x, y, colour),
+            badge if badge < self.chained_badges => {
+                self.chained[badge - 1].set_pixel(x % LED_MATRIX_WIDTH, y, colour)
+            }
+            _ => {}
+        }
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
+        match x / LED_MATRIX_WIDTH {
+            0 => self.raw_framebuffer.get_pixel(x, y),
+            badge if badge < self.chained_badges => {
+                self.chained[badge - 1].get_pixel(x % LED_MATRIX_WIDTH, y)
+            }
+            _ => LedPixel::default(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.set_all((0, 0, 0).into());
+    }
+}
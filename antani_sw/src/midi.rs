@@ -0,0 +1,115 @@
+use crate::rgbeffects::{ColorPalette, FragmentShader, RenderCommand, RenderManager};
+use crate::scenes::Scene;
+use crate::time::Time;
+use crate::{LedMatrix, LedPixel, OutputPower, TaskCommand};
+
+// midi control change numbers
+const CC_MODULATION: u8 = 1;
+const CC_VOLUME: u8 = 7;
+const CC_RED: u8 = 20;
+const CC_GREEN: u8 = 21;
+const CC_BLUE: u8 = 22;
+const CC_RAINBOW: u8 = 23;
+const CC_ALL_NOTES_OFF: u8 = 123;
+
+/// live changes to the current scene from the midi control change messages
+#[derive(Default)]
+pub struct MidiControls {
+    color: Option<LedPixel>,
+    rainbow_speed: Option<f32>,
+    shader_multiplier: Option<f32>,
+}
+
+impl MidiControls {
+    /// controller values go from 0 to 127
+    pub fn control_change(&mut self, controller: u8, value: u8) -> Option<TaskCommand> {
+        let value = value.min(127);
+
+        match controller {
+            CC_MODULATION => {
+                // from 0x to 2x, the center of the wheel leaves the shaders as they are
+                self.shader_multiplier = Some(value as f32 / 64.0);
+            }
+            CC_VOLUME => {
+                return Some(TaskCommand::SetBrightness(OutputPower::scaled(value, 127)));
+            }
+            CC_RED | CC_GREEN | CC_BLUE => {
+                let mut color = self.color.unwrap_or_default();
+                let value = value * 2;
+
+                match controller {
+                    CC_RED => color.r = value,
+                    CC_GREEN => color.g = value,
+                    _ => color.b = value,
+                }
+
+                self.color = Some(color);
+                self.rainbow_speed = None;
+            }
+            CC_RAINBOW => {
+                // up to 2 full rainbows per second, 0 goes back to the scene colors
+                self.rainbow_speed = (value > 0).then_some(value as f32 / 64.0);
+                self.color = None;
+            }
+            CC_ALL_NOTES_OFF => *self = Self::default(),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn apply(&self, layer: &RenderCommand) -> RenderCommand {
+        let mut layer = layer.clone();
+
+        if let Some(color) = self.color {
+            layer.color = ColorPalette::Solid(color);
+        }
+
+        if let Some(speed) = self.rainbow_speed {
+            layer.color = ColorPalette::Rainbow(speed);
+        }
+
+        if let Some(multiplier) = self.shader_multiplier {
+            for shader in layer
+                .pattern_shaders
+                .iter_mut()
+                .chain(layer.screen_shaders.iter_mut())
+            {
+                match shader {
+                    FragmentShader::Breathing(p)
+                    | FragmentShader::Blinking(p)
+                    | FragmentShader::LowPass(p)
+                    | FragmentShader::LowPassWithPeak(p)
+                    | FragmentShader::Rainbow2D(p)
+                    | FragmentShader::AudioLevel(p)
+                    | FragmentShader::BeatPulse(p)
+                    | FragmentShader::Glitch(p)
+                    | FragmentShader::Strobe(p)
+                    | FragmentShader::Heartbeat(p)
+                    | FragmentShader::PaletteCycle(p)
+                    | FragmentShader::Flash(_, p) => *p *= multiplier,
+                    // not a speed
+                    FragmentShader::WhitePoint(_) | FragmentShader::Blur(_) => {}
+                }
+            }
+        }
+
+        layer
+    }
+
+    /// renders the scene with the midi changes on top
+    pub fn render(
+        &self,
+        renderman: &mut RenderManager<LedMatrix>,
+        scene: &[RenderCommand],
+        t: Time,
+    ) {
+        if self.color.is_none() && self.rainbow_speed.is_none() && self.shader_multiplier.is_none()
+        {
+            return renderman.render(scene, t);
+        }
+
+        let scene: Scene = scene.iter().map(|layer| self.apply(layer)).collect();
+        renderman.render(&scene, t);
+    }
+}
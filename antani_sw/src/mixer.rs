@@ -0,0 +1,80 @@
+// two scenes at once, mixed with a crossfader like the two decks of a dj: the current
+// scene and a second one, from the current one only to the second one only
+//
+// driven over usb, or by a nec packet on MIX_IR_ADDRESS from an event host: a command up to
+// MAX_AMOUNT moves the crossfader, in percent of the second scene, SCENE plus the number of
+// a scene mixes it in from the current one only, STOP ends the mix
+
+use crate::color::lerp;
+use crate::rgbeffects::{RenderManager, RenderTarget, ShaderPersistentData};
+use crate::scenes::Scenes;
+use crate::time::Time;
+use crate::{LedMatrix, LedPixel, TaskCommand, CHAIN_SIZE};
+
+pub const MIX_IR_ADDRESS: u8 = 0x57;
+
+const MAX_AMOUNT: u8 = 100;
+const STOP: u8 = 0x7F;
+const SCENE: u8 = 0x80;
+
+#[derive(Clone)]
+pub struct Mix {
+    /// the second scene
+    pub scene: usize,
+    /// from 0.0, the current scene only, to 1.0, the second one only
+    pub amount: f32,
+    // the lowpass, the walkers and the rest of the state of the effects of the second
+    // scene, apart from the ones of the current scene
+    persistent_data: ShaderPersistentData,
+}
+
+impl Mix {
+    pub fn new(scene: usize) -> Self {
+        Self {
+            scene,
+            amount: 0.0,
+            persistent_data: ShaderPersistentData::default(),
+        }
+    }
+
+    /// mixes the second scene into the frame of the current one, already rendered, on all
+    /// the badges of the chain and before the overlays
+    pub fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, scenes: &Scenes, t: Time) {
+        let (width, height) = renderman.mtrx.size();
+        let mut current = [LedPixel::default(); CHAIN_SIZE];
+        for y in 0..height {
+            for x in 0..width {
+                current[y * width + x] = renderman.mtrx.get_pixel(x, y);
+            }
+        }
+
+        // the beats and the other inputs are the same for both scenes
+        self.persistent_data.copy_inputs(&renderman.persistent_data);
+        core::mem::swap(&mut renderman.persistent_data, &mut self.persistent_data);
+        renderman.mtrx.clear();
+        renderman.render(&scenes[self.scene], t);
+        core::mem::swap(&mut renderman.persistent_data, &mut self.persistent_data);
+
+        for y in 0..height {
+            for x in 0..width {
+                let second = renderman.mtrx.get_pixel(x, y);
+                let pixel = lerp(current[y * width + x], second, self.amount);
+                renderman.mtrx.set_pixel(x, y, pixel);
+            }
+        }
+    }
+}
+
+/// the command for a packet on MIX_IR_ADDRESS
+pub fn received(cmd: u8) -> Option<TaskCommand> {
+    match cmd {
+        0..=MAX_AMOUNT => Some(TaskCommand::SetMixAmount(cmd)),
+        // past the last scene
+        STOP => Some(TaskCommand::SetMixScene(u8::MAX)),
+        SCENE.. => Some(TaskCommand::SetMixScene(cmd - SCENE)),
+        _ => {
+            log::warn!("Unknown mix command {:#04x}", cmd);
+            None
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use embedded_hal_async::i2c::I2c;
+use num_traits::real::Real;
+
+use crate::TaskCommand;
+
+#[cfg(feature = "accel")]
+use embassy_time::{Duration, Ticker};
+
+#[cfg(feature = "accel")]
+use crate::input::Input;
+
+// the accelerometer is an add-on, on I2C0 with SDA on GPIO4 and SCL on GPIO5
+#[cfg(feature = "accel")]
+embassy_rp::bind_interrupts!(pub struct Irqs {
+    I2C0_IRQ => embassy_rp::i2c::InterruptHandler<embassy_rp::peripherals::I2C0>;
+});
+
+#[cfg(feature = "accel")]
+pub type MotionI2c =
+    embassy_rp::i2c::I2c<'static, embassy_rp::peripherals::I2C0, embassy_rp::i2c::Async>;
+
+/// which side of the badge points up, the sensor is mounted with
+/// x towards the right of the badge, y towards the top and z out of the front
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Upright,
+    UpsideDown,
+    RotatedLeft,  // the right side points up
+    RotatedRight, // the left side points up
+    FaceUp,
+    FaceDown,
+}
+
+impl Orientation {
+    /// clockwise quarter turns that keep the image upright,
+    /// None when lying flat because any rotation would be as good
+    pub fn quarter_turns(self) -> Option<u8> {
+        match self {
+            Orientation::Upright => Some(0),
+            Orientation::RotatedLeft => Some(1),
+            Orientation::UpsideDown => Some(2),
+            Orientation::RotatedRight => Some(3),
+            Orientation::FaceUp | Orientation::FaceDown => None,
+        }
+    }
+}
+
+pub trait Accelerometer {
+    type Error: core::fmt::Debug;
+
+    /// acceleration on x, y and z, in milli-g
+    async fn read(&mut self) -> Result<[i32; 3], Self::Error>;
+}
+
+pub struct Lis3dh<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Lis3dh<I> {
+    const WHO_AM_I: u8 = 0x0F;
+    const ID: u8 = 0x33;
+    const CTRL_REG1: u8 = 0x20;
+    const CTRL_REG4: u8 = 0x23;
+    const OUT_X_L: u8 = 0x28;
+    // set on the register address to read more than one register
+    const AUTO_INCREMENT: u8 = 0x80;
+
+    /// looks for the sensor on both its addresses and sets it up
+    pub async fn probe(mut i2c: I) -> Result<Self, I> {
+        for address in [0x18, 0x19] {
+            let mut id = [0];
+            if i2c
+                .write_read(address, &[Self::WHO_AM_I], &mut id)
+                .await
+                .is_err()
+                || id[0] != Self::ID
+            {
+                continue;
+            }
+
+            // 100Hz, all the axes
+            let ctrl1 = i2c.write(address, &[Self::CTRL_REG1, 0x57]).await;
+            // block data update, high resolution, +-2g
+            let ctrl4 = i2c.write(address, &[Self::CTRL_REG4, 0x88]).await;
+
+            if ctrl1.is_ok() && ctrl4.is_ok() {
+                return Ok(Self { i2c, address });
+            }
+        }
+
+        Err(i2c)
+    }
+}
+
+impl<I: I2c> Accelerometer for Lis3dh<I> {
+    type Error = I::Error;
+
+    async fn read(&mut self) -> Result<[i32; 3], Self::Error> {
+        let mut data = [0; 6];
+        self.i2c
+            .write_read(
+                self.address,
+                &[Self::OUT_X_L | Self::AUTO_INCREMENT],
+                &mut data,
+            )
+            .await?;
+
+        // 12 bits left aligned, 1mg per digit
+        let axis = |i: usize| (i16::from_le_bytes([data[i], data[i + 1]]) >> 4) as i32;
+        Ok([axis(0), axis(2), axis(4)])
+    }
+}
+
+pub struct Mpu6050<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Mpu6050<I> {
+    const ADDRESS: u8 = 0x68;
+    const WHO_AM_I: u8 = 0x75;
+    const PWR_MGMT_1: u8 = 0x6B;
+    const ACCEL_CONFIG: u8 = 0x1C;
+    const ACCEL_XOUT_H: u8 = 0x3B;
+    // at +-2g
+    const LSB_PER_G: i32 = 16384;
+
+    pub async fn probe(mut i2c: I) -> Result<Self, I> {
+        let mut id = [0];
+        let found = i2c
+            .write_read(Self::ADDRESS, &[Self::WHO_AM_I], &mut id)
+            .await
+            .is_ok()
+            && id[0] == Self::ADDRESS;
+
+        // out of sleep, +-2g
+        if found
+            && i2c
+                .write(Self::ADDRESS, &[Self::PWR_MGMT_1, 0])
+                .await
+                .is_ok()
+            && i2c
+                .write(Self::ADDRESS, &[Self::ACCEL_CONFIG, 0])
+                .await
+                .is_ok()
+        {
+            return Ok(Self { i2c });
+        }
+
+        Err(i2c)
+    }
+}
+
+impl<I: I2c> Accelerometer for Mpu6050<I> {
+    type Error = I::Error;
+
+    async fn read(&mut self) -> Result<[i32; 3], Self::Error> {
+        let mut data = [0; 6];
+        self.i2c
+            .write_read(Self::ADDRESS, &[Self::ACCEL_XOUT_H], &mut data)
+            .await?;
+
+        let axis = |i: usize| i16::from_be_bytes([data[i], data[i + 1]]) as i32;
+        Ok([axis(0), axis(2), axis(4)].map(|a| a * 1000 / Self::LSB_PER_G))
+    }
+}
+
+// all in milli-g, away from the 1g of gravity
+const SHAKE_THRESHOLD: f32 = 800.0;
+const TAP_THRESHOLD: f32 = 1500.0;
+const CALM_THRESHOLD: f32 = 300.0;
+// the leaky counter of the strong movements that makes a shake
+const SHAKE_LEAK: f32 = 0.95;
+const SHAKE_COUNT: f32 = 5.0;
+// ignore the shakes right after another one, so a long shake or a run
+// doesn't fire one event after the other, in samples
+const SHAKE_COOLDOWN: u16 = 200;
+// the turnarounds of a waved badge are strong accelerations along x
+const SWING_THRESHOLD: f32 = 1000.0;
+// a tap is a spike shorter than this, in samples
+const TAP_MAX_SAMPLES: u8 = 10;
+// an orientation must be stable for this many samples
+const ORIENTATION_SAMPLES: u8 = 20;
+
+/// turns the accelerometer samples into shake, swing, tap and orientation events
+pub struct MotionDetector {
+    shake: f32,
+    since_shake: u16,
+    swing_rightwards: bool,
+    spike: Option<u8>, // samples since the spike started
+    orientation: Option<Orientation>,
+    candidate: Option<(Orientation, u8)>,
+}
+
+impl Default for MotionDetector {
+    fn default() -> Self {
+        Self {
+            shake: 0.0,
+            // the first shake is never ignored
+            since_shake: SHAKE_COOLDOWN,
+            swing_rightwards: false,
+            spike: None,
+            orientation: None,
+            candidate: None,
+        }
+    }
+}
+
+impl MotionDetector {
+    /// takes a sample in milli-g, at 100Hz
+    pub fn sample(&mut self, [x, y, z]: [i32; 3]) -> Option<TaskCommand> {
+        let (x, y, z) = (x as f32, y as f32, z as f32);
+        let deviation = ((x * x + y * y + z * z).sqrt() - 1000.0).abs();
+
+        self.shake *= SHAKE_LEAK;
+        if deviation > SHAKE_THRESHOLD {
+            self.shake += 1.0;
+        }
+        self.since_shake = self.since_shake.saturating_add(1);
+
+        if self.shake > SHAKE_COUNT {
+            self.shake = 0.0;
+            self.spike = None;
+
+            if self.since_shake > SHAKE_COOLDOWN {
+                self.since_shake = 0;
+                return Some(TaskCommand::Shake);
+            }
+        }
+
+        // pushed towards the right at the left end of the swing and the other way around
+        if x.abs() > SWING_THRESHOLD && (x > 0.0) != self.swing_rightwards {
+            self.swing_rightwards = x > 0.0;
+            return Some(TaskCommand::Swing(self.swing_rightwards));
+        }
+
+        match self.spike {
+            None if deviation > TAP_THRESHOLD => self.spike = Some(0),
+            Some(samples) if deviation < CALM_THRESHOLD => {
+                self.spike = None;
+
+                // a long movement isn't a tap
+                if samples <= TAP_MAX_SAMPLES {
+                    return Some(TaskCommand::Tap);
+                }
+            }
+            Some(samples) => self.spike = Some(samples.saturating_add(1)),
+            None => {}
+        }
+
+        // the direction of gravity only makes sense while still
+        if deviation > CALM_THRESHOLD {
+            self.candidate = None;
+            return None;
+        }
+
+        let orientation = if x.abs() > y.abs() && x.abs() > z.abs() {
+            if x > 0.0 {
+                Orientation::RotatedLeft
+            } else {
+                Orientation::RotatedRight
+            }
+        } else if y.abs() > z.abs() {
+            if y > 0.0 {
+                Orientation::Upright
+            } else {
+                Orientation::UpsideDown
+            }
+        } else if z > 0.0 {
+            Orientation::FaceUp
+        } else {
+            Orientation::FaceDown
+        };
+
+        let stable = match self.candidate {
+            Some((candidate, samples)) if candidate == orientation => samples.saturating_add(1),
+            _ => 0,
+        };
+        self.candidate = Some((orientation, stable));
+
+        if stable >= ORIENTATION_SAMPLES && self.orientation != Some(orientation) {
+            self.orientation = Some(orientation);
+            return Some(TaskCommand::Orientation(orientation));
+        }
+
+        None
+    }
+}
+
+#[cfg(feature = "accel")]
+async fn run(sensor: &mut impl Accelerometer, input: &Input) {
+    let mut detector = MotionDetector::default();
+    let mut ticker = Ticker::every(Duration::from_hz(100));
+
+    loop {
+        ticker.next().await;
+
+        let sample = match sensor.read().await {
+            Ok(sample) => sample,
+            Err(e) => {
+                log::error!("Error reading the accelerometer: {:?}", e);
+                continue;
+            }
+        };
+
+        if let Some(event) = detector.sample(sample) {
+            log::info!("Motion event: {:?}", event);
+            input.send(event).await;
+        }
+    }
+}
+
+#[cfg(feature = "accel")]
+#[embassy_executor::task]
+pub async fn motion_tsk(i2c: MotionI2c, input: Input) {
+    let i2c = match Lis3dh::probe(i2c).await {
+        Ok(mut sensor) => return run(&mut sensor, &input).await,
+        Err(i2c) => i2c,
+    };
+
+    match Mpu6050::probe(i2c).await {
+        Ok(mut sensor) => run(&mut sensor, &input).await,
+        Err(_) => log::warn!("No accelerometer found"),
+    }
+}
@@ -0,0 +1,192 @@
+// the notifications drawn on top of the scenes, from the host, the temperature and the
+// games: they wait in a queue and the most important one is shown first, the ones that
+// wait too long are dropped
+//
+// a notification already shown or waiting isn't queued twice, the one shown starts over
+// and the one waiting waits longer, so a source repeating itself doesn't fill the queue
+
+use heapless::Vec;
+
+use crate::buzzer;
+use crate::rgbeffects::{RenderCommand, RenderManager};
+use crate::scenes::{self, Notification};
+use crate::time::Time;
+use crate::LedMatrix;
+
+const MAX_WAITING: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Notification {
+    fn priority(&self) -> Priority {
+        match self {
+            Notification::Overheating | Notification::BuildFailed => Priority::High,
+            Notification::Mention | Notification::Message | Notification::BuildPassed => {
+                Priority::Normal
+            }
+            Notification::Challenge => Priority::Low,
+        }
+    }
+
+    /// seconds on top of the scene
+    fn duration(&self) -> f32 {
+        match self {
+            Notification::Challenge => 1.5,
+            _ => scenes::NOTIFICATION_DURATION,
+        }
+    }
+
+    /// seconds it can wait to be shown, after that it's old news
+    fn expiry(&self) -> f32 {
+        match self {
+            Notification::Challenge => 5.0,
+            Notification::Overheating => 10.0,
+            _ => 60.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Waiting {
+    notification: Notification,
+    expires: Time,
+}
+
+#[derive(Clone, Debug)]
+struct Showing {
+    notification: Notification,
+    overlay: RenderCommand,
+    start: Time,
+}
+
+#[derive(Default)]
+pub struct Queue {
+    showing: Option<Showing>,
+    // in the order they came
+    waiting: Vec<Waiting, MAX_WAITING>,
+    // do not disturb, the notifications are dropped
+    muted: bool,
+}
+
+impl Queue {
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            self.showing = None;
+            self.waiting.clear();
+        }
+    }
+
+    pub fn push(&mut self, notification: Notification, t: Time) {
+        if self.muted {
+            log::info!("Muted notification {:?}", notification);
+            return;
+        }
+
+        if let Some(showing) = &mut self.showing {
+            if showing.notification == notification {
+                showing.start = t;
+                return;
+            }
+        }
+        if let Some(waiting) = self
+            .waiting
+            .iter_mut()
+            .find(|w| w.notification == notification)
+        {
+            waiting.expires = t + notification.expiry();
+            return;
+        }
+
+        // a more important one takes the place of the one shown, which waits to be shown
+        // again from the start
+        let preempts = self
+            .showing
+            .as_ref()
+            .is_some_and(|showing| notification.priority() > showing.notification.priority());
+        if preempts {
+            if let Some(preempted) = self.showing.take() {
+                self.enqueue(preempted.notification, t);
+            }
+            self.show(notification, t);
+        } else {
+            self.enqueue(notification, t);
+        }
+    }
+
+    /// when full, the oldest of the least important ones is dropped, or the new one if
+    /// it's less important than all of them
+    fn enqueue(&mut self, notification: Notification, t: Time) {
+        let waiting = Waiting {
+            expires: t + notification.expiry(),
+            notification,
+        };
+
+        if self.waiting.is_full() {
+            let least = self
+                .waiting
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, w)| (w.notification.priority(), *i))
+                .map(|(i, w)| (i, w.notification.priority()));
+
+            match least {
+                Some((i, priority)) if priority <= waiting.notification.priority() => {
+                    log::info!("Dropped notification {:?}", self.waiting[i].notification);
+                    self.waiting.remove(i);
+                }
+                _ => {
+                    log::info!("Dropped notification {:?}", waiting.notification);
+                    return;
+                }
+            }
+        }
+        let _ = self.waiting.push(waiting);
+    }
+
+    fn show(&mut self, notification: Notification, t: Time) {
+        log::info!("Notification: {:?}", notification);
+        buzzer::play(buzzer::Sound::Notification);
+
+        self.showing = Some(Showing {
+            overlay: scenes::notification(&notification),
+            notification,
+            start: t,
+        });
+    }
+
+    /// the most important notification that hasn't expired, the oldest first
+    fn next(&mut self, t: Time) -> Option<Notification> {
+        self.waiting.retain(|w| w.expires >= t);
+
+        let (i, _) = self
+            .waiting
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, w)| (w.notification.priority(), core::cmp::Reverse(*i)))?;
+        Some(self.waiting.remove(i).notification)
+    }
+
+    /// draws the notification shown, if any, on top of what is already drawn
+    pub fn render(&mut self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        if let Some(showing) = &self.showing {
+            if t.since(showing.start) > showing.notification.duration() {
+                self.showing = None;
+            }
+        }
+        if self.showing.is_none() {
+            if let Some(notification) = self.next(t) {
+                self.show(notification, t);
+            }
+        }
+
+        if let Some(showing) = &self.showing {
+            renderman.render(&[showing.overlay.clone()], t.relative_to(showing.start));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use embassy_rp::flash::ERASE_SIZE;
+
+use crate::config::{BadgeFlash, PALETTE_TABLE_OFFSET};
+use crate::rgbeffects::PaletteTable;
+
+// the colors of ColorPalette::Indexed, so a theme can change without a new firmware
+//
+// same layout as the calibration: magic, postcard data length, then the colors
+// serialized with postcard
+
+const PALETTE_TABLE_MAGIC: u32 = 0x4D42504C; // "MBPL"
+const HEADER_SIZE: usize = 8;
+const MAX_SIZE: usize = 128;
+
+/// reads the table from flash, an erased or invalid sector gives an empty table
+pub fn load(flash: &mut BadgeFlash) -> PaletteTable {
+    let mut data = [0; HEADER_SIZE + MAX_SIZE];
+
+    if let Err(e) = flash.blocking_read(PALETTE_TABLE_OFFSET, &mut data) {
+        log::error!("Error reading palette table: {:?}", e);
+        return PaletteTable::new();
+    }
+
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    if magic != PALETTE_TABLE_MAGIC || len > MAX_SIZE {
+        log::info!("No palette table in flash");
+        return PaletteTable::new();
+    }
+
+    postcard::from_bytes(&data[HEADER_SIZE..HEADER_SIZE + len]).unwrap_or_else(|e| {
+        log::error!("Error decoding palette table: {:?}", e);
+        PaletteTable::new()
+    })
+}
+
+pub fn store(flash: &mut BadgeFlash, table: &PaletteTable) {
+    let mut data = [0xFF; HEADER_SIZE + MAX_SIZE];
+
+    let len = match postcard::to_slice(table, &mut data[HEADER_SIZE..]) {
+        Ok(encoded) => encoded.len(),
+        Err(e) => {
+            log::error!("Error encoding palette table: {:?}", e);
+            return;
+        }
+    };
+    data[0..4].copy_from_slice(&PALETTE_TABLE_MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+
+    if let Err(e) = flash.blocking_erase(
+        PALETTE_TABLE_OFFSET,
+        PALETTE_TABLE_OFFSET + ERASE_SIZE as u32,
+    ) {
+        log::error!("Error erasing palette table: {:?}", e);
+        return;
+    }
+
+    if let Err(e) = flash.blocking_write(PALETTE_TABLE_OFFSET, &data[..HEADER_SIZE + len]) {
+        log::error!("Error writing palette table: {:?}", e);
+    }
+}
@@ -0,0 +1,97 @@
+// party sync: a leader badge sends its time and its scene over ir, the followers show
+// the same scene in step with it, for walls of badges
+//
+// a nec command is a byte, so the beacon is a packet with the low byte of the whole
+// seconds, sent as soon as the leader gets there, one with the high byte and a packet with
+// the scene; the party time wraps at PARTY_TIME_WRAP seconds, hours after the start of a
+// party so the effects don't jump, and it's only for the scenes, everything else keeps the
+// time of the badge
+
+use crate::time::Time;
+use crate::timesync::TimeSync;
+use crate::TaskCommand;
+
+// the nec addresses of the beacon, the command is the seconds or the scene of the leader
+pub const PARTY_TIME_IR_ADDRESS: u8 = 0x50;
+pub const PARTY_SCENE_IR_ADDRESS: u8 = 0x51;
+pub const PARTY_TIME_HIGH_IR_ADDRESS: u8 = 0x4F;
+
+/// the party time starts over here, so the seconds fit in two packets
+const PARTY_TIME_WRAP: Time = Time::from_micros(65_536_000_000);
+/// seconds between two beacons
+const BEACON_PERIOD: f32 = 2.0;
+/// from the start of the time packet on the leader to the follower decoding it,
+/// a nec packet is about 68 ms long
+const BEACON_DELAY: f32 = 0.07;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Role {
+    #[default]
+    Off,
+    Leader,
+    Follower,
+}
+
+#[derive(Default)]
+pub struct Party {
+    pub role: Role,
+    beacon: u32,
+    sync: TimeSync,
+    // the low byte of the seconds of the leader and the time it arrived, for the packet
+    // with the high byte after it
+    low: Option<(u8, Time)>,
+}
+
+impl Party {
+    pub fn set_role(&mut self, role: Role) {
+        log::info!("Party sync: {:?}", role);
+        self.role = role;
+        self.sync.reset();
+        self.low = None;
+    }
+
+    /// the time for the scenes, the time of the leader for the followers
+    pub fn time(&self, t: Time) -> Time {
+        match self.role {
+            Role::Off => t,
+            Role::Leader => Time::from_micros(t.as_micros() % PARTY_TIME_WRAP.as_micros()),
+            Role::Follower => self.sync.time(t, PARTY_TIME_WRAP),
+        }
+    }
+
+    /// the packets of the beacon when it's time to send one, only for the leader
+    pub fn beacon(&mut self, t: Time, scene: usize) -> Option<[TaskCommand; 3]> {
+        let beacon = self.time(t).steps(1.0 / BEACON_PERIOD);
+        if self.role != Role::Leader || beacon == self.beacon {
+            return None;
+        }
+        self.beacon = beacon;
+
+        let seconds = (beacon as f32 * BEACON_PERIOD) as u32;
+        Some([
+            TaskCommand::SendIrNec(PARTY_TIME_IR_ADDRESS, seconds as u8, false),
+            TaskCommand::SendIrNec(PARTY_TIME_HIGH_IR_ADDRESS, (seconds >> 8) as u8, false),
+            TaskCommand::SendIrNec(PARTY_SCENE_IR_ADDRESS, scene as u8, false),
+        ])
+    }
+
+    /// the time packet with the low byte of the seconds of the leader arrived at t, the
+    /// time of the leader is the one of this packet, sent on the second
+    pub fn received_time(&mut self, low: u8, t: Time) {
+        self.low = Some((low, t));
+    }
+
+    /// the packet with the high byte of the seconds of the leader arrived, only for the
+    /// followers, it's left out when the packet before it was lost
+    pub fn received_time_high(&mut self, high: u8) {
+        let Some((low, t)) = self.low.take() else {
+            return;
+        };
+
+        if self.role == Role::Follower {
+            let seconds = ((high as u32) << 8) | low as u32;
+            let leader = Time::from_micros(seconds as i64 * 1_000_000) + BEACON_DELAY;
+            self.sync.beacon(leader, t, PARTY_TIME_WRAP);
+        }
+    }
+}
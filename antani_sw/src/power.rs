@@ -0,0 +1,138 @@
+use embassy_rp::gpio::Input;
+#[cfg(feature = "rp2040")]
+use embassy_rp::gpio::{DormantWakeConfig, Pull};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+/// set to true to also wake up from DORMANT when an IR packet arrives
+pub const WAKE_ON_IR: bool = false;
+
+// signaled by the render task once the leds have been blanked, the button task
+// owns the wake-up pin so it's the one that actually puts the chip to sleep
+pub static DORMANT_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// stops every clock of the chip until the button (or the IR receiver) pulls its pin low
+///
+/// the caller must make sure the leds are already off, the ws2812 keep showing
+/// the last frame they received while we are sleeping
+pub async fn enter_dormant(button: &mut Input<'static>, ir_sensor: u8) {
+    // don't wake up immediately because the user is still holding the button
+    button.wait_for_high().await;
+    // give the white led and the ir blaster the time to finish what they are doing
+    Timer::after_millis(300).await;
+
+    log::info!("Entering dormant mode");
+    sleep_until_pressed(button, ir_sensor).await;
+    log::info!("Woke up from dormant mode");
+
+    // the press that woke us up must not be handled as a normal button press
+    button.wait_for_high().await;
+}
+
+#[cfg(feature = "rp2040")]
+async fn sleep_until_pressed(button: &mut Input<'static>, ir_sensor: u8) {
+    // same hack as the ir receiver, the pin is already owned by that task
+    let mut ir_pin = Input::new(
+        unsafe { embassy_rp::gpio::AnyPin::steal(ir_sensor) },
+        Pull::None,
+    );
+
+    {
+        let _button_wake = button.dormant_wake(DormantWakeConfig {
+            edge_low: true,
+            ..Default::default()
+        });
+        let _ir_wake = WAKE_ON_IR.then(|| {
+            ir_pin.dormant_wake(DormantWakeConfig {
+                edge_low: true,
+                ..Default::default()
+            })
+        });
+
+        embassy_rp::clocks::dormant_sleep();
+    }
+
+    // dropping the stolen pin would disconnect it from the ir receiver
+    core::mem::forget(ir_pin);
+}
+
+/// embassy can't put the rp2350 in dormant yet, the chip only waits with the leds off
+#[cfg(feature = "rp2350")]
+async fn sleep_until_pressed(button: &mut Input<'static>, _ir_sensor: u8) {
+    button.wait_for_low().await;
+}
+
+/// how long a single press of the sleep key adds to the timer
+const SLEEP_TIMER_STEP: Duration = Duration::from_secs(30 * 60);
+const SLEEP_TIMER_MAX: Duration = Duration::from_secs(60 * 60);
+/// pressing the key again while the remaining time is shown extends the timer
+const SLEEP_TIMER_REPRESS_WINDOW: Duration = Duration::from_secs(2);
+/// the leds fade out during the last seconds before going to sleep
+const SLEEP_TIMER_FADE: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+pub struct SleepTimer {
+    deadline: Option<Instant>,
+    last_press: Option<Instant>,
+}
+
+impl SleepTimer {
+    /// first press arms the timer, the following ones only show the remaining time,
+    /// unless they come right after another press: then the timer is extended
+    /// by another step, going past the maximum disarms it
+    pub fn press(&mut self) {
+        let now = Instant::now();
+        let repressed = self
+            .last_press
+            .is_some_and(|last| now - last < SLEEP_TIMER_REPRESS_WINDOW);
+        self.last_press = Some(now);
+
+        match self.deadline {
+            None => self.deadline = Some(now + SLEEP_TIMER_STEP),
+            Some(deadline) if repressed => {
+                let deadline = deadline + SLEEP_TIMER_STEP;
+                if deadline - now > SLEEP_TIMER_MAX {
+                    log::info!("Sleep timer disarmed");
+                    self.deadline = None;
+                } else {
+                    self.deadline = Some(deadline);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// number of leds to light up to show the remaining time, 9 = one hour
+    pub fn remaining_leds(&self) -> Option<u32> {
+        self.remaining().map(|r| {
+            let led_time = SLEEP_TIMER_MAX.as_millis() / 9;
+            (r.as_millis().div_ceil(led_time) as u32).min(9)
+        })
+    }
+
+    /// gain multiplier to fade out the leds before the timer expires
+    pub fn fade(&self) -> f32 {
+        match self.remaining() {
+            Some(r) if r < SLEEP_TIMER_FADE => {
+                r.as_millis() as f32 / SLEEP_TIMER_FADE.as_millis() as f32
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// returns true once, when the timer runs out
+    pub fn expired(&mut self) -> bool {
+        if self.remaining() == Some(Duration::from_ticks(0)) {
+            self.deadline = None;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// restarting the badges, or dropping them into the usb bootloader (BOOTSEL), without
+// touching the button, for the stations flashing many badges at events
+//
+// over usb and serial the message carries MAGIC, over ir the bytes of MAGIC are sent on
+// REBOOT_IR_ADDRESS one packet at a time from the first, then the target, each packet
+// within PACKET_GAP of the one before
+
+use crate::time::Time;
+
+pub const REBOOT_IR_ADDRESS: u8 = 0x56;
+
+pub const MAGIC: u32 = 0x4D425254; // "MBRT"
+
+// seconds between the packets of the ir sequence
+const PACKET_GAP: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Firmware,
+    Bootloader,
+}
+
+impl Target {
+    /// the last packet of the ir sequence
+    fn from_ir(cmd: u8) -> Option<Self> {
+        match cmd {
+            0 => Some(Target::Firmware),
+            1 => Some(Target::Bootloader),
+            _ => None,
+        }
+    }
+}
+
+/// follows the ir sequence, a wrong or late packet starts it over
+#[derive(Default)]
+pub struct IrSequence {
+    // bytes of MAGIC received so far, and when the last one arrived
+    matched: usize,
+    last: Time,
+}
+
+impl IrSequence {
+    /// a packet on REBOOT_IR_ADDRESS, the target at the end of the sequence
+    pub fn received(&mut self, cmd: u8, t: Time) -> Option<Target> {
+        let magic = MAGIC.to_be_bytes();
+
+        if t.since(self.last) > PACKET_GAP {
+            self.matched = 0;
+        }
+        self.last = t;
+
+        if self.matched == magic.len() {
+            self.matched = 0;
+            let target = Target::from_ir(cmd);
+            if target.is_none() {
+                log::warn!("Unknown reboot target {}", cmd);
+            }
+            return target;
+        }
+
+        self.matched = if cmd == magic[self.matched] {
+            self.matched + 1
+        } else {
+            (cmd == magic[0]) as usize
+        };
+        None
+    }
+}
+
+pub fn reboot(target: Target) -> ! {
+    log::warn!("Rebooting to {:?}", target);
+
+    if target == Target::Bootloader {
+        // doesn't return, the leds and the pins stay as they are
+        #[cfg(feature = "rp2040")]
+        embassy_rp::rom_data::reset_to_usb_boot(0, 0);
+        // REBOOT_TYPE_BOOTSEL, the watchdog restarts the chip 10 ms later
+        #[cfg(feature = "rp2350")]
+        {
+            embassy_rp::rom_data::reboot(0x0002, 10, 0, 0);
+            loop {
+                cortex_m::asm::wfi();
+            }
+        }
+    }
+    cortex_m::peripheral::SCB::sys_reset()
+}
@@ -0,0 +1,166 @@
+use embassy_rp::flash::ERASE_SIZE;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BadgeFlash, RECORDINGS_OFFSET, RECORDING_SECTORS};
+use crate::framebuffer::RawFramebuffer;
+use crate::rgbeffects::{color_frame, ColorFrame, LoopMode, Pattern, RenderCommand};
+use crate::scenes::{Scene, Scenes};
+use crate::time::Time;
+
+// records the frames shown by the badge, to keep a moment of a random scene: the
+// recordings are saved in a ring of sectors, one each, the newest one takes the place of
+// the oldest, and they are played in a loop as the last scenes from the next boot
+//
+// a sector holds the magic, a sequence number and the postcard data length, then the
+// recording serialized with postcard
+
+const RECORDING_MAGIC: u32 = 0x4D425243; // "MBRC"
+const HEADER_SIZE: usize = 12;
+
+/// frames per second of the recordings, the effects at 100 are subsampled
+pub const RECORD_FPS: f32 = 10.0;
+/// ten seconds, 27 bytes a frame
+pub const MAX_FRAMES: usize = 100;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub frames: Vec<ColorFrame, MAX_FRAMES>,
+}
+
+pub type Recordings = Vec<Recording, RECORDING_SECTORS>;
+
+/// the recording to save, from the main loop to the config task that has the flash
+pub static FINISHED: Signal<CriticalSectionRawMutex, Recording> = Signal::new();
+
+#[derive(Default)]
+pub struct Recorder {
+    recording: Recording,
+    // the last frame recorded, in steps of RECORD_FPS
+    last_step: Option<u32>,
+}
+
+impl Recorder {
+    /// adds the frame when it's time for the next one, true when it filled the recording
+    pub fn record(&mut self, framebuffer: &RawFramebuffer, t: Time) -> bool {
+        let step = t.steps(RECORD_FPS);
+        if self.last_step == Some(step) || self.recording.frames.is_full() {
+            return false;
+        }
+        self.last_step = Some(step);
+
+        let _ = self.recording.frames.push(color_frame(framebuffer));
+        self.recording.frames.is_full()
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+fn sector_offset(sector: usize) -> u32 {
+    RECORDINGS_OFFSET + (sector * ERASE_SIZE) as u32
+}
+
+/// the sequence number of the recording in every sector, None for the empty ones
+fn sequence_numbers(flash: &mut BadgeFlash) -> [Option<u32>; RECORDING_SECTORS] {
+    core::array::from_fn(|sector| {
+        let mut header = [0; HEADER_SIZE];
+        flash
+            .blocking_read(sector_offset(sector), &mut header)
+            .ok()?;
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let seq = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        (magic == RECORDING_MAGIC).then_some(seq)
+    })
+}
+
+/// reads the recordings from flash, the oldest first
+pub fn load(flash: &mut BadgeFlash) -> Recordings {
+    let mut sectors: Vec<(u32, usize), RECORDING_SECTORS> = sequence_numbers(flash)
+        .iter()
+        .enumerate()
+        .filter_map(|(sector, seq)| Some(((*seq)?, sector)))
+        .collect();
+    sectors.sort_unstable();
+
+    let mut recordings = Recordings::new();
+    let mut data = [0; ERASE_SIZE];
+    for (_, sector) in sectors {
+        if let Err(e) = flash.blocking_read(sector_offset(sector), &mut data) {
+            log::error!("Error reading recording: {:?}", e);
+            continue;
+        }
+
+        let len = u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize;
+        if len > ERASE_SIZE - HEADER_SIZE {
+            continue;
+        }
+
+        match postcard::from_bytes(&data[HEADER_SIZE..HEADER_SIZE + len]) {
+            Ok(recording) => {
+                let _ = recordings.push(recording);
+            }
+            Err(e) => log::error!("Error decoding recording: {:?}", e),
+        }
+    }
+    recordings
+}
+
+/// writes the recording in the sector after the newest one
+pub fn store(flash: &mut BadgeFlash, recording: &Recording) {
+    let mut data = [0xFF; ERASE_SIZE];
+
+    let len = match postcard::to_slice(recording, &mut data[HEADER_SIZE..]) {
+        Ok(encoded) => encoded.len(),
+        Err(e) => {
+            log::error!("Error encoding recording: {:?}", e);
+            return;
+        }
+    };
+
+    let newest = sequence_numbers(flash)
+        .iter()
+        .enumerate()
+        .filter_map(|(sector, seq)| Some(((*seq)?, sector)))
+        .max();
+    let (seq, sector) = match newest {
+        Some((seq, sector)) => (seq.wrapping_add(1), (sector + 1) % RECORDING_SECTORS),
+        None => (0, 0),
+    };
+
+    data[0..4].copy_from_slice(&RECORDING_MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&seq.to_le_bytes());
+    data[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+
+    let offset = sector_offset(sector);
+    if let Err(e) = flash.blocking_erase(offset, offset + ERASE_SIZE as u32) {
+        log::error!("Error erasing recording: {:?}", e);
+        return;
+    }
+
+    if let Err(e) = flash.blocking_write(offset, &data[..HEADER_SIZE + len]) {
+        log::error!("Error writing recording: {:?}", e);
+    }
+}
+
+/// appends the recordings to the scenes, played in a loop
+pub fn append(recordings: &'static Recordings, scenes: &mut Scenes) {
+    for recording in recordings.iter().filter(|r| !r.frames.is_empty()) {
+        let scene: Scene = core::iter::once(RenderCommand {
+            effect: Pattern::AnimationColor(&recording.frames, RECORD_FPS, LoopMode::Forward),
+            ..Default::default()
+        })
+        .collect();
+
+        if scenes.push(scene).is_err() {
+            log::warn!("Too many scenes, ignoring the rest of the recordings");
+            break;
+        }
+    }
+
+    log::info!("Loaded {} recordings", recordings.len());
+}
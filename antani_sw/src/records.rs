@@ -0,0 +1,549 @@
+// the config and the records of the store it's saved in, without the flash, so the tests
+// run on a pc with `cargo test` in minibadge-sim, see config.rs for the store
+//
+// a record is the magic, a sequence number, the crc-32 of the data, the length of the
+// data, the version of the Config, then the Config serialized with postcard
+
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use crate::calibration::Calibration;
+use crate::clock::QuietHours;
+use crate::framebuffer::MAX_CHAINED_BADGES;
+use crate::input::{IrFilter, IrProtocol, MAX_IR_REMOTES};
+use crate::scenes::Playback;
+use crate::strip::Strip;
+use crate::BRIGHTNESS_LEVELS;
+
+/// a record in every page
+pub const PAGE_SIZE: usize = 256;
+pub const RECORD_HEADER_SIZE: usize = 16;
+const RECORD_MAGIC: u32 = 0x4D425354; // "MBST"
+
+// the config of the firmwares before the store, in fixed places of a single sector
+const LEGACY_CONFIG_MAGIC: u32 = 0x4D424347; // "MBCG"
+const LEGACY_CONFIG_VERSION: u8 = 2;
+pub const LEGACY_CONFIG_SIZE: usize = 20;
+
+/// the version of the records written now, bump it when the Config changes and convert
+/// the older records in Config::decode
+pub const CONFIG_VERSION: u8 = 8;
+
+pub const DEFAULT_FPS: u8 = 100;
+// below 10 the animations stutter, above 200 the rendering can't keep up
+pub const MIN_FPS: u8 = 10;
+pub const MAX_FPS: u8 = 200;
+
+/// ceiling of the brightness in percent, after every other gain, lower it for badges
+/// in cases without ventilation: the setting saved in flash can only go below it
+pub const MAX_BRIGHTNESS: u8 = 100;
+
+/// bytes of the name of the owner
+pub const MAX_NAME_LEN: usize = 16;
+
+/// letters of the initials of the owner
+pub const MAX_INITIALS: usize = 3;
+
+/// settings that survive a power cycle
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// start from a random scene instead of the first one
+    pub random_boot_scene: bool,
+    /// how the button (and the timer) moves between scenes
+    pub playback: Playback,
+    /// attract mode for display stands
+    pub demo_mode: bool,
+    /// target frame rate of the rendering
+    pub fps: u8,
+    /// ceiling of the brightness in percent, at most MAX_BRIGHTNESS
+    pub max_brightness: u8,
+    /// the remotes the badge listens to
+    pub ir_filter: IrFilter,
+    /// the brightness level and the scene shown when the badge was turned off
+    pub brightness: u8,
+    pub scene: u8,
+    /// per led corrections measured during the factory test
+    pub calibration: Calibration,
+    /// the owner of the badge
+    pub name: String<MAX_NAME_LEN>,
+    /// the external strip, if any
+    pub strip: Strip,
+    /// badges of the daisy chain driven by this one, this one included
+    pub chained_badges: u8,
+    /// a busy sign instead of the scenes, until a double press
+    pub do_not_disturb: bool,
+    /// shown in place of the scene every few seconds, none = the scenes only
+    pub initials: String<MAX_INITIALS>,
+    /// a lower ceiling of the brightness at night, once the time is set
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// the records of the version before the daisy chain, postcard writes the fields of a
+/// struct one after the other, so the older versions are the fields before the new ones
+#[derive(Deserialize)]
+struct ConfigV4 {
+    v3: ConfigV3,
+    strip: Strip,
+}
+
+impl From<ConfigV4> for Config {
+    fn from(config: ConfigV4) -> Self {
+        Self {
+            strip: config.strip,
+            ..config.v3.into()
+        }
+    }
+}
+
+/// the records of the version before the quiet hours
+#[derive(Deserialize)]
+struct ConfigV7 {
+    v6: ConfigV6,
+    initials: String<MAX_INITIALS>,
+}
+
+impl From<ConfigV7> for Config {
+    fn from(config: ConfigV7) -> Self {
+        Self {
+            initials: config.initials,
+            ..config.v6.into()
+        }
+    }
+}
+
+/// the records of the version before the initials
+#[derive(Deserialize)]
+struct ConfigV6 {
+    v5: ConfigV5,
+    do_not_disturb: bool,
+}
+
+impl From<ConfigV6> for Config {
+    fn from(config: ConfigV6) -> Self {
+        Self {
+            do_not_disturb: config.do_not_disturb,
+            ..config.v5.into()
+        }
+    }
+}
+
+/// the records of the version before do not disturb
+#[derive(Deserialize)]
+struct ConfigV5 {
+    v4: ConfigV4,
+    chained_badges: u8,
+}
+
+impl From<ConfigV5> for Config {
+    fn from(config: ConfigV5) -> Self {
+        Self {
+            chained_badges: config.chained_badges,
+            ..config.v4.into()
+        }
+    }
+}
+
+/// the records of the version before the strip
+#[derive(Deserialize)]
+struct ConfigV3 {
+    random_boot_scene: bool,
+    playback: Playback,
+    demo_mode: bool,
+    fps: u8,
+    max_brightness: u8,
+    ir_filter: IrFilter,
+    brightness: u8,
+    scene: u8,
+    calibration: Calibration,
+    name: String<MAX_NAME_LEN>,
+}
+
+impl From<ConfigV3> for Config {
+    fn from(config: ConfigV3) -> Self {
+        Self {
+            random_boot_scene: config.random_boot_scene,
+            playback: config.playback,
+            demo_mode: config.demo_mode,
+            fps: config.fps,
+            max_brightness: config.max_brightness,
+            ir_filter: config.ir_filter,
+            brightness: config.brightness,
+            scene: config.scene,
+            calibration: config.calibration,
+            name: config.name,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            random_boot_scene: false,
+            playback: Playback::default(),
+            demo_mode: false,
+            fps: DEFAULT_FPS,
+            max_brightness: MAX_BRIGHTNESS,
+            ir_filter: IrFilter::default(),
+            brightness: BRIGHTNESS_LEVELS - 1,
+            scene: 0,
+            calibration: Calibration::default(),
+            name: String::new(),
+            strip: Strip::default(),
+            chained_badges: 1,
+            do_not_disturb: false,
+            initials: String::new(),
+            quiet_hours: None,
+        }
+    }
+}
+
+/// a frame rate the badge can keep
+pub fn clamp_fps(fps: u8) -> u8 {
+    fps.clamp(MIN_FPS, MAX_FPS)
+}
+
+/// a brightness ceiling that doesn't go above the one of the firmware, nor turns the leds off
+pub fn clamp_max_brightness(percent: u8) -> u8 {
+    percent.clamp(1, MAX_BRIGHTNESS)
+}
+
+/// a chain of badges that has this one and fits in the frames
+pub fn clamp_chained_badges(badges: u8) -> u8 {
+    badges.clamp(1, MAX_CHAINED_BADGES as u8)
+}
+
+/// crc-32 (ieee) bit by bit, a record is small
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub struct RecordHeader {
+    pub seq: u32,
+    pub crc: u32,
+    pub len: usize,
+    pub version: u8,
+}
+
+impl RecordHeader {
+    pub fn parse(page: &[u8]) -> Option<Self> {
+        let word = |i: usize| u32::from_le_bytes([page[i], page[i + 1], page[i + 2], page[i + 3]]);
+
+        let header = Self {
+            seq: word(4),
+            crc: word(8),
+            len: u16::from_le_bytes([page[12], page[13]]) as usize,
+            version: page[14],
+        };
+        (word(0) == RECORD_MAGIC && header.len <= PAGE_SIZE - RECORD_HEADER_SIZE).then_some(header)
+    }
+
+    pub fn write(&self, page: &mut [u8]) {
+        page[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        page[4..8].copy_from_slice(&self.seq.to_le_bytes());
+        page[8..12].copy_from_slice(&self.crc.to_le_bytes());
+        page[12..14].copy_from_slice(&(self.len as u16).to_le_bytes());
+        page[14] = self.version;
+    }
+}
+
+/// the pages of the store, in sectors erased all at once
+#[derive(Clone, Copy)]
+pub struct Ring {
+    pub sectors: usize,
+    pub slots_per_sector: usize,
+}
+
+impl Ring {
+    pub const fn slots(&self) -> usize {
+        self.sectors * self.slots_per_sector
+    }
+
+    /// the first page of a sector, erased before it's written
+    pub fn starts_sector(&self, slot: usize) -> bool {
+        slot.is_multiple_of(self.slots_per_sector)
+    }
+
+    /// the sequence number and the slot of the record after the newest one, blank tells
+    /// if a page is still erased
+    pub fn next(
+        &self,
+        newest: Option<(u32, usize)>,
+        blank: impl FnOnce(usize) -> bool,
+    ) -> (u32, usize) {
+        let (seq, slot) = match newest {
+            Some((seq, slot)) => (seq.wrapping_add(1), (slot + 1) % self.slots()),
+            None => return (0, 0),
+        };
+
+        // a page that isn't blank was left by a write cut short, the sector it's in is
+        // skipped instead of erased, it may have the newest good record
+        if !self.starts_sector(slot) && !blank(slot) {
+            let sector = (slot / self.slots_per_sector + 1) % self.sectors;
+            return (seq, sector * self.slots_per_sector);
+        }
+        (seq, slot)
+    }
+}
+
+impl Config {
+    /// the records of every version the store ever had
+    pub fn decode(version: u8, data: &[u8]) -> Option<Self> {
+        match version {
+            CONFIG_VERSION => postcard::from_bytes(data).ok(),
+            7 => postcard::from_bytes::<ConfigV7>(data).ok().map(Self::from),
+            6 => postcard::from_bytes::<ConfigV6>(data).ok().map(Self::from),
+            5 => postcard::from_bytes::<ConfigV5>(data).ok().map(Self::from),
+            4 => postcard::from_bytes::<ConfigV4>(data).ok().map(Self::from),
+            3 => postcard::from_bytes::<ConfigV3>(data).ok().map(Self::from),
+            _ => None,
+        }
+    }
+
+    /// the config of the firmwares before the store, the calibration was in its own sector
+    pub fn from_legacy_bytes(data: &[u8; LEGACY_CONFIG_SIZE]) -> Option<Self> {
+        if data[0..4] != LEGACY_CONFIG_MAGIC.to_le_bytes() || data[4] != LEGACY_CONFIG_VERSION {
+            return None;
+        }
+
+        let playback = match data[6] {
+            1 => Playback::Shuffle {
+                interval: u16::from_le_bytes([data[7], data[8]]),
+            },
+            _ => Playback::Sequential,
+        };
+
+        let mut ir_filter = IrFilter::default();
+        for i in 0..MAX_IR_REMOTES {
+            let protocol = match data[12 + i * 2] {
+                0 => IrProtocol::Nec,
+                1 => IrProtocol::Samsung,
+                _ => continue,
+            };
+            ir_filter.remotes[i] = Some((protocol, data[13 + i * 2]));
+        }
+
+        Some(Self {
+            random_boot_scene: data[5] == 1,
+            playback,
+            demo_mode: data[9] == 1,
+            // the configs saved before the frame rate was added have 0xFF here
+            fps: match data[10] {
+                0xFF => DEFAULT_FPS,
+                fps => clamp_fps(fps),
+            },
+            max_brightness: match data[11] {
+                0xFF => MAX_BRIGHTNESS,
+                percent => clamp_max_brightness(percent),
+            },
+            ir_filter,
+            ..Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strip::Mapping;
+
+    // three sectors of four pages, small enough to go around
+    const RING: Ring = Ring {
+        sectors: 3,
+        slots_per_sector: 4,
+    };
+
+    fn encode(value: &impl Serialize) -> std::vec::Vec<u8> {
+        let mut buffer = [0; PAGE_SIZE];
+        postcard::to_slice(value, &mut buffer).unwrap().to_vec()
+    }
+
+    // the fields of a version 3 record, postcard writes a struct like a tuple of its fields
+    fn v3() -> impl Serialize {
+        (
+            true,
+            Playback::Shuffle { interval: 30 },
+            false,
+            50u8,
+            80u8,
+            IrFilter::default(),
+            3u8,
+            2u8,
+            Calibration::default(),
+            String::<MAX_NAME_LEN>::try_from("antani").unwrap(),
+        )
+    }
+
+    #[test]
+    fn crc_of_the_check_string() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn headers_read_back() {
+        let mut page = [0xFF; PAGE_SIZE];
+        RecordHeader {
+            seq: 7,
+            crc: 0xDEAD_BEEF,
+            len: 42,
+            version: CONFIG_VERSION,
+        }
+        .write(&mut page);
+
+        let header = RecordHeader::parse(&page).unwrap();
+        assert_eq!(
+            (header.seq, header.crc, header.len, header.version),
+            (7, 0xDEAD_BEEF, 42, CONFIG_VERSION)
+        );
+
+        // an erased page, and a length past the end of the page
+        assert!(RecordHeader::parse(&[0xFF; PAGE_SIZE]).is_none());
+        RecordHeader {
+            seq: 7,
+            crc: 0,
+            len: PAGE_SIZE - RECORD_HEADER_SIZE + 1,
+            version: CONFIG_VERSION,
+        }
+        .write(&mut page);
+        assert!(RecordHeader::parse(&page).is_none());
+    }
+
+    #[test]
+    fn records_go_around_the_ring() {
+        let blank = |_| true;
+
+        assert_eq!(RING.next(None, blank), (0, 0));
+        assert_eq!(RING.next(Some((0, 0)), blank), (1, 1));
+        assert_eq!(RING.next(Some((5, 3)), blank), (6, 4));
+        assert!(RING.starts_sector(4));
+        // from the last page back to the first, and the sequence numbers too
+        assert_eq!(RING.next(Some((11, 11)), blank), (12, 0));
+        assert_eq!(RING.next(Some((u32::MAX, 2)), blank), (0, 3));
+    }
+
+    #[test]
+    fn torn_pages_skip_their_sector() {
+        let torn = |_| false;
+
+        assert_eq!(RING.next(Some((1, 1)), torn), (2, 4));
+        assert_eq!(RING.next(Some((9, 9)), torn), (10, 0));
+        // the first page of a sector is erased anyway
+        assert_eq!(RING.next(Some((3, 3)), torn), (4, 4));
+    }
+
+    #[test]
+    fn records_read_back() {
+        let config = Config {
+            fps: 60,
+            name: String::try_from("antani").unwrap(),
+            quiet_hours: Some(QuietHours {
+                start: 23 * 60,
+                end: 7 * 60,
+                max_brightness: 20,
+            }),
+            ..Default::default()
+        };
+
+        let decoded = Config::decode(CONFIG_VERSION, &encode(&config)).unwrap();
+        assert_eq!(decoded.fps, 60);
+        assert_eq!(decoded.name, "antani");
+        assert_eq!(decoded.quiet_hours, config.quiet_hours);
+    }
+
+    #[test]
+    fn older_records_keep_their_settings() {
+        let strip = Strip {
+            length: 30,
+            mapping: Mapping::Gradient,
+        };
+        let initials = String::<MAX_INITIALS>::try_from("AB").unwrap();
+        let records = [
+            (3, encode(&v3())),
+            (4, encode(&(v3(), strip))),
+            (5, encode(&((v3(), strip), 4u8))),
+            (6, encode(&(((v3(), strip), 4u8), true))),
+            (7, encode(&((((v3(), strip), 4u8), true), initials.clone()))),
+        ];
+
+        for (version, data) in records {
+            let config = Config::decode(version, &data).unwrap();
+
+            assert!(config.random_boot_scene);
+            assert_eq!(config.playback, Playback::Shuffle { interval: 30 });
+            assert_eq!((config.fps, config.max_brightness), (50, 80));
+            assert_eq!((config.brightness, config.scene), (3, 2));
+            assert_eq!(config.name, "antani");
+
+            // the settings of the later versions are the defaults
+            let default = Config::default();
+            let at = |since: u8| version >= since;
+            assert_eq!(config.strip, if at(4) { strip } else { default.strip });
+            assert_eq!(config.chained_badges, if at(5) { 4 } else { 1 });
+            assert_eq!(config.do_not_disturb, at(6));
+            assert_eq!(
+                config.initials,
+                if at(7) {
+                    initials.clone()
+                } else {
+                    default.initials
+                }
+            );
+            assert_eq!(config.quiet_hours, None);
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_left_out() {
+        assert!(Config::decode(2, &encode(&v3())).is_none());
+        assert!(Config::decode(CONFIG_VERSION + 1, &encode(&Config::default())).is_none());
+    }
+
+    #[test]
+    fn legacy_configs_read_back() {
+        let mut data = [0xFF; LEGACY_CONFIG_SIZE];
+        data[0..4].copy_from_slice(&LEGACY_CONFIG_MAGIC.to_le_bytes());
+        data[4] = LEGACY_CONFIG_VERSION;
+        // a random boot scene, shuffle every 45 seconds, no demo mode
+        data[5] = 1;
+        data[6] = 1;
+        data[7..9].copy_from_slice(&45u16.to_le_bytes());
+        data[9] = 0;
+        // a samsung remote on address 7, the other places empty
+        data[12..14].copy_from_slice(&[1, 7]);
+
+        // the frame rate and the brightness saved before they were added
+        let config = Config::from_legacy_bytes(&data).unwrap();
+        assert!(config.random_boot_scene && !config.demo_mode);
+        assert_eq!(config.playback, Playback::Shuffle { interval: 45 });
+        assert_eq!(
+            (config.fps, config.max_brightness),
+            (DEFAULT_FPS, MAX_BRIGHTNESS)
+        );
+        assert_eq!(
+            config.ir_filter.remotes,
+            [Some((IrProtocol::Samsung, 7)), None, None, None]
+        );
+
+        data[6] = 0;
+        data[10] = 5;
+        data[11] = 0;
+        let config = Config::from_legacy_bytes(&data).unwrap();
+        assert_eq!(config.playback, Playback::Sequential);
+        assert_eq!((config.fps, config.max_brightness), (MIN_FPS, 1));
+
+        data[4] = LEGACY_CONFIG_VERSION + 1;
+        assert!(Config::from_legacy_bytes(&data).is_none());
+    }
+}
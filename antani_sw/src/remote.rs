@@ -0,0 +1,117 @@
+// the keys of the samsung tv remote, so the same key does the same thing wherever
+// it's handled
+
+use usbd_hid::descriptor::KeyboardUsage;
+
+use crate::time::Time;
+
+pub const SAMSUNG_IR_ADDRESS: u8 = 7;
+
+// packets of the same key closer than this are the key held down, the remotes send
+// one about every 110 ms
+const HOLD_GAP: f32 = 0.25;
+// a held key acts once, then again after REPEAT_DELAY every REPEAT_PERIOD
+const REPEAT_DELAY: f32 = 0.5;
+const REPEAT_PERIOD: f32 = 0.15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Power,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Exit,
+    Digit(u8),
+}
+
+// samsung command of every key
+const SAMSUNG_KEYS: [(u8, Key); 19] = [
+    (2, Key::Power),
+    (7, Key::VolumeUp),
+    (11, Key::VolumeDown),
+    (15, Key::Mute),
+    (96, Key::Up),
+    (97, Key::Down),
+    (101, Key::Left),
+    (98, Key::Right),
+    (104, Key::Enter),
+    (102, Key::Exit),
+    (4, Key::Digit(1)),
+    (5, Key::Digit(2)),
+    (6, Key::Digit(3)),
+    (8, Key::Digit(4)),
+    (9, Key::Digit(5)),
+    (10, Key::Digit(6)),
+    (12, Key::Digit(7)),
+    (13, Key::Digit(8)),
+    (14, Key::Digit(9)),
+];
+
+/// the key of a packet of the samsung remote
+pub fn samsung_key(cmd: u8) -> Option<Key> {
+    SAMSUNG_KEYS
+        .iter()
+        .find(|(key_cmd, _)| *key_cmd == cmd)
+        .map(|(_, key)| *key)
+}
+
+impl Key {
+    /// the key sent to the pc when the badge is used as a remote for it
+    pub fn keyboard_usage(self) -> Option<KeyboardUsage> {
+        let usage = match self {
+            Key::Mute => KeyboardUsage::KeyboardMute,
+            Key::Up => KeyboardUsage::KeyboardUpArrow,
+            Key::Down => KeyboardUsage::KeyboardDownArrow,
+            Key::Left => KeyboardUsage::KeyboardLeftArrow,
+            Key::Right => KeyboardUsage::KeyboardRightArrow,
+            Key::Enter => KeyboardUsage::KeyboardEnter,
+            Key::Exit => KeyboardUsage::KeyboardEscape,
+            // the volume sets the brightness, the digits choose the scenes and the power
+            // key blanks the badge
+            Key::Power | Key::VolumeUp | Key::VolumeDown | Key::Digit(_) => return None,
+        };
+        Some(usage)
+    }
+}
+
+/// tells a key held down from a key pressed again, whether the remote sends repeat
+/// packets (nec) or the same packet again (samsung)
+#[derive(Default)]
+pub struct KeyRepeat {
+    // the key held down, when it was pressed, its last packet and when it last acted
+    held: Option<(Key, Time, Time, Time)>,
+}
+
+impl KeyRepeat {
+    /// a packet of key arrived at t, true when the key acts on it
+    pub fn packet(&mut self, key: Key, t: Time) -> bool {
+        match &mut self.held {
+            Some((held, pressed, last, acted)) if *held == key && t.since(*last) < HOLD_GAP => {
+                *last = t;
+
+                let repeat = t.since(*pressed) >= REPEAT_DELAY && t.since(*acted) >= REPEAT_PERIOD;
+                if repeat {
+                    *acted = t;
+                }
+                repeat
+            }
+            _ => {
+                self.held = Some((key, t, t, t));
+                true
+            }
+        }
+    }
+
+    /// a packet of key arrived at t, true only when the key was just pressed, for the
+    /// keys that act once however long they are held
+    pub fn press(&mut self, key: Key, t: Time) -> bool {
+        let held = matches!(self.held, Some((held, _, last, _)) if held == key && t.since(last) < HOLD_GAP);
+        self.packet(key, t);
+        !held
+    }
+}
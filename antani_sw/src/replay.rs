@@ -0,0 +1,57 @@
+// the timing of the replays of the recorded input events, see input.rs, without any
+// hardware behind it so the tests run on a pc with `cargo test` in minibadge-sim
+
+use embassy_time::Instant;
+
+/// when every event is sent again in a replay starting at start: in the order they were
+/// recorded, as far from the first event as they were, at is the time an event was recorded
+pub fn schedule<T>(
+    events: &[T],
+    at: impl Fn(&T) -> Instant,
+    start: Instant,
+) -> impl Iterator<Item = (Instant, &T)> {
+    let first = events.first().map(&at);
+
+    events.iter().map(move |event| match first {
+        // an event can't be sent before the replay starts
+        Some(first) => (start + at(event).saturating_duration_since(first), event),
+        None => (start, event),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(events: &[(u64, char)], start: u64) -> std::vec::Vec<(u64, char)> {
+        let at = |event: &(u64, char)| Instant::from_millis(event.0);
+        schedule(events, at, Instant::from_millis(start))
+            .map(|(at, event)| (at.as_millis(), event.1))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_order_and_the_gaps() {
+        let events = [(1000, 'a'), (1250, 'b'), (1250, 'c'), (3000, 'd')];
+
+        assert_eq!(
+            replay(&events, 10_000),
+            [(10_000, 'a'), (10_250, 'b'), (10_250, 'c'), (12_000, 'd')]
+        );
+    }
+
+    #[test]
+    fn starts_right_away() {
+        assert_eq!(replay(&[(500, 'a')], 0), [(0, 'a')]);
+        assert_eq!(replay(&[], 0), []);
+    }
+
+    #[test]
+    fn never_before_the_start() {
+        // recorded in this order, the second one with an earlier time
+        assert_eq!(
+            replay(&[(1000, 'a'), (900, 'b')], 5000),
+            [(5000, 'a'), (5000, 'b')]
+        );
+    }
+}
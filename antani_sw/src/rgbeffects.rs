@@ -1,131 +1,267 @@
-use core::f64;
 use heapless::Vec;
+#[cfg(not(feature = "std"))]
 use num_traits::real::Real;
 use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
 
-use crate::{LedMatrix, LedPixel, RawFramebuffer};
+use crate::color::{dim, hue_wheel, lerp, ramp, smooth, tint, white_point};
+use crate::lut::sin;
+use crate::time::Time;
+use crate::{ChainFramebuffer, LedPixel, RawFramebuffer};
 
 pub type LedPattern = u16;
+/// a frame of an animation with its own duration, pattern and milliseconds
+pub type TimedFrame = (LedPattern, u16);
+/// a frame of pixel art, rows from the top, black leds are left to the layers below
+pub type ColorFrame = [[(u8, u8, u8); 3]; 3];
+/// full color frames, authored offline, the palette of the layer is not used
+pub type ColorAnimation = &'static [ColorFrame];
+
+pub const MAX_SHADERS: usize = 8;
+pub const MAX_PALETTE_COLORS: usize = 16;
+pub const PALETTE_TABLE_SIZE: usize = 16;
+pub const MAX_WALKERS: usize = 4;
+/// how long a glitch lasts, and how often one can start
+const GLITCH_TIME: f32 = 0.08;
+/// how long a flash of the strobe lasts, at most half of the period
+const STROBE_FLASH_TIME: f32 = 0.03;
+/// how far a ring of a ripple goes, past the farthest corner of the pattern
+const RIPPLE_TRAVEL: f32 = 4.0;
 
 #[derive(Clone, Default, Debug)]
 pub struct RenderCommand {
     pub effect: Pattern,
     pub color: ColorPalette,
-    pub pattern_shaders: Vec<FragmentShader, 8>,
-    pub screen_shaders: Vec<FragmentShader, 8>,
-    pub time_offset: f64,
+    pub pattern_shaders: Vec<FragmentShader, MAX_SHADERS>,
+    pub screen_shaders: Vec<FragmentShader, MAX_SHADERS>,
+    pub time_offset: f32,
 }
 
 #[derive(Clone, Default)]
 pub struct ShaderPersistentData {
     pub frame_counter: u32,
-    pub lowpass: RawFramebuffer,
+    pub lowpass: ChainFramebuffer,
+    pub beats: u32,
+    pub last_beat: Option<Time>,
+    pub last_press: Option<Time>,
+    pub last_ir: Option<Time>,
+    pub glitch_slot: u32,
+    pub glitch: Glitch,
+    /// the strobe flashes start over here, on a beat, an ir beacon or a sync pulse
+    pub strobe_sync: Option<Time>,
+    /// the heartbeat advances its own phase, its rate changes with the temperature
+    pub heartbeat_phase: f32,
+    pub heartbeat_at: Option<Time>,
+    /// the leds of the random walk, as bits of the pattern, and when every led was last
+    /// visited, for the trails
+    pub walkers: Vec<usize, MAX_WALKERS>,
+    pub walk_step: u32,
+    pub visited: [Option<Time>; 9],
 }
 
-pub struct RenderManager {
-    pub mtrx: LedMatrix,
+impl ShaderPersistentData {
+    /// the input events of other, for the effects of two scenes rendered at once that keep
+    /// the rest of the state each
+    pub fn copy_inputs(&mut self, other: &Self) {
+        self.beats = other.beats;
+        self.last_beat = other.last_beat;
+        self.last_press = other.last_press;
+        self.last_ir = other.last_ir;
+        self.strobe_sync = other.strobe_sync;
+    }
+}
+
+/// what the glitch shader does to the frames of the current GLITCH_TIME
+#[derive(Clone, Copy, Default)]
+pub enum Glitch {
+    #[default]
+    None,
+    ShiftRow(usize, usize), // row, pixels to the right
+    Invert(u32),            // one bit per pixel, repeated on bigger targets
+    Drop,                   // black frames
+}
+
+/// what the effects draw on, the leds of the badge or anything else with pixels in a grid
+pub trait RenderTarget {
+    /// width and height in pixels
+    fn size(&self) -> (usize, usize);
+    fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel);
+    fn get_pixel(&self, x: usize, y: usize) -> LedPixel;
+    fn clear(&mut self);
+}
+
+/// the colors of ColorPalette::Indexed, uploaded by the user
+pub type PaletteTable = Vec<LedPixel, PALETTE_TABLE_SIZE>;
+
+pub struct RenderManager<M: RenderTarget> {
+    pub mtrx: M,
     pub rng: SmallRng,
     pub persistent_data: ShaderPersistentData,
+    pub palette_table: PaletteTable,
 }
 
-impl RenderManager {
-    fn render_single(&mut self, command: &RenderCommand, t: f64) {
+impl<M: RenderTarget> RenderManager<M> {
+    fn render_single(&mut self, command: &RenderCommand, t: Time) {
         let t = t + command.time_offset;
-        let startcolor = command.color.render(t);
+        let startcolor = command.color.render(t, &self.palette_table);
 
         let pattern = command.effect.render(t, self);
 
-        // this maps bits in the pattern bitfield to the corresponding led in the matrix
-        let bit_offsets = [
-            (0, 2), // bit 0, first led
-            (0, 1),
-            (0, 0),
-            (1, 2),
-            (1, 1),
-            (1, 0),
-            (2, 2),
-            (2, 1),
-            (2, 0), // bit 8, the last led
-        ];
+        // this maps bits in the pattern bitfield to the corresponding led in the matrix,
+        // bit 0 is (0, 2) and bit 8 is (2, 0), targets bigger than 3x3 repeat the pattern
+        let (width, height) = self.mtrx.size();
 
-        for (i, (x, y)) in bit_offsets.iter().enumerate() {
-            // if a pixel is outside of the pattern, I still expect screen-space shaders to be applied to it
-            if pattern & (1 << i) != 0 {
-                let mut color = startcolor;
+        for x in 0..width {
+            for y in (0..height).rev() {
+                let bit = (x % 3) * 3 + 2 - y % 3;
 
-                for shader in command.pattern_shaders.iter() {
-                    color = shader.render(t, color, *x, *y, self);
+                let own_color = command.effect.pixel(t, x, y, (width, height));
+
+                // if a pixel is outside of the pattern, I still expect screen-space shaders to be applied to it
+                if own_color.is_some() || pattern & (1 << bit) != 0 {
+                    let mut color = own_color.unwrap_or(startcolor);
+
+                    let level = command.effect.level(t, bit, &self.persistent_data);
+                    if level < 1.0 {
+                        color = dim(color, level);
+                    }
+
+                    for shader in command.pattern_shaders.iter() {
+                        color = shader.render(t, color, x, y, &command.color, self);
+                    }
+
+                    self.mtrx.set_pixel(x, y, color);
                 }
 
-                self.mtrx.set_pixel(*x, *y, color);
+                for shader in command.screen_shaders.iter() {
+                    let mut color = self.mtrx.get_pixel(x, y);
+                    color = shader.render(t, color, x, y, &command.color, self);
+                    self.mtrx.set_pixel(x, y, color);
+                }
             }
+        }
 
-            for shader in command.screen_shaders.iter() {
-                let mut color = self.mtrx.get_pixel(*x, *y);
-                color = shader.render(t, color, *x, *y, self);
-                self.mtrx.set_pixel(*x, *y, color);
+        // post-processing, for the shaders that need the neighbours of a pixel,
+        // on the whole target once the layer is drawn
+        for shader in command
+            .pattern_shaders
+            .iter()
+            .chain(command.screen_shaders.iter())
+        {
+            match shader {
+                FragmentShader::Blur(amount) => blur(&mut self.mtrx, *amount),
+                FragmentShader::Glitch(rate) => self.glitch(*rate, t),
+                _ => {}
             }
         }
     }
 
-    pub fn render(&mut self, command: &[RenderCommand], t: f64) {
-        for c in command.iter() {
-            self.render_single(c, t);
+    /// every GLITCH_TIME a glitch can start, rate of them per second on average
+    fn glitch(&mut self, rate: f32, t: Time) {
+        let slot = t.steps(1.0 / GLITCH_TIME);
+        let data = &mut self.persistent_data;
+
+        if slot != data.glitch_slot {
+            data.glitch_slot = slot;
+            data.glitch = if self.rng.gen::<f32>() < rate * GLITCH_TIME {
+                let (width, height) = self.mtrx.size();
+
+                match self.rng.gen_range(0..3) {
+                    0 => Glitch::ShiftRow(
+                        self.rng.gen_range(0..height),
+                        self.rng.gen_range(1..width.max(2)),
+                    ),
+                    1 => Glitch::Invert(self.rng.gen()),
+                    _ => Glitch::Drop,
+                }
+            } else {
+                Glitch::None
+            };
         }
-    }
-}
 
-fn hsl2rgb(h: f64, s: f64, l: f64) -> LedPixel {
-    let h = h * 360.0;
-    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = l - c / 2.0;
+        let (width, height) = self.mtrx.size();
+
+        match data.glitch {
+            Glitch::None => {}
+            Glitch::ShiftRow(row, pixels) => {
+                for _ in 0..pixels {
+                    let mut carried = self.mtrx.get_pixel(width - 1, row);
+                    for x in 0..width {
+                        let pixel = self.mtrx.get_pixel(x, row);
+                        self.mtrx.set_pixel(x, row, carried);
+                        carried = pixel;
+                    }
+                }
+            }
+            Glitch::Invert(mask) => {
+                for y in 0..height {
+                    for x in 0..width {
+                        if mask & (1 << ((y * width + x) % 32)) != 0 {
+                            let c = self.mtrx.get_pixel(x, y);
+                            self.mtrx
+                                .set_pixel(x, y, (255 - c.r, 255 - c.g, 255 - c.b).into());
+                        }
+                    }
+                }
+            }
+            Glitch::Drop => self.mtrx.clear(),
+        }
+    }
 
-    let (r, g, b) = match h {
-        0.0..=60.0 => (c, x, 0.0),
-        60.0..=120.0 => (x, c, 0.0),
-        120.0..=180.0 => (0.0, c, x),
-        180.0..=240.0 => (0.0, x, c),
-        240.0..=300.0 => (x, 0.0, c),
-        300.0..=360.0 => (c, 0.0, x),
-        _ => (0.0, 0.0, 0.0), // This should not happen in a properly constrained input.
-    };
+    /// an input event for the patterns and shaders waiting for it
+    pub fn trigger(&mut self, trigger: Trigger, t: Time) {
+        let data = &mut self.persistent_data;
 
-    let r = ((r + m) * 255.0).round() as u8;
-    let g = ((g + m) * 255.0).round() as u8;
-    let b = ((b + m) * 255.0).round() as u8;
+        match trigger {
+            Trigger::Loop => {}
+            Trigger::Beat => {
+                data.beats += 1;
+                data.last_beat = Some(t);
+            }
+            Trigger::Button => data.last_press = Some(t),
+            Trigger::Ir => data.last_ir = Some(t),
+        }
+    }
 
-    (r, g, b).into()
+    pub fn render(&mut self, command: &[RenderCommand], t: Time) {
+        for c in command.iter() {
+            self.render_single(c, t);
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum FragmentShader {
     Breathing(f32),       // speed
     Blinking(f32),        // speed
     LowPass(f32),         // tau
     LowPassWithPeak(f32), // tau
     Rainbow2D(f32),       // speed
+    AudioLevel(f32),      // hue change with the level, 0 = only brightness
+    BeatPulse(f32),       // decay per second
+    WhitePoint(f32),      // kelvin, 6500 = unchanged, lower is warmer
+    Blur(f32),            // amount, 0 = none, 1 = 3x3 gaussian kernel
+    Glitch(f32),          // glitches per second
+    Strobe(f32),          // flashes per second, from the last sync
+    Heartbeat(f32),       // beats per second when cool, three times faster when throttling
+    PaletteCycle(f32),    // palette colors per second, one color further for every pixel
+    Flash(Trigger, f32),  // to white on the trigger, then fading by decay per second
 }
 
 impl FragmentShader {
-    fn render(
+    fn render<M: RenderTarget>(
         &self,
-        t: f64,
+        t: Time,
         color: LedPixel,
         x: usize,
         y: usize,
-        renderman: &mut RenderManager,
+        palette: &ColorPalette,
+        renderman: &mut RenderManager<M>,
     ) -> LedPixel {
         match self {
-            FragmentShader::Breathing(speed) => {
-                let t = t * *speed as f64;
-                let l = 0.5 + 0.5 * (2.0 * f64::consts::PI * t).sin();
-                let c = (color.r as f64 * l, color.g as f64 * l, color.b as f64 * l);
-                (c.0 as u8, c.1 as u8, c.2 as u8).into()
-            }
+            FragmentShader::Breathing(speed) => dim(color, 0.5 + 0.5 * sin(t.phase(*speed))),
             FragmentShader::Blinking(speed) => {
-                let t = (t * *speed as f64) % 1.0;
-                if t < 0.5 {
+                if t.phase(*speed) < 0.5 {
                     color
                 } else {
                     (0, 0, 0).into()
@@ -135,14 +271,8 @@ impl FragmentShader {
             FragmentShader::LowPass(tau) => {
                 // low pass pixel value
 
-                let rgb = renderman.persistent_data.lowpass.get_pixel(x, y);
-                let (r, g, b) = (rgb.r as f32, rgb.g as f32, rgb.b as f32);
-
-                let r = r + (color.r as f32 - r) / *tau;
-                let g = g + (color.g as f32 - g) / *tau;
-                let b = b + (color.b as f32 - b) / *tau;
-
-                let col = (r as u8, g as u8, b as u8).into();
+                let last = renderman.persistent_data.lowpass.get_pixel(x, y);
+                let col = lerp(last, color, 1.0 / *tau);
                 renderman.persistent_data.lowpass.set_pixel(x, y, col);
 
                 assert!(renderman.persistent_data.lowpass.get_pixel(x, y) == col);
@@ -173,19 +303,160 @@ impl FragmentShader {
             FragmentShader::Rainbow2D(speed) => {
                 // rainbow effect that moves in 2D space
 
-                let t = t * *speed as f64;
-                let h = (x as f64 + y as f64) / 16.0 + t;
-                hsl2rgb(h % 1.0, 1.0, 0.5)
+                let h = (x as f32 + y as f32) / 16.0 + t.phase(*speed);
+                hue_wheel(h)
+            }
+
+            FragmentShader::AudioLevel(hue) => {
+                // louder = brighter, needs the mic feature
+                let level = crate::audio::level();
+
+                let color = if *hue == 0.0 {
+                    color
+                } else {
+                    hue_wheel(level * *hue)
+                };
+
+                dim(color, level)
+            }
+
+            FragmentShader::BeatPulse(decay) => {
+                // flash on every beat, then fade out
+                let Some(last_beat) = renderman.persistent_data.last_beat else {
+                    return (0, 0, 0).into();
+                };
+
+                dim(color, (-t.since(last_beat).max(0.0) * *decay).exp())
+            }
+
+            FragmentShader::WhitePoint(kelvin) => {
+                // the white of the leds is a cold 6500 K, warmer under stage lights
+                tint(color, white_point(*kelvin))
+            }
+
+            FragmentShader::Strobe(rate) => {
+                // counted from the last sync, so the flashes line up with the other lights
+                let since = match renderman.persistent_data.strobe_sync {
+                    Some(sync) => t.relative_to(sync),
+                    None => t,
+                };
+
+                if since.phase(*rate) < (STROBE_FLASH_TIME * *rate).min(0.5) {
+                    color
+                } else {
+                    (0, 0, 0).into()
+                }
+            }
+
+            FragmentShader::Heartbeat(rate) => {
+                // calm blue when cool, fast red when hot, advanced once per frame
+                let heat = crate::thermal::heat();
+                let data = &mut renderman.persistent_data;
+
+                if let Some(last) = data.heartbeat_at {
+                    if t != last {
+                        let beats = t.since(last).max(0.0) * *rate * (1.0 + 2.0 * heat);
+                        data.heartbeat_phase = (data.heartbeat_phase + beats).fract();
+                    }
+                }
+                data.heartbeat_at = Some(t);
+
+                // a strong beat and a weaker one a quarter of a beat later
+                let (since, strength) = if data.heartbeat_phase < 0.25 {
+                    (data.heartbeat_phase, 1.0)
+                } else {
+                    (data.heartbeat_phase - 0.25, 0.6)
+                };
+
+                let color = lerp((0, 0, 255).into(), (255, 0, 0).into(), heat);
+                dim(color, strength * (-since * 12.0).exp())
+            }
+
+            FragmentShader::PaletteCycle(speed) => palette
+                .cycle(t, *speed, x + y, &renderman.palette_table)
+                .unwrap_or(color),
+
+            FragmentShader::Flash(trigger, decay) => {
+                // a loop flashes every second
+                match trigger.elapsed(t, 1.0, &renderman.persistent_data) {
+                    Some(elapsed) => lerp(color, (255, 255, 255).into(), (-elapsed * *decay).exp()),
+                    None => color,
+                }
             }
+
+            // applied by render_single once the layer is drawn
+            FragmentShader::Blur(_) | FragmentShader::Glitch(_) => color,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+/// 3x3 blur in place, as a horizontal and a vertical pass keeping only the previous
+/// pixel so any target size works, past the edges the edge pixels are repeated
+fn blur<M: RenderTarget>(target: &mut M, amount: f32) {
+    let side = amount.clamp(0.0, 1.0) / 4.0;
+    let (width, height) = target.size();
+
+    for y in 0..height {
+        let mut previous = target.get_pixel(0, y);
+        for x in 0..width {
+            let pixel = target.get_pixel(x, y);
+            let next = if x + 1 < width {
+                target.get_pixel(x + 1, y)
+            } else {
+                pixel
+            };
+            target.set_pixel(x, y, smooth(previous, pixel, next, side));
+            previous = pixel;
+        }
+    }
+
+    for x in 0..width {
+        let mut previous = target.get_pixel(x, 0);
+        for y in 0..height {
+            let pixel = target.get_pixel(x, y);
+            let next = if y + 1 < height {
+                target.get_pixel(x, y + 1)
+            } else {
+                pixel
+            };
+            target.set_pixel(x, y, smooth(previous, pixel, next, side));
+            previous = pixel;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ColorPalette {
     Rainbow(f32), // speed
     Solid(LedPixel),
-    Custom(Vec<LedPixel, 16>, f32), // palette, speed
+    Custom(Vec<LedPixel, MAX_PALETTE_COLORS>, f32), // palette, speed
+    Sensor(Sensor, Vec<LedPixel, MAX_PALETTE_COLORS>), // ramp from low to high, empty = blue to red
+    Indexed(f32), // speed, cycles through the palette table, a rainbow while it's empty
+    // a, b, bias: a blend, 0.0 = all a and 1.0 = all b, with PaletteCycle the leds alternate
+    // between a and b, bias = how long b lasts
+    Duotone(LedPixel, LedPixel, f32),
+}
+
+/// live values for ColorPalette::Sensor, from 0.0 to 1.0
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Sensor {
+    Temperature, // 1.0 when the leds are throttled
+    AudioLevel,  // needs the mic feature, like the bands
+    Bass,
+    Mid,
+    Treble,
+}
+
+impl Sensor {
+    pub fn value(self) -> f32 {
+        match self {
+            Sensor::Temperature => crate::thermal::heat(),
+            Sensor::AudioLevel => crate::audio::level(),
+            Sensor::Bass => crate::audio::bands()[0],
+            Sensor::Mid => crate::audio::bands()[1],
+            Sensor::Treble => crate::audio::bands()[2],
+        }
+    }
 }
 
 impl Default for ColorPalette {
@@ -195,16 +466,62 @@ impl Default for ColorPalette {
 }
 
 impl ColorPalette {
-    fn render(&self, t: f64) -> LedPixel {
+    fn render(&self, t: Time, table: &PaletteTable) -> LedPixel {
         match self {
-            ColorPalette::Rainbow(speed) => hsl2rgb((t * *speed as f64) % 1.0, 1.0, 0.5),
+            ColorPalette::Rainbow(speed) => hue_wheel(t.phase(*speed)),
             ColorPalette::Solid(rgb) => *rgb,
             ColorPalette::Custom(palette, speed) => {
-                let idx = (t * *speed as f64).floor() as usize % palette.len();
+                let idx = t.steps(*speed) as usize % palette.len();
                 palette[idx]
             }
+            ColorPalette::Sensor(sensor, colors) => {
+                let value = sensor.value();
+
+                if colors.is_empty() {
+                    hue_wheel((1.0 - value.clamp(0.0, 1.0)) * 2.0 / 3.0)
+                } else {
+                    ramp(colors, value)
+                }
+            }
+            ColorPalette::Indexed(speed) if table.is_empty() => hue_wheel(t.phase(*speed)),
+            ColorPalette::Indexed(speed) => table[t.steps(*speed) as usize % table.len()],
+            ColorPalette::Duotone(a, b, bias) => lerp(*a, *b, bias.clamp(0.0, 1.0)),
         }
     }
+
+    /// the palette turning at speed colors per second, smoothly, offset colors further,
+    /// None for the palettes with a single color
+    fn cycle(&self, t: Time, speed: f32, offset: usize, table: &PaletteTable) -> Option<LedPixel> {
+        let colors = match self {
+            // the wheel is split in 8 colors, an empty table is a rainbow too
+            ColorPalette::Rainbow(_) => {
+                return Some(hue_wheel(t.phase(speed / 8.0) + offset as f32 / 8.0));
+            }
+            ColorPalette::Indexed(_) if table.is_empty() => {
+                return ColorPalette::Rainbow(speed).cycle(t, speed, offset, table);
+            }
+            ColorPalette::Indexed(_) => table,
+            // neighbours half a period apart, so they alternate
+            ColorPalette::Duotone(a, b, bias) => {
+                let phase = (t.phase(speed / 2.0) + offset as f32 / 2.0).fract();
+                return Some(if phase < *bias { *b } else { *a });
+            }
+            ColorPalette::Solid(_) => return None,
+            ColorPalette::Custom(colors, _) | ColorPalette::Sensor(_, colors) => colors,
+        };
+
+        if colors.len() < 2 {
+            return None;
+        }
+
+        let len = colors.len();
+        let position = t.phase(speed / len as f32) * len as f32;
+        let index = position as usize;
+        let from = (index + offset) % len;
+        let to = (from + 1) % len;
+
+        Some(lerp(colors[from], colors[to], position - index as f32))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -213,6 +530,306 @@ pub enum Pattern {
     Animation(&'static [LedPattern], f32), // pattern, speed
     AnimationReverse(&'static [LedPattern], f32), // pattern, speed
     AnimationRandom(&'static [LedPattern], u16), // pattern, decimation
+    AnimationBeat(&'static [LedPattern]),  // next frame on every beat
+    AnimationLoop(&'static [LedPattern], f32, LoopMode), // pattern, speed, what happens at the end
+    AnimationTimed(&'static [TimedFrame], LoopMode), // frames with their durations
+    AnimationRle(RleAnimation, f32, LoopMode), // compressed frames, speed, loop mode
+    AnimationColor(ColorAnimation, f32, LoopMode), // frames, speed, loop mode
+    // sprite, position of its top left corner in leds from the top left, speed in leds per
+    // second, a moving sprite wraps around the target
+    Sprite(Sprite, (f32, f32), (f32, f32)),
+    Transformed(LedPattern, Transform, f32), // pattern, transform, steps per second
+    RandomWalk(u8, f32, f32), // walkers, steps per second, how much of a trail fades per second
+    Scanner(ScanAxis, f32, f32), // axis, leds per second, leds of the trail behind the head
+    // origin as row and column from the top left, leds per second, fading per second, what
+    // starts a ring
+    Ripple((u8, u8), f32, f32, Trigger),
+    BinaryClock(ClockDigits),
+    Spectrum, // low, mid and high frequencies as bars, from left to right
+}
+
+// the 3x3 matrix can't show hours and minutes at the same time,
+// so they are shown one after the other
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ClockDigits {
+    Hours,
+    Minutes,
+}
+
+/// how an animation goes on after its last frame, the one shot ones start at time 0
+/// (the boot, a reset of the time or the start of a notification)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoopMode {
+    Forward,
+    PingPong, // back to the first frame, without repeating the last and the first one
+    Hold,     // once, then the last frame
+    Clear,    // once, then nothing
+}
+
+impl LoopMode {
+    /// the frame to show after step steps, None when there is nothing to show
+    fn frame(self, step: u32, frames: usize) -> Option<usize> {
+        let step = step as usize;
+
+        match self {
+            _ if frames == 0 => None,
+            LoopMode::Forward => Some(step % frames),
+            LoopMode::PingPong if frames == 1 => Some(0),
+            LoopMode::PingPong => {
+                let period = 2 * frames - 2;
+                let step = step % period;
+                Some(if step < frames { step } else { period - step })
+            }
+            LoopMode::Hold => Some(step.min(frames - 1)),
+            LoopMode::Clear => (step < frames).then_some(step),
+        }
+    }
+
+    /// the time into an animation of length, for the ones with a duration per frame,
+    /// ping-pong plays it backwards in time so the last frame lasts twice as long
+    fn time(self, elapsed: u32, length: u32) -> Option<u32> {
+        match self {
+            _ if length == 0 => None,
+            LoopMode::Forward => Some(elapsed % length),
+            LoopMode::PingPong => {
+                let elapsed = elapsed % (2 * length);
+                Some(if elapsed < length {
+                    elapsed
+                } else {
+                    2 * length - 1 - elapsed
+                })
+            }
+            LoopMode::Hold => Some(elapsed.min(length - 1)),
+            LoopMode::Clear => (elapsed < length).then_some(elapsed),
+        }
+    }
+}
+
+/// a long animation kept compressed in flash as runs of the same frame, every run
+/// is 3 bytes: how many frames in a row (at least 1) and the pattern, little endian
+#[derive(Clone, Copy, Debug)]
+pub struct RleAnimation {
+    runs: &'static [u8],
+    frames: usize,
+}
+
+impl RleAnimation {
+    /// checks the runs, in a const a broken animation doesn't compile
+    pub const fn new(runs: &'static [u8]) -> Self {
+        assert!(runs.len().is_multiple_of(3), "an RLE run is 3 bytes");
+
+        let mut frames = 0;
+        let mut i = 0;
+        while i < runs.len() {
+            assert!(runs[i] > 0, "an RLE run can't be empty");
+            frames += runs[i] as usize;
+            i += 3;
+        }
+
+        RleAnimation { runs, frames }
+    }
+
+    /// the frame at index, decoded from the runs as they come, the animation is
+    /// never unpacked in ram
+    fn frame(&self, mut index: usize) -> Option<LedPattern> {
+        for run in self.runs.chunks_exact(3) {
+            let count = run[0] as usize;
+            if index < count {
+                return Some(LedPattern::from_le_bytes([run[1], run[2]]));
+            }
+            index -= count;
+        }
+        None
+    }
+}
+
+/// how Pattern::Transformed changes its pattern, a bit more at every step
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Transform {
+    Rotate,        // by 90° clockwise
+    Shift(i8, i8), // by columns to the right and rows down, wrapping around
+    Mirror,        // left to right, every other step
+    Flip,          // upside down, every other step
+}
+
+impl Transform {
+    fn apply(self, pattern: LedPattern, step: u32) -> LedPattern {
+        match self {
+            Transform::Rotate => (0..step % 4).fold(pattern, |pattern, _| {
+                remap(pattern, |row, column| (2 - column, row))
+            }),
+            Transform::Shift(columns, rows) => {
+                let columns = (columns as i32 * (step % 3) as i32).rem_euclid(3) as usize;
+                let rows = (rows as i32 * (step % 3) as i32).rem_euclid(3) as usize;
+
+                remap(pattern, |row, column| {
+                    ((row + 3 - rows) % 3, (column + 3 - columns) % 3)
+                })
+            }
+            Transform::Mirror if step % 2 == 1 => remap(pattern, |row, column| (row, 2 - column)),
+            Transform::Flip if step % 2 == 1 => remap(pattern, |row, column| (2 - row, column)),
+            Transform::Mirror | Transform::Flip => pattern,
+        }
+    }
+}
+
+/// a pattern with every led taken from the led of source at from(row, column),
+/// rows from the top and columns from the left
+fn remap(source: LedPattern, from: impl Fn(usize, usize) -> (usize, usize)) -> LedPattern {
+    let bit = |row: usize, column: usize| 8 - row * 3 - column;
+    let mut pattern = 0;
+
+    for row in 0..3 {
+        for column in 0..3 {
+            let (from_row, from_column) = from(row, column);
+
+            if source & (1 << bit(from_row, from_column)) != 0 {
+                pattern |= 1 << bit(row, column);
+            }
+        }
+    }
+    pattern
+}
+
+/// the leds a Pattern::Scanner goes back and forth on, rows and columns from the top left
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScanAxis {
+    Row(u8),      // from left to right
+    Column(u8),   // from top to bottom
+    Diagonal,     // from the top left to the bottom right
+    AntiDiagonal, // from the top right to the bottom left
+}
+
+impl ScanAxis {
+    /// how far along the axis the led at bit is, None if it's not on the axis
+    fn position(self, bit: usize) -> Option<usize> {
+        let (row, column) = ((8 - bit) / 3, (8 - bit) % 3);
+
+        match self {
+            ScanAxis::Row(n) => (row == n as usize).then_some(column),
+            ScanAxis::Column(n) => (column == n as usize).then_some(row),
+            ScanAxis::Diagonal => (row == column).then_some(row),
+            ScanAxis::AntiDiagonal => (row + column == 2).then_some(row),
+        }
+    }
+}
+
+/// how bright the led at position of a scanner is, the head goes 0, 1, 2, 1, 0 at
+/// speed leds per second and the trail fades over trail leds behind it
+fn scanner_level(t: Time, position: usize, speed: f32, trail: f32) -> f32 {
+    let head = t.phase(speed / 4.0) * 4.0;
+
+    // the head passes every led going forth at position, and going back at 4 - position
+    let behind = |at: usize| (head - at as f32 + 4.0) % 4.0;
+    let behind = behind(position).min(behind(4 - position));
+
+    (1.0 - behind / (trail + 1.0)).max(0.0)
+}
+
+/// what starts a ring of a Pattern::Ripple or a FragmentShader::Flash, an input event
+/// passed to RenderManager::trigger or a loop
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    Loop, // again and again, for the ripple as soon as the last ring is gone
+    Beat,
+    Button, // any press
+    Ir,     // any packet received
+}
+
+impl Trigger {
+    /// seconds since it last fired, None if it never did, a loop fires every period
+    fn elapsed(self, t: Time, period: f32, data: &ShaderPersistentData) -> Option<f32> {
+        match self {
+            Trigger::Loop => Some(t.phase(1.0 / period) * period),
+            Trigger::Beat => data.last_beat.map(|beat| t.since(beat).max(0.0)),
+            Trigger::Button => data.last_press.map(|press| t.since(press).max(0.0)),
+            Trigger::Ir => data.last_ir.map(|ir| t.since(ir).max(0.0)),
+        }
+    }
+}
+
+/// how bright a led at distance leds from the origin of a ripple is, elapsed seconds
+/// after it started: a ring 1 led wide going out at speed leds per second, fading by decay
+fn ripple_level(distance: f32, elapsed: f32, speed: f32, decay: f32) -> f32 {
+    let ring = 1.0 - (distance - elapsed * speed).abs();
+
+    ring.max(0.0) * (-elapsed * decay).exp()
+}
+
+/// a random step from the led at bit to one of its neighbours, inside the 3x3 pattern
+fn walk(bit: usize, rng: &mut SmallRng) -> usize {
+    let (row, column) = ((8 - bit) / 3, (8 - bit) % 3);
+
+    let moves = [
+        (row.wrapping_sub(1), column),
+        (row + 1, column),
+        (row, column.wrapping_sub(1)),
+        (row, column + 1),
+    ];
+    let inside = |(row, column): &(usize, usize)| *row < 3 && *column < 3;
+
+    let count = moves.into_iter().filter(inside).count();
+    let (row, column) = moves
+        .into_iter()
+        .filter(inside)
+        .nth(rng.gen_range(0..count))
+        .unwrap_or((row, column));
+
+    8 - row * 3 - column
+}
+
+/// how bright the trail of a led visited at visited is, fading by decay per second
+fn trail(visited: Option<Time>, t: Time, decay: f32) -> f32 {
+    // a visit after t is from before the time started over, long faded
+    visited
+        .filter(|&visited| visited <= t)
+        .map_or(0.0, |visited| {
+            (1.0 - t.since(visited) * decay).clamp(0.0, 1.0)
+        })
+}
+
+/// pixel art of any size for Pattern::Sprite, rows from the top, None is transparent
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    width: usize,
+    height: usize,
+    pixels: &'static [Option<(u8, u8, u8)>],
+}
+
+impl Sprite {
+    /// checks the size, in a const a broken sprite doesn't compile
+    pub const fn new(width: usize, pixels: &'static [Option<(u8, u8, u8)>]) -> Self {
+        assert!(
+            width > 0 && pixels.len().is_multiple_of(width),
+            "a sprite is a rectangle"
+        );
+
+        Sprite {
+            width,
+            height: pixels.len() / width,
+            pixels,
+        }
+    }
+
+    fn pixel(&self, column: usize, row: usize) -> Option<(u8, u8, u8)> {
+        if column >= self.width {
+            return None;
+        }
+        self.pixels
+            .get(row * self.width + column)
+            .copied()
+            .flatten()
+    }
+}
+
+/// the first led of a sprite of size leds along an axis of length leds, from position
+/// at speed leds per second, to the nearest led, it slides out on one side and back in
+/// on the other
+fn sprite_offset(t: Time, position: f32, speed: f32, size: usize, leds: usize) -> i32 {
+    let (size, span) = (size as i32, (leds + size) as i32);
+    let travelled = t.phase(speed.abs() / span as f32) * span as f32 * speed.signum();
+
+    ((position + travelled).round() as i32 + size).rem_euclid(span) - size
 }
 
 impl Default for Pattern {
@@ -221,17 +838,130 @@ impl Default for Pattern {
     }
 }
 
+/// the frame of an AnimationColor that shows the leds of the framebuffer
+pub fn color_frame(framebuffer: &RawFramebuffer) -> ColorFrame {
+    // the rows from the top are the x from the bottom, see Pattern::pixel
+    core::array::from_fn(|row| {
+        core::array::from_fn(|column| {
+            let pixel = framebuffer.get_pixel(2 - row, column);
+            (pixel.r, pixel.g, pixel.b)
+        })
+    })
+}
+
 impl Pattern {
-    fn render(&self, t: f64, renderman: &mut RenderManager) -> LedPattern {
+    /// the color of the led at (x, y) for the patterns with their own colors, None where
+    /// the layers below show through, size is the size of the target
+    fn pixel(&self, t: Time, x: usize, y: usize, size: (usize, usize)) -> Option<LedPixel> {
+        match self {
+            Pattern::AnimationColor(frames, speed, mode) => {
+                let idx = mode.frame(t.steps(*speed), frames.len())?;
+                let rgb = frames[idx][2 - x % 3][y % 3];
+
+                (rgb != (0, 0, 0)).then(|| rgb.into())
+            }
+            Pattern::Sprite(sprite, position, speed) => {
+                // x is the row from the bottom, y the column from the left
+                let left = sprite_offset(t, position.0, speed.0, sprite.width, size.1);
+                let top = sprite_offset(t, position.1, speed.1, sprite.height, size.0);
+
+                let column = usize::try_from(y as i32 - left).ok()?;
+                let row = usize::try_from((size.0 - 1 - x) as i32 - top).ok()?;
+                sprite.pixel(column, row).map(Into::into)
+            }
+            _ => None,
+        }
+    }
+
+    /// how bright the led at bit of the pattern is, for the patterns that fade
+    fn level(&self, t: Time, bit: usize, data: &ShaderPersistentData) -> f32 {
+        match self {
+            Pattern::RandomWalk(_, _, decay) => trail(data.visited[bit], t, *decay),
+            Pattern::Scanner(axis, speed, trail) => axis
+                .position(bit)
+                .map_or(0.0, |position| scanner_level(t, position, *speed, *trail)),
+            Pattern::Ripple(origin, speed, decay, trigger) => {
+                let Some(elapsed) = trigger.elapsed(t, RIPPLE_TRAVEL / speed, data) else {
+                    return 0.0;
+                };
+
+                let (row, column) = ((8 - bit) / 3, (8 - bit) % 3);
+                let distance =
+                    (row as f32 - origin.0 as f32).hypot(column as f32 - origin.1 as f32);
+
+                ripple_level(distance, elapsed, *speed, *decay)
+            }
+            _ => 1.0,
+        }
+    }
+
+    fn render<M: RenderTarget>(&self, t: Time, renderman: &mut RenderManager<M>) -> LedPattern {
         match self {
             Pattern::Simple(pattern) => *pattern,
             Pattern::Animation(pattern, speed) => {
-                let idx = (t * *speed as f64) as usize % pattern.len();
+                let idx = t.steps(*speed) as usize % pattern.len();
                 let pattern = &pattern[idx];
                 *pattern
             }
+            Pattern::AnimationLoop(pattern, speed, mode) => {
+                match mode.frame(t.steps(*speed), pattern.len()) {
+                    Some(idx) => pattern[idx],
+                    None => 0,
+                }
+            }
+            Pattern::AnimationRle(animation, speed, mode) => mode
+                .frame(t.steps(*speed), animation.frames)
+                .and_then(|idx| animation.frame(idx))
+                .unwrap_or(0),
+            Pattern::Transformed(pattern, transform, speed) => {
+                transform.apply(*pattern, t.steps(*speed))
+            }
+            Pattern::RandomWalk(count, speed, decay) => {
+                let step = t.steps(*speed);
+                let count = (*count as usize).min(MAX_WALKERS);
+                let data = &mut renderman.persistent_data;
+
+                if data.walkers.len() != count {
+                    data.walkers.clear();
+                    for _ in 0..count {
+                        let _ = data.walkers.push(renderman.rng.gen_range(0..9));
+                    }
+                } else if step != data.walk_step {
+                    for walker in data.walkers.iter_mut() {
+                        *walker = walk(*walker, &mut renderman.rng);
+                    }
+                }
+                data.walk_step = step;
+
+                for walker in data.walkers.iter() {
+                    data.visited[*walker] = Some(t);
+                }
+
+                (0..9)
+                    .filter(|bit| trail(data.visited[*bit], t, *decay) > 0.0)
+                    .fold(0, |pattern, bit| pattern | 1 << bit)
+            }
+            Pattern::Scanner(..) | Pattern::Ripple(..) => (0..9)
+                .filter(|bit| self.level(t, *bit, &renderman.persistent_data) > 0.0)
+                .fold(0, |pattern, bit| pattern | 1 << bit),
+            // they have their own colors, see pixel()
+            Pattern::AnimationColor(..) | Pattern::Sprite(..) => 0,
+            Pattern::AnimationTimed(frames, mode) => {
+                let length = frames.iter().map(|(_, millis)| *millis as u32).sum();
+
+                let Some(mut at) = mode.time(t.steps(1000.0), length) else {
+                    return 0;
+                };
+                for (pattern, millis) in frames.iter() {
+                    if at < *millis as u32 {
+                        return *pattern;
+                    }
+                    at -= *millis as u32;
+                }
+                0
+            }
             Pattern::AnimationReverse(pattern, speed) => {
-                let idx = (t * *speed as f64) as usize % pattern.len();
+                let idx = t.steps(*speed) as usize % pattern.len();
                 let pattern = &pattern[pattern.len() - idx - 1];
                 *pattern
             }
@@ -241,7 +971,11 @@ impl Pattern {
 
                 renderman.persistent_data.frame_counter += 1;
 
-                if renderman.persistent_data.frame_counter % *decimation as u32 == 0 {
+                if renderman
+                    .persistent_data
+                    .frame_counter
+                    .is_multiple_of(*decimation as u32)
+                {
                     let idx = renderman.rng.gen_range(0..pattern.len());
                     let pattern = &pattern[idx];
                     *pattern
@@ -249,6 +983,245 @@ impl Pattern {
                     0
                 }
             }
+            Pattern::AnimationBeat(pattern) => {
+                pattern[renderman.persistent_data.beats as usize % pattern.len()]
+            }
+            Pattern::Spectrum => {
+                let mut pattern = 0;
+
+                for (column, level) in crate::audio::bands().iter().enumerate() {
+                    let height = (level * 3.0).round() as usize;
+
+                    // the bottom row is bits 0-2, the leftmost led of a row is the highest bit
+                    for row in 0..height.min(3) {
+                        pattern |= 1 << (row * 3 + 2 - column);
+                    }
+                }
+                pattern
+            }
+            Pattern::BinaryClock(digits) => {
+                let Some(seconds) = crate::clock::seconds_of_day() else {
+                    // blink the center led, the time was never set
+                    return if t.phase(1.0) < 0.5 { 0b000010000 } else { 0 };
+                };
+
+                // 2 seconds for the hours, 2 seconds for the minutes
+                let showing_hours = t.phase(0.25) < 0.5;
+
+                match digits {
+                    ClockDigits::Hours if showing_hours => (seconds / 3600) as LedPattern,
+                    ClockDigits::Minutes if !showing_hours => (seconds / 60 % 60) as LedPattern,
+                    _ => 0,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn frames(mode: LoopMode, count: usize) -> [Option<usize>; 8] {
+        core::array::from_fn(|step| mode.frame(step as u32, count))
+    }
+
+    #[test]
+    fn forward_loops() {
+        assert_eq!(
+            frames(LoopMode::Forward, 3),
+            [0, 1, 2, 0, 1, 2, 0, 1].map(Some)
+        );
+    }
+
+    #[test]
+    fn ping_pong_turns_around() {
+        assert_eq!(
+            frames(LoopMode::PingPong, 3),
+            [0, 1, 2, 1, 0, 1, 2, 1].map(Some)
+        );
+        assert_eq!(frames(LoopMode::PingPong, 1), [Some(0); 8]);
+    }
+
+    #[test]
+    fn one_shots_stop() {
+        assert_eq!(
+            frames(LoopMode::Hold, 3),
+            [0, 1, 2, 2, 2, 2, 2, 2].map(Some)
+        );
+        assert_eq!(
+            frames(LoopMode::Clear, 3),
+            [Some(0), Some(1), Some(2), None, None, None, None, None]
+        );
+    }
+
+    #[test]
+    fn no_frames() {
+        assert_eq!(frames(LoopMode::Forward, 0), [None; 8]);
+        assert_eq!(LoopMode::Hold.time(10, 0), None);
+    }
+
+    #[test]
+    fn timed_loops() {
+        assert_eq!(LoopMode::Forward.time(1250, 1000), Some(250));
+        assert_eq!(LoopMode::PingPong.time(250, 1000), Some(250));
+        assert_eq!(LoopMode::PingPong.time(1250, 1000), Some(749));
+        assert_eq!(LoopMode::Hold.time(1250, 1000), Some(999));
+        assert_eq!(LoopMode::Clear.time(999, 1000), Some(999));
+        assert_eq!(LoopMode::Clear.time(1000, 1000), None);
+    }
+
+    #[test]
+    fn rle_decodes_runs() {
+        const ANIMATION: RleAnimation =
+            RleAnimation::new(&[2, 0xff, 0x01, 1, 0x10, 0x00, 3, 0x00, 0x00]);
+
+        assert_eq!(ANIMATION.frames, 6);
+        assert_eq!(
+            core::array::from_fn::<_, 7, _>(|i| ANIMATION.frame(i)),
+            [
+                Some(0x1ff),
+                Some(0x1ff),
+                Some(0x10),
+                Some(0),
+                Some(0),
+                Some(0),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn transforms() {
+        let glider = 0b010_001_111;
+
+        assert_eq!(Transform::Rotate.apply(glider, 1), 0b100_101_110);
+        assert_eq!(Transform::Rotate.apply(glider, 4), glider);
+        assert_eq!(Transform::Shift(1, 0).apply(glider, 1), 0b001_100_111);
+        assert_eq!(Transform::Shift(0, -1).apply(glider, 1), 0b001_111_010);
+        assert_eq!(Transform::Shift(1, 1).apply(glider, 3), glider);
+        assert_eq!(Transform::Mirror.apply(glider, 1), 0b010_100_111);
+        assert_eq!(Transform::Mirror.apply(glider, 2), glider);
+        assert_eq!(Transform::Flip.apply(glider, 1), 0b111_001_010);
+    }
+
+    #[test]
+    fn walkers_step_to_a_neighbour() {
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        for bit in 0..9 {
+            for _ in 0..16 {
+                let next = walk(bit, &mut rng);
+                let (row, column) = ((8 - bit) / 3, (8 - bit) % 3);
+                let (next_row, next_column) = ((8 - next) / 3, (8 - next) % 3);
+
+                assert!(next < 9);
+                assert_eq!(row.abs_diff(next_row) + column.abs_diff(next_column), 1);
+            }
         }
     }
+
+    #[test]
+    fn trails_fade() {
+        let visited = Some(Time::from_secs(1.0));
+
+        assert_eq!(trail(visited, Time::from_secs(1.0), 2.0), 1.0);
+        assert_eq!(trail(visited, Time::from_secs(1.25), 2.0), 0.5);
+        assert_eq!(trail(visited, Time::from_secs(2.0), 2.0), 0.0);
+        assert_eq!(trail(None, Time::from_secs(1.0), 2.0), 0.0);
+        assert_eq!(trail(visited, Time::from_secs(0.5), 2.0), 0.0);
+    }
+
+    #[test]
+    fn scanner_axes() {
+        let leds = |axis: ScanAxis| (0..9).filter_map(move |bit| axis.position(bit).map(|_| bit));
+
+        assert!(leds(ScanAxis::Row(0)).eq([6, 7, 8]));
+        assert!(leds(ScanAxis::Column(2)).eq([0, 3, 6]));
+        assert!(leds(ScanAxis::Diagonal).eq([0, 4, 8]));
+        assert_eq!(ScanAxis::AntiDiagonal.position(6), Some(0));
+        assert_eq!(ScanAxis::AntiDiagonal.position(2), Some(2));
+    }
+
+    #[test]
+    fn scanner_trails_behind() {
+        let levels =
+            |seconds| [0, 1, 2].map(|led| scanner_level(Time::from_secs(seconds), led, 1.0, 1.0));
+
+        // going forth, the head on 1
+        assert_eq!(levels(1.0), [0.5, 1.0, 0.0]);
+        // going back, the head on 1 again
+        assert_eq!(levels(3.0), [0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn ripples_go_out() {
+        // the ring is on the origin when it starts, one led out after a second
+        assert_eq!(ripple_level(0.0, 0.0, 1.0, 0.0), 1.0);
+        assert_eq!(ripple_level(1.0, 0.0, 1.0, 0.0), 0.0);
+        assert_eq!(ripple_level(1.0, 1.0, 1.0, 0.0), 1.0);
+        assert_eq!(ripple_level(0.0, 1.0, 1.0, 0.0), 0.0);
+        assert_eq!(ripple_level(1.0, 0.5, 1.0, 0.0), 0.5);
+        assert!(ripple_level(1.0, 1.0, 1.0, 1.0) < 0.4);
+    }
+
+    #[test]
+    fn color_frames_replay_the_framebuffer() {
+        let mut framebuffer = RawFramebuffer::new();
+        framebuffer.set_pixel(0, 0, (255, 0, 0).into());
+        framebuffer.set_pixel(2, 0, (0, 255, 0).into());
+        framebuffer.set_pixel(0, 1, (0, 0, 255).into());
+        framebuffer.set_pixel(1, 2, (255, 255, 0).into());
+
+        let frames = std::vec![color_frame(&framebuffer)].leak();
+        let pattern = Pattern::AnimationColor(frames, 1.0, LoopMode::Forward);
+        for x in 0..3 {
+            for y in 0..3 {
+                let pixel = pattern.pixel(Time::default(), x, y, (3, 3));
+                assert_eq!(pixel.unwrap_or_default(), framebuffer.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn boot_logo_frames_show_like_streamed_ones() {
+        // what the badge gets over usb for a streamed frame or a frame of the boot logo
+        let data: std::vec::Vec<u8> = (0..27).map(|i| i * 9).collect();
+        let streamed = RawFramebuffer::from_rgb(&data);
+        let logo = std::vec![color_frame(&streamed)].leak();
+
+        let pattern = Pattern::AnimationColor(logo, 1.0, LoopMode::Forward);
+        for x in 0..3 {
+            for y in 0..3 {
+                let pixel = pattern.pixel(Time::default(), x, y, (3, 3));
+                assert_eq!(pixel.unwrap_or_default(), streamed.get_pixel(x, y));
+            }
+        }
+        for (i, rgb) in data.chunks_exact(3).enumerate() {
+            assert_eq!(logo[0][2 - i % 3][i / 3], (rgb[0], rgb[1], rgb[2]));
+        }
+    }
+
+    #[test]
+    fn sprites_wrap_around() {
+        let at = |seconds| sprite_offset(Time::from_secs(seconds), 0.0, 1.0, 2, 3);
+
+        assert_eq!([0.0, 1.0, 2.0, 3.0, 4.0, 5.0].map(at), [0, 1, 2, -2, -1, 0]);
+        assert_eq!(sprite_offset(Time::from_secs(1.0), 0.0, -1.0, 2, 3), -1);
+        assert_eq!(sprite_offset(Time::from_secs(9.0), 1.0, 0.0, 2, 3), 1);
+    }
+
+    #[test]
+    fn sprites_are_transparent_outside() {
+        const SPRITE: Sprite = Sprite::new(2, &[Some((1, 2, 3)), None, None, Some((4, 5, 6))]);
+
+        assert_eq!(SPRITE.height, 2);
+        assert_eq!(SPRITE.pixel(0, 0), Some((1, 2, 3)));
+        assert_eq!(SPRITE.pixel(1, 0), None);
+        assert_eq!(SPRITE.pixel(1, 1), Some((4, 5, 6)));
+        assert_eq!(SPRITE.pixel(2, 0), None);
+        assert_eq!(SPRITE.pixel(0, 2), None);
+    }
 }
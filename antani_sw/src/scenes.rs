@@ -1,31 +1,116 @@
+use core::ops::Deref;
+
 use embassy_sync::lazy_lock::LazyLock;
+use embassy_time::{Duration, Instant};
 use heapless::Vec;
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::rgbeffects::{
+    ClockDigits, ColorAnimation, ColorPalette, FragmentShader, LedPattern, LoopMode, Pattern,
+    RenderCommand, RleAnimation, Sprite, TimedFrame,
+};
+
+// scene description DSL, the capacity of every list is checked at compile time
+//
+//     scene![
+//         layer! {
+//             effect: Pattern::Simple(patterns.glider),
+//             pattern_shaders: shaders![FragmentShader::Breathing(0.7)],
+//         },
+//     ]
+//
+// a scene brighter than the others starts with its gain, scene![gain: 0.6; layer! { .. }]
 
-use crate::rgbeffects::{ColorPalette, FragmentShader, LedPattern, Pattern, RenderCommand};
+/// builds a heapless::Vec, failing to compile if the elements don't fit
+macro_rules! bounded_vec {
+    (@unit $item:expr) => {
+        ()
+    };
+    ($cap:expr; $($item:expr),* $(,)?) => {{
+        const _: () = assert!(
+            <[()]>::len(&[$($crate::scenes::bounded_vec!(@unit $item)),*]) <= $cap,
+            "too many elements for the capacity of the list"
+        );
+        ::heapless::Vec::<_, { $cap }>::from_slice(&[$($item),*]).unwrap()
+    }};
+}
+
+/// a single render command, missing fields take the default value
+macro_rules! layer {
+    ($($field:ident : $value:expr),* $(,)?) => {
+        $crate::rgbeffects::RenderCommand {
+            $($field: $value,)*
+            ..Default::default()
+        }
+    };
+}
+
+/// the layers of a scene, rendered one on top of the other
+macro_rules! scene {
+    (gain: $gain:expr; $($layer:expr),* $(,)?) => {
+        $crate::scenes::Scene {
+            layers: $crate::scenes::bounded_vec!($crate::scenes::MAX_LAYERS; $($layer),*),
+            gain: $gain,
+        }
+    };
+    ($($layer:expr),* $(,)?) => {
+        $crate::scenes::scene![gain: 1.0; $($layer),*]
+    };
+}
+
+/// the list of scenes selected with the button
+macro_rules! scene_list {
+    ($($scene:expr),* $(,)?) => {
+        $crate::scenes::bounded_vec!($crate::scenes::MAX_SCENES; $($scene),*)
+    };
+}
+
+/// list of fragment shaders for a layer
+macro_rules! shaders {
+    ($($shader:expr),* $(,)?) => {
+        $crate::scenes::bounded_vec!($crate::rgbeffects::MAX_SHADERS; $($shader),*)
+    };
+}
+
+/// list of colors for ColorPalette::Custom
+macro_rules! palette {
+    ($($color:expr),* $(,)?) => {
+        $crate::scenes::bounded_vec!($crate::rgbeffects::MAX_PALETTE_COLORS; $($color),*)
+    };
+}
+
+// the ones the other macros reach through $crate::scenes
+pub(crate) use {bounded_vec, scene};
 
 pub struct Patterns {
-    pub power_100: LedPattern,
-    pub power_75: LedPattern,
-    pub power_50: LedPattern,
-    pub power_25: LedPattern,
     pub glider: LedPattern,
     pub all_on: LedPattern,
+    pub cross: LedPattern,
+    pub plus: LedPattern,
     pub vertical_stripe_1: LedPattern,
     pub vertical_stripe_2: LedPattern,
     pub vertical_stripe_3: LedPattern,
     pub everything_once: &'static [LedPattern],
+    pub dice: &'static [LedPattern],
     pub boot_animation: &'static [LedPattern],
+    pub eyes: &'static [TimedFrame],
+    pub fireworks: RleAnimation,
+    pub fire: ColorAnimation,
+    pub comet: Sprite,
 }
 
-pub static PATTERNS: LazyLock<Patterns> = LazyLock::new(|| Patterns {
-    // patterns for light power
-    power_100: 0b111111111,
-    power_75: 0b000111111,
-    power_50: 0b000000111,
-    power_25: 0b000000001,
+// the colors of PATTERNS.fire
+const EMBER: (u8, u8, u8) = (200, 10, 0);
+const FLAME: (u8, u8, u8) = (255, 80, 0);
+const TIP: (u8, u8, u8) = (255, 170, 20);
+const NONE: (u8, u8, u8) = (0, 0, 0);
 
+pub static PATTERNS: LazyLock<Patterns> = LazyLock::new(|| Patterns {
     glider: 0b010001111,
     all_on: 0b111111111,
+    cross: 0b101010101,
+    plus: 0b010111010,
     vertical_stripe_1: 0b100100100,
     vertical_stripe_2: 0b010010010,
     vertical_stripe_3: 0b001001001,
@@ -41,6 +126,18 @@ pub static PATTERNS: LazyLock<Patterns> = LazyLock::new(|| Patterns {
         0b000000010,
         0b000000001,
     ],
+    // dice faces, from 1 to 9 pips
+    dice: &[
+        0b000010000,
+        0b001000100,
+        0b001010100,
+        0b101000101,
+        0b101010101,
+        0b111000111,
+        0b111010111,
+        0b111101111,
+        0b111111111,
+    ],
     boot_animation: &[
         0b010000000,
         0b010010000,
@@ -53,134 +150,310 @@ pub static PATTERNS: LazyLock<Patterns> = LazyLock::new(|| Patterns {
         0b000000000,
         0b000000000,
     ],
+    // open most of the time, a blink now and then, twice in a row sometimes
+    eyes: &[
+        (0b101101000, 2500),
+        (0b000101000, 150),
+        (0b101101000, 1500),
+        (0b000101000, 150),
+        (0b101101000, 200),
+        (0b000101000, 150),
+    ],
+    // a rocket going up and bursting, then a pause before the next one
+    fireworks: const {
+        RleAnimation::new(&[
+            3, 0x02, 0x00, // 000000010
+            3, 0x10, 0x00, // 000010000
+            3, 0x80, 0x00, // 010000000
+            2, 0xba, 0x00, // 010111010
+            3, 0x55, 0x01, // 101010101
+            4, 0xef, 0x01, // 111101111
+            3, 0x45, 0x01, // 101000101
+            12, 0x00, 0x00, // 000000000
+        ])
+    },
+    // flickering flames, the tips move from side to side
+    fire: &[
+        [
+            [NONE, TIP, NONE],
+            [FLAME, TIP, FLAME],
+            [EMBER, FLAME, EMBER],
+        ],
+        [
+            [NONE, NONE, TIP],
+            [TIP, FLAME, FLAME],
+            [EMBER, EMBER, FLAME],
+        ],
+        [
+            [TIP, NONE, NONE],
+            [FLAME, FLAME, TIP],
+            [FLAME, EMBER, EMBER],
+        ],
+        [
+            [NONE, NONE, NONE],
+            [FLAME, TIP, FLAME],
+            [EMBER, FLAME, EMBER],
+        ],
+    ],
+    // a white head with a tail fading to red
+    comet: const {
+        Sprite::new(
+            3,
+            &[Some((60, 0, 0)), Some((255, 60, 0)), Some((255, 230, 180))],
+        )
+    },
 });
 
-pub type Scenes = Vec<Vec<RenderCommand, 8>, 20>;
-pub fn scenes() -> Scenes {
+/// notifications shown on top of the current scene, the first ones are sent by the host
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    Mention,
+    Message,
+    BuildFailed,
+    BuildPassed,
+    /// the leds are being dimmed to cool the badge down
+    Overheating,
+    /// a badge nearby wants to play rock paper scissors
+    Challenge,
+}
+
+/// how long a notification stays on top of the scene, in seconds
+pub const NOTIFICATION_DURATION: f32 = 3.0;
+
+pub fn notification(notification: &Notification) -> RenderCommand {
     let patterns = PATTERNS.get();
 
-    Vec::from_slice(&[
-        // normal glider
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.glider),
-            color: ColorPalette::Solid((0, 0, 255).into()),
-            ..Default::default()
-        }])
-        .unwrap(),
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.glider),
-            color: ColorPalette::Solid((0, 255, 0).into()),
-            ..Default::default()
-        }])
-        .unwrap(),
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.glider),
-            color: ColorPalette::Solid((255, 0, 0).into()),
-            ..Default::default()
-        }])
-        .unwrap(),
-        // breathing glider
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.glider),
-            color: ColorPalette::Solid((0, 0, 255).into()),
-            pattern_shaders: Vec::from_slice(&[FragmentShader::Breathing(0.7)]).unwrap(),
-            ..Default::default()
-        }])
-        .unwrap(),
-        // italy flag
-        Vec::from_slice(&[
-            RenderCommand {
-                effect: Pattern::Simple(patterns.vertical_stripe_1),
-                color: ColorPalette::Solid((0, 255, 0).into()),
-                ..Default::default()
-            },
-            RenderCommand {
-                effect: Pattern::Simple(patterns.vertical_stripe_2),
-                color: ColorPalette::Solid((255, 255, 255).into()),
-                ..Default::default()
-            },
-            RenderCommand {
-                effect: Pattern::Simple(patterns.vertical_stripe_3),
-                color: ColorPalette::Solid((255, 0, 0).into()),
-                ..Default::default()
-            },
-        ])
-        .unwrap(),
-        // single rainbow glider
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.glider),
-            pattern_shaders: Vec::from_slice(&[FragmentShader::Rainbow2D(0.5)]).unwrap(),
+    match notification {
+        Notification::Mention => RenderCommand {
+            effect: Pattern::Simple(patterns.all_on),
+            color: ColorPalette::Solid((0, 200, 255).into()),
+            pattern_shaders: shaders![FragmentShader::Blinking(4.0)],
             ..Default::default()
-        }])
-        .unwrap(),
-        // rainbow 2d
-        Vec::from_slice(&[RenderCommand {
-            screen_shaders: Vec::from_slice(&[FragmentShader::Rainbow2D(0.5)]).unwrap(),
+        },
+        Notification::Message => RenderCommand {
+            effect: Pattern::Animation(patterns.everything_once, 9.0),
+            color: ColorPalette::Solid((255, 255, 255).into()),
             ..Default::default()
-        }])
-        .unwrap(),
-        // solid red
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.all_on),
+        },
+        Notification::BuildFailed => RenderCommand {
+            effect: Pattern::Simple(patterns.cross),
             color: ColorPalette::Solid((255, 0, 0).into()),
+            pattern_shaders: shaders![FragmentShader::Blinking(2.0)],
             ..Default::default()
-        }])
-        .unwrap(),
-        // solid green
-        Vec::from_slice(&[RenderCommand {
+        },
+        Notification::BuildPassed => RenderCommand {
             effect: Pattern::Simple(patterns.all_on),
             color: ColorPalette::Solid((0, 255, 0).into()),
+            pattern_shaders: shaders![FragmentShader::Breathing(1.0)],
             ..Default::default()
-        }])
-        .unwrap(),
-        // solid blue
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.all_on),
-            color: ColorPalette::Solid((0, 0, 255).into()),
-            ..Default::default()
-        }])
-        .unwrap(),
-        // solid white
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.all_on),
-            color: ColorPalette::Solid((255, 255, 255).into()),
-            ..Default::default()
-        }])
-        .unwrap(),
-        // police lights
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(patterns.all_on),
-            color: ColorPalette::Custom(
-                Vec::from_slice(&[
-                    (0, 0, 0).into(),
-                    (255, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (255, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 255).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 255).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                    (0, 0, 0).into(),
-                ])
-                .unwrap(),
-                15.0,
-            ),
+        },
+        // the heartbeat is red and fast when hot
+        Notification::Overheating => RenderCommand {
+            effect: Pattern::Simple(patterns.plus),
+            pattern_shaders: shaders![FragmentShader::Heartbeat(1.0)],
             ..Default::default()
-        }])
-        .unwrap(),
-        // off
-        Vec::from_slice(&[RenderCommand {
-            effect: Pattern::Simple(0),
-            color: ColorPalette::Solid((0, 0, 0).into()),
+        },
+        Notification::Challenge => RenderCommand {
+            effect: Pattern::Simple(patterns.cross),
+            color: ColorPalette::Solid((255, 160, 0).into()),
+            pattern_shaders: shaders![FragmentShader::Blinking(3.0)],
             ..Default::default()
-        }])
-        .unwrap(),
-    ])
-    .unwrap()
+        },
+    }
+}
+
+/// do not disturb, a still dim red bar instead of everything else
+pub fn do_not_disturb() -> RenderCommand {
+    RenderCommand {
+        effect: Pattern::Simple(0b000_111_000),
+        color: ColorPalette::Solid((80, 0, 0).into()),
+        ..Default::default()
+    }
+}
+
+/// how long the number of a scene chosen with the digits of a remote is shown, in seconds
+pub const SCENE_NUMBER_DURATION: f32 = 1.0;
+
+/// as many blinking leds as the number, from the top left
+pub fn scene_number(number: u8) -> RenderCommand {
+    const ALL_LEDS: LedPattern = 0b111_111_111;
+
+    RenderCommand {
+        effect: Pattern::Simple(ALL_LEDS & !(ALL_LEDS >> number)),
+        color: ColorPalette::Solid((255, 255, 255).into()),
+        pattern_shaders: shaders![FragmentShader::Blinking(4.0)],
+        ..Default::default()
+    }
+}
+
+/// seconds the button is held through the countdown of the factory reset
+pub const FACTORY_RESET_COUNTDOWN: f32 = 5.0;
+
+/// red leds going off one at a time until the factory reset, from the time reset
+pub fn factory_reset_countdown() -> RenderCommand {
+    static FRAMES: [LedPattern; 9] = [
+        0b111_111_111,
+        0b011_111_111,
+        0b001_111_111,
+        0b000_111_111,
+        0b000_011_111,
+        0b000_001_111,
+        0b000_000_111,
+        0b000_000_011,
+        0b000_000_001,
+    ];
+
+    RenderCommand {
+        effect: Pattern::AnimationLoop(
+            &FRAMES,
+            FRAMES.len() as f32 / FACTORY_RESET_COUNTDOWN,
+            LoopMode::Hold,
+        ),
+        color: ColorPalette::Solid((255, 0, 0).into()),
+        ..Default::default()
+    }
+}
+
+pub const MAX_LAYERS: usize = 8;
+// the scene player keeps one bit per scene in a u32
+pub const MAX_SCENES: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub layers: Vec<RenderCommand, MAX_LAYERS>,
+    /// multiplies the brightness chosen with the button, so a scene with all the leds
+    /// in white doesn't look much brighter than one with a single led
+    pub gain: f32,
+}
+
+// a scene is rendered as the list of its layers
+impl Deref for Scene {
+    type Target = [RenderCommand];
+
+    fn deref(&self) -> &Self::Target {
+        &self.layers
+    }
+}
+
+/// a scene with no gain trim
+impl FromIterator<RenderCommand> for Scene {
+    fn from_iter<I: IntoIterator<Item = RenderCommand>>(iter: I) -> Self {
+        Self {
+            layers: iter.into_iter().collect(),
+            gain: 1.0,
+        }
+    }
+}
+
+pub type Scenes = Vec<Scene, MAX_SCENES>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Playback {
+    #[default]
+    Sequential,
+    Shuffle {
+        interval: u16,
+    }, // seconds between scenes, 0 = only on button press
+}
+
+/// picks the next scene to show
+pub struct ScenePlayer {
+    pub playback: Playback,
+    // scenes already shown in this shuffle round, one bit per scene, 64 so that the mask
+    // of all the MAX_SCENES doesn't overflow
+    played: u64,
+    last_change: Instant,
+}
+
+impl ScenePlayer {
+    pub fn new(playback: Playback) -> Self {
+        Self {
+            playback,
+            played: 0,
+            last_change: Instant::now(),
+        }
+    }
+
+    pub fn next(&mut self, current: usize, count: usize, rng: &mut SmallRng) -> usize {
+        self.last_change = Instant::now();
+
+        match self.playback {
+            Playback::Sequential => (current + 1) % count,
+            Playback::Shuffle { .. } => {
+                self.played |= 1 << current;
+
+                let all = (1u64 << count) - 1;
+                if self.played & all == all {
+                    // every scene was shown, start a new round
+                    // without showing the same scene twice in a row
+                    self.played = 1 << current;
+                }
+
+                let candidates = count as u32 - (self.played & all).count_ones();
+                if candidates == 0 {
+                    // there is only one scene
+                    return current;
+                }
+
+                let mut pick = rng.gen_range(0..candidates);
+                for i in 0..count {
+                    if self.played & (1 << i) == 0 {
+                        if pick == 0 {
+                            return i;
+                        }
+                        pick -= 1;
+                    }
+                }
+                current
+            }
+        }
+    }
+
+    /// jumps to a random scene other than the current one, whatever the playback
+    pub fn random(&mut self, current: usize, count: usize, rng: &mut SmallRng) -> usize {
+        self.last_change = Instant::now();
+
+        if count < 2 {
+            return current;
+        }
+
+        let pick = rng.gen_range(0..count - 1);
+        if pick >= current {
+            pick + 1
+        } else {
+            pick
+        }
+    }
+
+    /// true when it's time to move to the next scene on its own
+    pub fn timer_expired(&self) -> bool {
+        match self.playback {
+            Playback::Shuffle { interval } if interval > 0 => {
+                self.last_change.elapsed() > Duration::from_secs(interval as u64)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// the scenes are described in scenes.ron and turned into code by build.rs
+pub fn scenes() -> Scenes {
+    let patterns = PATTERNS.get();
+
+    #[allow(unused_mut)]
+    let mut scenes: Scenes = include!(concat!(env!("OUT_DIR"), "/scenes.rs"));
+
+    // spectrum analyzer, only with a microphone, before the leds off scene
+    #[cfg(feature = "mic")]
+    scenes
+        .insert(
+            scenes.len() - 1,
+            scene![layer! {
+                effect: Pattern::Spectrum,
+                pattern_shaders: shaders![FragmentShader::Rainbow2D(0.2)],
+            }],
+        )
+        .ok();
+
+    scenes
 }
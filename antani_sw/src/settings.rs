@@ -0,0 +1,201 @@
+// the settings menu, so the badge can be set up without a computer: five presses open
+// it, a long press moves to the next setting and the short presses change its value,
+// after the last setting the menu closes and the settings that changed are saved
+//
+// each setting is shown by its icon for a moment, then by its value: green or a red
+// cross for the switches, a bar in the color of the setting for the others
+
+use heapless::Vec;
+
+use crate::records::clamp_max_brightness;
+use crate::rgbeffects::{ColorPalette, LedPattern, Pattern, RenderCommand, RenderManager};
+use crate::scenes::{self, Playback};
+use crate::time::Time;
+use crate::{LedMatrix, TaskCommand};
+
+// seconds the icon is shown when moving to a setting
+const ICON_DURATION: f32 = 1.0;
+
+const PLAYBACKS: [Playback; 4] = [
+    Playback::Sequential,
+    Playback::Shuffle { interval: 0 },
+    Playback::Shuffle { interval: 60 },
+    Playback::Shuffle { interval: 300 },
+];
+const MAX_BRIGHTNESSES: [u8; 4] = [25, 50, 75, 100];
+
+const ON: (u8, u8, u8) = (0, 255, 0);
+const OFF: (u8, u8, u8) = (255, 0, 0);
+
+/// the settings of the menu, the ones of the config that make sense without a computer
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub random_boot_scene: bool,
+    pub playback: Playback,
+    pub max_brightness: u8,
+    pub demo_mode: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    RandomBootScene,
+    Playback,
+    MaxBrightness,
+    DemoMode,
+}
+
+impl Setting {
+    fn next(self) -> Option<Self> {
+        match self {
+            Setting::RandomBootScene => Some(Setting::Playback),
+            Setting::Playback => Some(Setting::MaxBrightness),
+            Setting::MaxBrightness => Some(Setting::DemoMode),
+            Setting::DemoMode => None,
+        }
+    }
+
+    fn icon(self) -> LedPattern {
+        match self {
+            // a die
+            Setting::RandomBootScene => 0b100010001,
+            // play
+            Setting::Playback => 0b010001010,
+            // a sun
+            Setting::MaxBrightness => 0b010111010,
+            // a frame on a stand
+            Setting::DemoMode => 0b111101111,
+        }
+    }
+
+    fn color(self) -> (u8, u8, u8) {
+        match self {
+            Setting::RandomBootScene => (255, 160, 0),
+            Setting::Playback => (0, 200, 255),
+            Setting::MaxBrightness => (255, 255, 255),
+            Setting::DemoMode => (200, 0, 255),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Menu {
+    setting: Setting,
+    // the icon is shown until then
+    icon_until: Time,
+    saved: Settings,
+    settings: Settings,
+}
+
+impl Menu {
+    pub fn new(settings: Settings, t: Time) -> Self {
+        Self {
+            setting: Setting::RandomBootScene,
+            icon_until: t + ICON_DURATION,
+            saved: settings.clone(),
+            settings,
+        }
+    }
+
+    /// the next value of the setting
+    pub fn press(&mut self, t: Time) {
+        let settings = &mut self.settings;
+        match self.setting {
+            Setting::RandomBootScene => settings.random_boot_scene = !settings.random_boot_scene,
+            Setting::Playback => settings.playback = next_choice(&PLAYBACKS, settings.playback),
+            Setting::MaxBrightness => {
+                settings.max_brightness = next_choice(&MAX_BRIGHTNESSES, settings.max_brightness)
+            }
+            Setting::DemoMode => settings.demo_mode = !settings.demo_mode,
+        }
+        // the value right away
+        self.icon_until = t;
+    }
+
+    /// moves to the next setting, false after the last one when the menu closes
+    pub fn long_press(&mut self, t: Time) -> bool {
+        match self.setting.next() {
+            Some(setting) => {
+                self.setting = setting;
+                self.icon_until = t + ICON_DURATION;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// the commands applying and saving the settings that changed
+    pub fn changes(&self) -> Vec<TaskCommand, 4> {
+        let (saved, settings) = (&self.saved, &self.settings);
+        let mut commands = Vec::new();
+
+        if settings.random_boot_scene != saved.random_boot_scene {
+            let _ = commands.push(TaskCommand::SetRandomBootScene(settings.random_boot_scene));
+        }
+        if settings.playback != saved.playback {
+            let _ = commands.push(TaskCommand::SetPlayback(settings.playback));
+        }
+        if settings.max_brightness != saved.max_brightness {
+            // the choices go up to 100, past the limit of some boards
+            let percent = clamp_max_brightness(settings.max_brightness);
+            let _ = commands.push(TaskCommand::SetMaxBrightness(percent));
+        }
+        // last, it leaves the menu
+        if settings.demo_mode != saved.demo_mode {
+            let _ = commands.push(TaskCommand::SetDemoMode(settings.demo_mode));
+        }
+        commands
+    }
+
+    pub fn render(&self, renderman: &mut RenderManager<LedMatrix>, t: Time) {
+        let settings = &self.settings;
+        let (pattern, color) = if t < self.icon_until {
+            (self.setting.icon(), self.setting.color())
+        } else {
+            match self.setting {
+                Setting::RandomBootScene => switch(settings.random_boot_scene),
+                Setting::DemoMode => switch(settings.demo_mode),
+                Setting::Playback => (bar(&PLAYBACKS, settings.playback), self.setting.color()),
+                Setting::MaxBrightness => (
+                    bar(&MAX_BRIGHTNESSES, settings.max_brightness),
+                    self.setting.color(),
+                ),
+            }
+        };
+
+        renderman.render(
+            &[RenderCommand {
+                effect: Pattern::Simple(pattern),
+                color: ColorPalette::Solid(color.into()),
+                ..Default::default()
+            }],
+            t,
+        );
+    }
+}
+
+/// the choice after value, the first one for a value set over usb that isn't a choice
+fn next_choice<T: Copy + PartialEq>(choices: &[T], value: T) -> T {
+    match choices.iter().position(|choice| *choice == value) {
+        Some(i) => choices[(i + 1) % choices.len()],
+        None => choices[0],
+    }
+}
+
+fn switch(on: bool) -> (LedPattern, (u8, u8, u8)) {
+    let patterns = scenes::PATTERNS.get();
+    if on {
+        (patterns.all_on, ON)
+    } else {
+        (patterns.cross, OFF)
+    }
+}
+
+/// more leds for the later choices, all of them for the last one, none for a value set
+/// over usb that isn't a choice
+fn bar<T: PartialEq>(choices: &[T], value: T) -> LedPattern {
+    let leds = match choices.iter().position(|choice| *choice == value) {
+        Some(i) => (i as u32 + 1) * 9 / choices.len() as u32,
+        None => 0,
+    };
+    (1 << leds) - 1
+}
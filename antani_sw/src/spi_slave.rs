@@ -0,0 +1,124 @@
+// the rp2040 spi peripheral can work as a slave but embassy-rp only supports the master
+// mode, so the frames are received by a pio state machine on PIO1
+
+use embassy_rp::bind_interrupts;
+use embassy_rp::peripherals::PIO1;
+use embassy_rp::pio::{
+    Common, Config, Direction, FifoJoin, InterruptHandler, PioPin, ShiftConfig, ShiftDirection,
+    StateMachine,
+};
+use embassy_time::{with_timeout, Duration};
+
+use crate::{LedPixel, RawFramebuffer, HOST_FRAME_SIGNAL, LED_MATRIX_SIZE};
+
+bind_interrupts!(pub struct Irqs {
+    PIO1_IRQ_0 => InterruptHandler<PIO1>;
+});
+
+const FRAME_SIZE: usize = LED_MATRIX_SIZE * 3;
+// a pause longer than this between two bytes starts a new frame
+const FRAME_GAP: Duration = Duration::from_millis(1);
+
+/// spi mode 0 receiver, MOSI, SCK and CS must be consecutive pins
+pub struct SpiSlave<'d> {
+    sm: StateMachine<'d, PIO1, 0>,
+    origin: u8,
+}
+
+impl<'d> SpiSlave<'d> {
+    pub fn new(
+        pio: &mut Common<'d, PIO1>,
+        mut sm: StateMachine<'d, PIO1, 0>,
+        mosi: impl PioPin,
+        sck: impl PioPin,
+        cs: impl PioPin,
+    ) -> Self {
+        // the bits are sampled on the rising edge of the clock while CS is low,
+        // the state machine just spins while CS is high
+        let prg = pio_proc::pio_asm!(
+            ".wrap_target",
+            "idle:",
+            "    jmp pin idle",
+            "    wait 1 pin 1",
+            "    in pins, 1",
+            "    wait 0 pin 1",
+            ".wrap",
+        );
+
+        let mosi = pio.make_pio_pin(mosi);
+        let sck = pio.make_pio_pin(sck);
+        let cs = pio.make_pio_pin(cs);
+
+        let program = pio.load_program(&prg.program);
+        let origin = program.origin;
+
+        let mut cfg = Config::default();
+        cfg.use_program(&program, &[]);
+        cfg.set_in_pins(&[&mosi, &sck, &cs]);
+        cfg.set_jmp_pin(&cs);
+
+        // one byte at a time in the fifo, most significant bit first
+        cfg.fifo_join = FifoJoin::RxOnly;
+        cfg.shift_in = ShiftConfig {
+            auto_fill: true,
+            threshold: 8,
+            direction: ShiftDirection::Left,
+        };
+
+        sm.set_config(&cfg);
+        sm.set_pin_dirs(Direction::In, &[&mosi, &sck, &cs]);
+        sm.set_enable(true);
+
+        Self { sm, origin }
+    }
+
+    /// throws away a partial frame, the state machine may be in the middle of a byte
+    fn resync(&mut self) {
+        self.sm.set_enable(false);
+        self.sm.clear_fifos();
+        self.sm.restart();
+        // back to waiting for CS
+        unsafe { self.sm.exec_jmp(self.origin) };
+        self.sm.set_enable(true);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn spi_slave_tsk(mut spi: SpiSlave<'static>) {
+    let mut frame = [0u8; FRAME_SIZE];
+
+    loop {
+        // the first byte can take forever, the others must follow it closely
+        frame[0] = spi.sm.rx().wait_pull().await as u8;
+
+        let mut complete = true;
+        for byte in frame.iter_mut().skip(1) {
+            match with_timeout(FRAME_GAP, spi.sm.rx().wait_pull()).await {
+                Ok(word) => *byte = word as u8,
+                Err(_) => {
+                    complete = false;
+                    break;
+                }
+            }
+        }
+
+        if !complete {
+            log::warn!("Incomplete SPI frame");
+            spi.resync();
+            continue;
+        }
+
+        let mut fb = RawFramebuffer::new();
+        for (led, rgb) in fb.framebuffer.iter_mut().zip(frame.chunks_exact(3)) {
+            *led = LedPixel {
+                r: rgb[0],
+                g: rgb[1],
+                b: rgb[2],
+                w: 0,
+            };
+        }
+
+        // same path as the frames streamed over usb
+        HOST_FRAME_SIGNAL.signal(fb);
+    }
+}
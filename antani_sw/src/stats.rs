@@ -0,0 +1,72 @@
+use embassy_time::{Duration, Instant};
+
+/// how often the frame times are logged on the debug interface
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// render time, time spent waiting for the leds and loop jitter, in microseconds
+#[derive(Default)]
+pub struct FrameStats {
+    frames: u32,
+    render_total: u64,
+    render_max: u64,
+    wait_total: u64,
+    wait_max: u64,
+    jitter_max: u64,
+    last_frame: Option<Instant>,
+    last_report: Option<Instant>,
+}
+
+impl FrameStats {
+    /// called when a frame starts, period is the time the frame should take
+    pub fn frame_start(&mut self, period: Duration) -> Instant {
+        let now = Instant::now();
+
+        if let Some(last) = self.last_frame {
+            let interval = (now - last).as_micros();
+            self.jitter_max = self.jitter_max.max(interval.abs_diff(period.as_micros()));
+        }
+        self.last_frame = Some(now);
+
+        now
+    }
+
+    /// called when a frame has been rendered and handed to the leds
+    pub fn frame_end(&mut self, start: Instant, send_start: Instant) {
+        let render = (send_start - start).as_micros();
+        let wait = send_start.elapsed().as_micros();
+
+        self.frames += 1;
+        self.render_total += render;
+        self.render_max = self.render_max.max(render);
+        self.wait_total += wait;
+        self.wait_max = self.wait_max.max(wait);
+
+        let last_report = *self.last_report.get_or_insert(start);
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            self.log();
+            *self = Self {
+                last_frame: self.last_frame,
+                last_report: Some(Instant::now()),
+                ..Default::default()
+            };
+        }
+    }
+
+    pub fn log(&self) {
+        let Some(since) = self.last_report else {
+            return;
+        };
+        let frames = self.frames.max(1) as u64;
+        let elapsed = since.elapsed().as_millis().max(1);
+
+        log::info!(
+            "stats fps {} render {}/{}us leds {}/{}us jitter {}us",
+            self.frames as u64 * 1000 / elapsed,
+            self.render_total / frames,
+            self.render_max,
+            self.wait_total / frames,
+            self.wait_max,
+            self.jitter_max,
+        );
+    }
+}
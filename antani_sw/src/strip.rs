@@ -0,0 +1,95 @@
+// an external ws2812 strip on the breakout pad, built with --features strip: it shows
+// the frames of the matrix, repeated along it or stretched into a gradient
+//
+// the length and the mapping are saved in the config, the badges without a strip have
+// a length of 0
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::framebuffer::{LedPixel, LED_MATRIX_HEIGHT, LED_MATRIX_SIZE, LED_MATRIX_WIDTH};
+
+/// the longest strip, one meter of the densest ones, the leds take 30 us each to send
+pub const MAX_LEDS: usize = 144;
+
+pub type Frame = [LedPixel; MAX_LEDS];
+
+/// how the frame of the matrix is laid on the strip
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mapping {
+    /// the leds of the matrix row by row from the top left, over and over
+    #[default]
+    Mirror,
+    /// the columns of the matrix from left to right over the whole strip, with the colors
+    /// in between, so the rainbows and the gradients go on along it
+    Gradient,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Strip {
+    /// leds of the strip, at most MAX_LEDS, 0 = no strip
+    pub length: u16,
+    pub mapping: Mapping,
+}
+
+impl Strip {
+    pub const fn new() -> Self {
+        Self {
+            length: 0,
+            mapping: Mapping::Mirror,
+        }
+    }
+
+    pub fn leds(&self) -> usize {
+        (self.length as usize).min(MAX_LEDS)
+    }
+
+    /// the frame of the strip for a frame of the leds, from the matrix of this badge at
+    /// its start, the leds after the length are off
+    pub fn render(&self, matrix: &[LedPixel]) -> Frame {
+        let mut frame = [LedPixel::default(); MAX_LEDS];
+        let leds = &mut frame[..self.leds()];
+
+        match self.mapping {
+            Mapping::Mirror => {
+                for (led, pixel) in leds
+                    .iter_mut()
+                    .zip(matrix[..LED_MATRIX_SIZE].iter().cycle())
+                {
+                    *led = *pixel;
+                }
+            }
+            Mapping::Gradient => {
+                let columns: [LedPixel; LED_MATRIX_WIDTH] = core::array::from_fn(|x| {
+                    let average = |channel: fn(&LedPixel) -> u8| {
+                        let sum: u32 = (0..LED_MATRIX_HEIGHT)
+                            .map(|y| channel(&matrix[y * LED_MATRIX_WIDTH + x]) as u32)
+                            .sum();
+                        (sum / LED_MATRIX_HEIGHT as u32) as u8
+                    };
+                    (average(|p| p.r), average(|p| p.g), average(|p| p.b)).into()
+                });
+
+                let last = leds.len().saturating_sub(1).max(1) as f32;
+                for (i, led) in leds.iter_mut().enumerate() {
+                    *led = color::ramp(&columns, i as f32 / last);
+                }
+            }
+        }
+        frame
+    }
+}
+
+static STRIP: Mutex<CriticalSectionRawMutex, Cell<Strip>> = Mutex::new(Cell::new(Strip::new()));
+
+pub fn set(strip: Strip) {
+    STRIP.lock(|s| s.set(strip));
+}
+
+pub fn get() -> Strip {
+    STRIP.lock(Cell::get)
+}
@@ -0,0 +1,149 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_rp::adc;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Ticker;
+
+use crate::{audio, MegaPublisher, TaskCommand};
+
+// the thermal throttle skips the channel so it's never late behind a burst of
+// input events, 1.0 = no throttle, 0.0 = full throttle
+pub static THERMAL_THROTTLE: Signal<CriticalSectionRawMutex, f32> = Signal::new();
+
+// the leds are throttled from full power at THROTTLE_FROM to 0.1 at THROTTLE_TO
+const THROTTLE_FROM: f32 = 55.0;
+const THROTTLE_TO: f32 = 65.0;
+// below this the badge is cool, for the effects that show the temperature
+const COOL: f32 = 30.0;
+
+// how close to throttling, from 0.0 to 1.0, stored as f32 bits,
+// written by the adc task and read by the render task on the other core
+static HEAT: AtomicU32 = AtomicU32::new(0);
+
+/// 0.0 when the badge is cool, 1.0 when the leds are throttled or the sensor fails
+pub fn heat() -> f32 {
+    f32::from_bits(HEAT.load(Ordering::Relaxed))
+}
+
+// a failed reading is tried again a few times before waiting for the next one
+const READ_RETRIES: u32 = 3;
+// without a temperature for this many readings the badge could be overheating
+// without knowing it, so the leds are capped until the sensor works again
+const FAILED_READINGS_BEFORE_DEGRADED: u32 = 3;
+const DEGRADED_GAIN: f32 = 0.5;
+
+/// the temperature of the chip in degrees, None if the adc keeps failing or reads
+/// something the sensor can't read, errors counts every failed read
+async fn read_temperature(
+    adc: &mut adc::Adc<'static, adc::Async>,
+    ts: &mut adc::Channel<'static>,
+    errors: &mut u32,
+) -> Option<f32> {
+    for _ in 0..READ_RETRIES {
+        match adc.read(ts).await {
+            Ok(v) => {
+                // TODO: yeah let's waste precious CPU cycles to calculate the temperature before checking if we need to throttle
+                let adc_voltage = (3.3 / 4096.0) * v as f32;
+                let degrees = 27.0 - (adc_voltage - 0.706) / 0.001721;
+
+                // a disconnected reference reads 0 or 4095, which is hundreds of degrees
+                if (-40.0..=125.0).contains(&degrees) {
+                    return Some(degrees);
+                }
+                *errors += 1;
+                log::error!(
+                    "Temperature out of range: {}, {} adc errors",
+                    degrees,
+                    errors
+                );
+            }
+            Err(e) => {
+                *errors += 1;
+                log::error!("Error reading temperature: {:?}, {} adc errors", e, errors);
+            }
+        }
+    }
+    None
+}
+
+#[embassy_executor::task]
+pub async fn temperature(
+    mut adc: adc::Adc<'static, adc::Async>,
+    mut ts: adc::Channel<'static>,
+    mut mic: Option<adc::Channel<'static>>,
+    publisher: MegaPublisher,
+) {
+    // with a microphone the adc is mostly busy sampling it,
+    // the temperature is still read once per second
+    #[cfg(feature = "mic")]
+    let (mut ticker, samples_per_reading) =
+        (Ticker::every(audio::SAMPLE_PERIOD), audio::SAMPLE_RATE);
+    #[cfg(not(feature = "mic"))]
+    let (mut ticker, samples_per_reading) =
+        (Ticker::every(embassy_time::Duration::from_secs(1)), 1);
+
+    let mut envelope = audio::EnvelopeFollower::new();
+    let mut beat_detector = audio::BeatDetector::default();
+    let mut spectrum = audio::SpectrumAnalyzer::default();
+    let mut samples = 0;
+    let mut errors = 0;
+    let mut failed_readings = 0;
+
+    loop {
+        ticker.next().await;
+
+        if let Some(mic) = &mut mic {
+            match adc.read(mic).await {
+                Ok(v) => {
+                    let sample = envelope.sample(v);
+                    spectrum.sample(sample);
+
+                    if beat_detector.sample(sample) {
+                        // never wait here, a late beat is worse than a missed one
+                        publisher.publish_immediate(TaskCommand::Beat);
+                    }
+                }
+                Err(e) => {
+                    // the sample is skipped, logged less and less often since there are
+                    // thousands every second
+                    errors += 1;
+                    if errors.is_power_of_two() {
+                        log::error!("Error reading microphone: {:?}, {} adc errors", e, errors);
+                    }
+                }
+            }
+        }
+
+        samples += 1;
+        if samples < samples_per_reading {
+            continue;
+        }
+        samples = 0;
+
+        let Some(temp_degrees_c) = read_temperature(&mut adc, &mut ts, &mut errors).await else {
+            failed_readings += 1;
+            if failed_readings == FAILED_READINGS_BEFORE_DEGRADED {
+                log::warn!("No temperature, leds capped at {}", DEGRADED_GAIN);
+            }
+            if failed_readings >= FAILED_READINGS_BEFORE_DEGRADED {
+                THERMAL_THROTTLE.signal(DEGRADED_GAIN);
+                HEAT.store(1.0f32.to_bits(), Ordering::Relaxed);
+            }
+            continue;
+        };
+
+        if failed_readings >= FAILED_READINGS_BEFORE_DEGRADED {
+            log::info!("Temperature is back: {}", temp_degrees_c);
+        }
+        failed_readings = 0;
+
+        let heat = (temp_degrees_c - COOL) / (THROTTLE_FROM - COOL);
+        HEAT.store(heat.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+
+        // lerp from 55 to 65 degrees maps to gain from 1.0 to 0.1,
+        // sent every time so the leds go back to full power when the badge cools down
+        let gain: f32 = 1.0 - (temp_degrees_c - THROTTLE_FROM) / (THROTTLE_TO - THROTTLE_FROM);
+        THERMAL_THROTTLE.signal(gain.clamp(0.0, 1.0));
+    }
+}
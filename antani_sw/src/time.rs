@@ -0,0 +1,147 @@
+// the time seen by the effects, in microseconds since boot (or since the last reset)
+//
+// an f32 of seconds loses its fractions after a few hours of uptime, so the effects
+// don't use the seconds directly: they ask for the phase or the step count at their
+// speed, computed in fixed point and wrapping, so they look the same after days
+
+use core::ops::Add;
+
+const MICROS_PER_SEC: i64 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time {
+    micros: i64,
+}
+
+impl Time {
+    pub const fn from_micros(micros: i64) -> Self {
+        Self { micros }
+    }
+
+    pub fn from_secs(seconds: f32) -> Self {
+        Self::from_micros((seconds * MICROS_PER_SEC as f32) as i64)
+    }
+
+    /// the time after some frames at fps frames per second
+    pub fn after_frames(self, frames: u32, fps: u8) -> Self {
+        Self::from_micros(self.micros + frames as i64 * MICROS_PER_SEC / fps as i64)
+    }
+
+    /// turns done at speed turns per second, 16.16 fixed point, wrapping
+    fn turns(self, speed: f32) -> i64 {
+        let speed = (speed * 65536.0) as i64;
+        let seconds = self.micros.div_euclid(MICROS_PER_SEC);
+        let micros = self.micros.rem_euclid(MICROS_PER_SEC);
+
+        // only the low bits of the whole seconds matter, so the product can wrap
+        seconds
+            .wrapping_mul(speed)
+            .wrapping_add(micros * speed / MICROS_PER_SEC)
+    }
+
+    /// how far into the current cycle, from 0.0 to 1.0, at speed cycles per second
+    pub fn phase(self, speed: f32) -> f32 {
+        (self.turns(speed) & 0xFFFF) as f32 / 65536.0
+    }
+
+    /// how many whole cycles were done at speed cycles per second, wraps at u32::MAX
+    pub fn steps(self, speed: f32) -> u32 {
+        (self.turns(speed) >> 16) as u32
+    }
+
+    pub const fn as_micros(self) -> i64 {
+        self.micros
+    }
+
+    /// the same instant measured from origin, so the phases and the steps start over there
+    pub fn relative_to(self, origin: Time) -> Self {
+        Self::from_micros(self.micros - origin.micros)
+    }
+
+    /// seconds from earlier to self, only for short intervals
+    pub fn since(self, earlier: Time) -> f32 {
+        (self.micros - earlier.micros) as f32 / MICROS_PER_SEC as f32
+    }
+}
+
+/// a time some seconds later, or earlier if they are negative
+impl Add<f32> for Time {
+    type Output = Time;
+
+    fn add(self, seconds: f32) -> Time {
+        Time::from_micros(self.micros + Time::from_secs(seconds).micros)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // speeds that are exact in 16.16 fixed point, all of them do whole cycles in 4 s
+    const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 1.5, 2.0];
+    // days of uptime, a multiple of 4 s
+    const DAYS: i64 = 5 * 24 * 3600 * MICROS_PER_SEC;
+
+    #[test]
+    fn days_of_uptime_look_like_the_boot() {
+        for speed in SPEEDS {
+            for micros in [0, 1, 123_456, 999_999, 1_000_000, 2_718_281, 3_999_999] {
+                let boot = Time::from_micros(micros);
+                let later = Time::from_micros(DAYS + micros);
+                let cycles = (DAYS / MICROS_PER_SEC) as f32 * speed;
+
+                assert_eq!(later.phase(speed), boot.phase(speed), "{speed} {micros}");
+                assert_eq!(
+                    later.steps(speed),
+                    boot.steps(speed).wrapping_add(cycles as u32),
+                    "{speed} {micros}"
+                );
+            }
+        }
+
+        // the phase still moves by a single microsecond after days
+        let later = Time::from_micros(DAYS);
+        assert!(Time::from_micros(DAYS + 100).phase(1.0) > later.phase(1.0));
+    }
+
+    #[test]
+    fn phases_and_steps_at_known_times() {
+        let time = Time::from_secs(2.5);
+        assert_eq!(time.phase(1.0), 0.5);
+        assert_eq!(time.steps(1.0), 2);
+        assert_eq!(time.phase(0.5), 0.25);
+        assert_eq!(time.steps(0.5), 1);
+        assert_eq!(time.phase(2.0), 0.0);
+        assert_eq!(time.steps(2.0), 5);
+    }
+
+    #[test]
+    fn negative_times_count_back_from_zero() {
+        // a quarter of a cycle before zero is three quarters into the last cycle
+        let before = Time::from_micros(-250_000);
+        assert_eq!(before.phase(1.0), 0.75);
+        assert_eq!(before.steps(1.0), u32::MAX);
+
+        // and they are whole cycles away from the positive times, like the others
+        for speed in SPEEDS {
+            for micros in [-1, -123_456, -1_000_000, -3_999_999, -DAYS] {
+                let time = Time::from_micros(micros);
+                let after = Time::from_micros(micros + DAYS);
+                let cycles = (DAYS / MICROS_PER_SEC) as f32 * speed;
+
+                assert_eq!(after.phase(speed), time.phase(speed), "{speed} {micros}");
+                assert_eq!(
+                    after.steps(speed),
+                    time.steps(speed).wrapping_add(cycles as u32),
+                    "{speed} {micros}"
+                );
+            }
+        }
+
+        // the times before an origin, and the ones some seconds back
+        let origin = Time::from_secs(10.0);
+        assert_eq!(Time::from_secs(9.75).relative_to(origin), before);
+        assert_eq!(origin + -10.25, before);
+        assert_eq!(origin.since(Time::from_secs(10.5)), -0.5);
+    }
+}
@@ -0,0 +1,166 @@
+// keeps the time of a follower in step with the beacons of the party leader, see party.rs
+//
+// every beacon gives the offset from our time to the time of the leader, late by a
+// varying part of a frame: the estimate moves by a fraction of the error of every
+// beacon so the jitter averages out, and it's kept as it is while beacons are missed
+
+use crate::time::Time;
+
+/// a beacon this far from the estimate is a jump of the time of the leader, followed
+/// right away
+const MAX_ERROR_MICROS: i64 = 250_000;
+/// the estimate moves by 1 / SMOOTHING of the error of every beacon
+const SMOOTHING: i64 = 4;
+
+#[derive(Default)]
+pub struct TimeSync {
+    /// micros from our time to the time of the leader, None before the first beacon
+    offset: Option<i64>,
+}
+
+impl TimeSync {
+    pub fn reset(&mut self) {
+        self.offset = None;
+    }
+
+    /// the leader was at leader when we were at local, both wrapping at wrap
+    pub fn beacon(&mut self, leader: Time, local: Time, wrap: Time) {
+        let wrap = wrap.as_micros();
+        let sample = wrapped(leader.as_micros() - local.as_micros(), wrap);
+
+        self.offset = Some(match self.offset {
+            Some(offset) => {
+                let error = wrapped(sample - offset, wrap);
+
+                if error.abs() > MAX_ERROR_MICROS {
+                    log::info!("Party time jumped by {} ms", error / 1000);
+                    sample
+                } else {
+                    offset + error / SMOOTHING
+                }
+            }
+            None => sample,
+        });
+    }
+
+    /// our time in the time of the leader, it wraps like the one of the leader
+    pub fn time(&self, local: Time, wrap: Time) -> Time {
+        let local = local.as_micros() + self.offset.unwrap_or(0);
+
+        Time::from_micros(local.rem_euclid(wrap.as_micros()))
+    }
+}
+
+/// micros into -wrap / 2..wrap / 2, the shortest way around
+fn wrapped(micros: i64, wrap: i64) -> i64 {
+    (micros + wrap / 2).rem_euclid(wrap) - wrap / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRAP: Time = Time::from_micros(10_000_000);
+
+    fn ms(millis: i64) -> Time {
+        Time::from_micros(millis * 1000)
+    }
+
+    // the leader 2 s ahead of us, after the first beacon
+    fn synced() -> TimeSync {
+        let mut sync = TimeSync::default();
+        sync.beacon(ms(3000), ms(1000), WRAP);
+        sync
+    }
+
+    #[test]
+    fn the_first_beacon_is_taken_as_it_is() {
+        let mut sync = TimeSync::default();
+        assert_eq!(sync.time(ms(1000), WRAP), ms(1000));
+
+        sync.beacon(ms(3000), ms(1000), WRAP);
+        assert_eq!(sync.time(ms(1500), WRAP), ms(3500));
+
+        sync.reset();
+        assert_eq!(sync.time(ms(1500), WRAP), ms(1500));
+    }
+
+    #[test]
+    fn beacons_are_smoothed() {
+        let mut sync = synced();
+
+        // a beacon 40 ms late moves the estimate by a quarter of it
+        sync.beacon(ms(4000), ms(2040), WRAP);
+        assert_eq!(sync.time(ms(3000), WRAP), ms(4990));
+
+        // the jitter of the beacons around the time of the leader averages out
+        let mut sync = synced();
+        for beacon in 0..40 {
+            let late = if beacon % 2 == 0 { 20 } else { -20 };
+            sync.beacon(
+                ms(3000 + beacon * 100),
+                ms(1000 + beacon * 100 + late),
+                WRAP,
+            );
+        }
+        let error = sync.time(ms(5000), WRAP).as_micros() - ms(7000).as_micros();
+        assert!(error.abs() <= 10_000, "{error}");
+
+        // and the estimate gets to a leader that's steadily late
+        let mut sync = synced();
+        for beacon in 0..40 {
+            sync.beacon(ms(3000 + beacon * 100), ms(1030 + beacon * 100), WRAP);
+        }
+        let error = sync.time(ms(5000), WRAP).as_micros() - ms(6970).as_micros();
+        assert!(error.abs() <= 1000, "{error}");
+    }
+
+    #[test]
+    fn missed_beacons_keep_the_offset() {
+        let mut sync = synced();
+        sync.beacon(ms(4000), ms(2040), WRAP);
+
+        // no beacon for a long while, the offset is the same and the time goes on
+        for local in [3000, 9000, 60_000, 3_600_000] {
+            assert_eq!(
+                sync.time(ms(local), WRAP),
+                ms((local + 1990).rem_euclid(10_000))
+            );
+        }
+    }
+
+    #[test]
+    fn jumps_of_the_leader_are_followed() {
+        let mut sync = synced();
+
+        // just inside MAX_ERROR_MICROS it's still smoothed
+        sync.beacon(ms(4000), ms(2000 - 248), WRAP);
+        assert_eq!(sync.time(ms(3000), WRAP), ms(5062));
+
+        // past it the new offset is taken right away, either way
+        let mut sync = synced();
+        sync.beacon(ms(4300), ms(2000), WRAP);
+        assert_eq!(sync.time(ms(3000), WRAP), ms(5300));
+        sync.beacon(ms(5000), ms(3000), WRAP);
+        assert_eq!(sync.time(ms(3000), WRAP), ms(5000));
+    }
+
+    #[test]
+    fn estimates_go_around_the_wrap() {
+        // the leader just before the wrap and then just after it
+        let mut sync = TimeSync::default();
+        sync.beacon(ms(9990), ms(1000), WRAP);
+        sync.beacon(ms(30), ms(1000 + 40 + 40), WRAP);
+
+        // the beacon after the wrap is 40 ms late, not a jump of almost a whole wrap
+        assert_eq!(sync.time(ms(1000), WRAP), ms(9980));
+        assert_eq!(sync.time(ms(1030), WRAP), ms(10));
+
+        // a leader that wrapped while we didn't, and the other way around
+        let mut sync = TimeSync::default();
+        sync.beacon(ms(500), ms(9500), WRAP);
+        assert_eq!(sync.time(ms(9600), WRAP), ms(600));
+        sync.beacon(ms(9500), ms(500), WRAP);
+        assert_eq!(sync.time(ms(600), WRAP), ms(9600));
+    }
+}
@@ -0,0 +1,86 @@
+use embassy_rp::bind_interrupts;
+use embassy_rp::peripherals::UART0;
+use embassy_rp::uart::{BufferedInterruptHandler, BufferedUartRx};
+use embedded_io_async::Read;
+use heapless::Vec;
+
+use crate::usb::AlignedVec;
+use crate::{MegaPublisher, TaskCommand};
+
+// the same capnp messages of the usb control interface, every message is
+// cobs encoded and followed by a 0, so a lost byte only breaks one message
+// RX on GPIO1, 115200 baud
+
+bind_interrupts!(pub struct Irqs {
+    UART0_IRQ => BufferedInterruptHandler<UART0>;
+});
+
+/// decodes a cobs frame, without the trailing 0
+fn cobs_decode(frame: &[u8], out: &mut Vec<u8, 256>) -> Option<()> {
+    let mut i = 0;
+
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        if code == 0 {
+            return None;
+        }
+
+        out.extend_from_slice(frame.get(i + 1..i + code)?).ok()?;
+        i += code;
+
+        // a full block isn't followed by a 0
+        if code < 0xFF && i < frame.len() {
+            out.push(0).ok()?;
+        }
+    }
+
+    Some(())
+}
+
+#[embassy_executor::task]
+pub async fn uart_tsk(mut rx: BufferedUartRx<'static, UART0>, publisher: MegaPublisher) {
+    let mut buf = [0; 64];
+    let mut frame = Vec::<u8, 256>::new();
+    // the frame didn't fit, throw it away until the next 0
+    let mut overflow = false;
+
+    loop {
+        let n = match rx.read(&mut buf).await {
+            Ok(n) => n,
+            Err(e) => {
+                log::error!("UART error: {:?}", e);
+                continue;
+            }
+        };
+
+        for byte in &buf[..n] {
+            if *byte != 0 {
+                overflow |= frame.push(*byte).is_err();
+                continue;
+            }
+
+            let mut message = AlignedVec::new();
+            let decoded = !overflow && cobs_decode(&frame, &mut message.x).is_some();
+            frame.clear();
+            overflow = false;
+
+            if !decoded {
+                log::error!("Bad UART frame");
+                publisher.publish(TaskCommand::Error).await;
+                continue;
+            }
+
+            match crate::capnp::deserialize_message(&mut message.x.as_slice()) {
+                Ok(TaskCommand::StreamFrame(fb)) => crate::HOST_FRAME_SIGNAL.signal(fb),
+                Ok(command) => {
+                    publisher.publish(command).await;
+                    publisher.publish(TaskCommand::UsbActivity).await;
+                }
+                Err(e) => {
+                    log::error!("Error deserializing UART message: {:?}", e);
+                    publisher.publish(TaskCommand::Error).await;
+                }
+            }
+        }
+    }
+}
@@ -7,7 +7,7 @@ use embassy_time::{Duration, Timer};
 use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
 use embassy_usb::class::hid::{self, HidWriter};
 use heapless::Vec;
-use log::{error, info};
+use log::{debug, error, info};
 use static_cell::StaticCell;
 use usbd_hid::descriptor::{KeyboardReport, SerializedDescriptor};
 
@@ -38,7 +38,7 @@ pub async fn usb_main(usb: USB, publisher: MegaPublisher, mut subscriber: MegaSu
     let mut config = Config::new(0x0000, 0x0000);
     config.manufacturer = Some("ESC");
     config.product = Some("Mini Badge");
-    config.serial_number = Some("12345678");
+    config.serial_number = Some(crate::identity::get().serial());
     config.max_power = 100;
     config.max_packet_size_0 = 64;
 
@@ -185,7 +185,25 @@ async fn midi_echo<'d, T: Instance + 'd>(
                 }
             };
 
-            let [_, _, button, value] = buf;
+            let [header, _, button, value] = *buf;
+
+            // the low nibble of the usb midi header is the kind of message
+            let value = match header & 0x0F {
+                // note on
+                0x9 => value,
+                // note off
+                0x8 => 0,
+                // control change, button is the controller number
+                0xB => {
+                    info!("midi control: {}, value: {}", button, value);
+
+                    publisher
+                        .publish(crate::TaskCommand::MidiControlChange(button, value))
+                        .await;
+                    continue;
+                }
+                _ => continue,
+            };
 
             info!("midi pixel: {}, value: {}", button, value);
 
@@ -218,13 +236,14 @@ async fn midi_echo<'d, T: Instance + 'd>(
     }
 }
 
-struct AlignedVec {
-    x: Vec<u8, 256>,
+// capnp wants the messages aligned to 8 bytes
+pub struct AlignedVec {
+    pub x: Vec<u8, 256>,
     _alignment: [u64; 0],
 }
 
 impl AlignedVec {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             x: Vec::<u8, 256>::new(),
             _alignment: [0; 0],
@@ -241,7 +260,7 @@ async fn usb_control<'d, T: Instance + 'd>(
     loop {
         let n = class.read_packet(&mut buf).await?;
         let data = &buf[..n];
-        info!("usb cdc data: {:?}", data);
+        debug!("usb cdc data: {:?}", data);
 
         // append to the mega deserialization buffer
         // we don't really care if it fails, we'll just clear it later
@@ -250,6 +269,12 @@ async fn usb_control<'d, T: Instance + 'd>(
         let e = crate::capnp::deserialize_message(&mut mega_deserialization_buf.x.as_slice());
 
         match e {
+            Ok(crate::TaskCommand::StreamFrame(frame)) => {
+                // frames come in fast, no logging and no activity led
+                mega_deserialization_buf.x.clear();
+
+                crate::HOST_FRAME_SIGNAL.signal(frame);
+            }
             Ok(command) => {
                 info!("Deserialized message");
 
@@ -0,0 +1,168 @@
+use embassy_rp::flash::ERASE_SIZE;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BadgeFlash, USER_SCENES_OFFSET};
+use crate::rgbeffects::{
+    ClockDigits, ColorPalette, FragmentShader, LedPattern, LoopMode, Pattern, RenderCommand,
+    MAX_SHADERS,
+};
+use crate::scenes::{Scene, Scenes, MAX_LAYERS, PATTERNS};
+
+// scenes saved in flash, so new content doesn't need a firmware update
+//
+// the sector starts with a small header (magic, postcard data length)
+// followed by the scenes serialized with postcard
+
+const USER_SCENES_MAGIC: u32 = 0x4D42534E; // "MBSN"
+const HEADER_SIZE: usize = 8;
+
+pub const MAX_USER_SCENES: usize = 8;
+
+/// animations can't be stored, the frames must live in flash,
+/// so user scenes refer to the built-in ones
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Animation {
+    EverythingOnce,
+    Dice,
+    BootAnimation,
+}
+
+impl Animation {
+    fn frames(self) -> &'static [LedPattern] {
+        let patterns = PATTERNS.get();
+
+        match self {
+            Animation::EverythingOnce => patterns.everything_once,
+            Animation::Dice => patterns.dice,
+            Animation::BootAnimation => patterns.boot_animation,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum StoredPattern {
+    Simple(LedPattern),
+    Animation(Animation, f32),        // animation, speed
+    AnimationReverse(Animation, f32), // animation, speed
+    AnimationRandom(Animation, u16),  // animation, decimation
+    BinaryClock(ClockDigits),
+    AnimationBeat(Animation),
+    Spectrum,
+    AnimationLoop(Animation, f32, LoopMode), // animation, speed, loop mode
+}
+
+impl Default for StoredPattern {
+    fn default() -> Self {
+        StoredPattern::Simple(0b111111111)
+    }
+}
+
+/// same as RenderCommand, in a form that can be serialized
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StoredLayer {
+    pub effect: StoredPattern,
+    pub color: ColorPalette,
+    pub pattern_shaders: Vec<FragmentShader, MAX_SHADERS>,
+    pub screen_shaders: Vec<FragmentShader, MAX_SHADERS>,
+    // f64 in flash, to keep the format of the scenes already saved
+    pub time_offset: f64,
+}
+
+pub type StoredScene = Vec<StoredLayer, MAX_LAYERS>;
+pub type StoredScenes = Vec<StoredScene, MAX_USER_SCENES>;
+
+impl From<&StoredLayer> for RenderCommand {
+    fn from(layer: &StoredLayer) -> Self {
+        let effect = match layer.effect {
+            StoredPattern::Simple(pattern) => Pattern::Simple(pattern),
+            StoredPattern::Animation(a, speed) => Pattern::Animation(a.frames(), speed),
+            StoredPattern::AnimationReverse(a, speed) => {
+                Pattern::AnimationReverse(a.frames(), speed)
+            }
+            StoredPattern::AnimationRandom(a, decimation) => {
+                Pattern::AnimationRandom(a.frames(), decimation)
+            }
+            StoredPattern::BinaryClock(ref digits) => Pattern::BinaryClock(digits.clone()),
+            StoredPattern::AnimationBeat(a) => Pattern::AnimationBeat(a.frames()),
+            StoredPattern::Spectrum => Pattern::Spectrum,
+            StoredPattern::AnimationLoop(a, speed, mode) => {
+                Pattern::AnimationLoop(a.frames(), speed, mode)
+            }
+        };
+
+        RenderCommand {
+            effect,
+            color: layer.color.clone(),
+            pattern_shaders: layer.pattern_shaders.clone(),
+            screen_shaders: layer.screen_shaders.clone(),
+            time_offset: layer.time_offset as f32,
+        }
+    }
+}
+
+/// reads the scenes saved in flash, an erased or invalid sector gives no scenes
+pub fn read(flash: &mut BadgeFlash) -> StoredScenes {
+    let mut data = [0; ERASE_SIZE];
+
+    if let Err(e) = flash.blocking_read(USER_SCENES_OFFSET, &mut data) {
+        log::error!("Error reading user scenes: {:?}", e);
+        return Vec::new();
+    }
+
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    if magic != USER_SCENES_MAGIC || len > ERASE_SIZE - HEADER_SIZE {
+        log::info!("No user scenes in flash");
+        return Vec::new();
+    }
+
+    match postcard::from_bytes(&data[HEADER_SIZE..HEADER_SIZE + len]) {
+        Ok(scenes) => scenes,
+        Err(e) => {
+            log::error!("Error decoding user scenes: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+pub fn store(flash: &mut BadgeFlash, scenes: &StoredScenes) {
+    let mut data = [0xFF; ERASE_SIZE];
+
+    let len = match postcard::to_slice(scenes, &mut data[HEADER_SIZE..]) {
+        Ok(encoded) => encoded.len(),
+        Err(e) => {
+            log::error!("Error encoding user scenes: {:?}", e);
+            return;
+        }
+    };
+    data[0..4].copy_from_slice(&USER_SCENES_MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+
+    if let Err(e) = flash.blocking_erase(USER_SCENES_OFFSET, USER_SCENES_OFFSET + ERASE_SIZE as u32)
+    {
+        log::error!("Error erasing user scenes: {:?}", e);
+        return;
+    }
+
+    if let Err(e) = flash.blocking_write(USER_SCENES_OFFSET, &data[..HEADER_SIZE + len]) {
+        log::error!("Error writing user scenes: {:?}", e);
+    }
+}
+
+/// appends the scenes saved in flash to the built-in ones
+pub fn load(flash: &mut BadgeFlash, scenes: &mut Scenes) {
+    let stored = read(flash);
+
+    for stored_scene in &stored {
+        let scene: Scene = stored_scene.iter().map(RenderCommand::from).collect();
+
+        if scenes.push(scene).is_err() {
+            log::warn!("Too many scenes, ignoring the rest of the user scenes");
+            break;
+        }
+    }
+
+    log::info!("Loaded {} user scenes", stored.len());
+}
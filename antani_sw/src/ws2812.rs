@@ -1,10 +1,16 @@
 // everything in this is copy pasted from embassy example
 
+use core::sync::atomic::{AtomicU8, Ordering};
+
 use embassy_rp::dma;
 use embassy_rp::pio::{
     Common, Config, FifoJoin, Instance, PioPin, ShiftConfig, ShiftDirection, StateMachine,
 };
 
+use embassy_futures::join::join;
+use embassy_rp::peripherals::PIO0;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::zerocopy_channel::{Receiver, Sender};
 use embassy_time::Timer;
 use fixed::types::U24F8;
 use fixed_macro::fixed;
@@ -12,9 +18,16 @@ use fixed_macro::fixed;
 use embassy_rp::{clocks, into_ref, Peripheral, PeripheralRef};
 use smart_leds::{RGB8, RGBA};
 
+use crate::framebuffer::{CHAIN_SIZE, LED_MATRIX_SIZE};
+use crate::strip;
+
 pub struct Ws2812<'d, P: Instance, const S: usize, const N: usize> {
     dma: PeripheralRef<'d, dma::AnyChannel>,
     sm: StateMachine<'d, P, S>,
+    // leds of the chain, the first ones of the frame, see boards.rs
+    led_count: usize,
+    // leds of the badges daisy chained after this one, from the frame after its matrix
+    chained_leds: usize,
 }
 
 impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
@@ -23,6 +36,7 @@ impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
         mut sm: StateMachine<'d, P, S>,
         dma: impl Peripheral<P = impl dma::Channel> + 'd,
         pin: impl PioPin,
+        led_count: usize,
     ) -> Self {
         into_ref!(dma);
 
@@ -84,28 +98,95 @@ impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
         Self {
             dma: dma.map_into(),
             sm,
+            led_count: led_count.min(N),
+            chained_leds: 0,
         }
     }
 
+    /// for the chains that change length, like the strip
+    pub fn set_led_count(&mut self, led_count: usize) {
+        self.led_count = led_count.min(N);
+    }
+
+    /// for the matrix of the first badge of a chain
+    pub fn set_chained_leds(&mut self, leds: usize) {
+        self.chained_leds = leds.min(N.saturating_sub(LED_MATRIX_SIZE));
+    }
+
     pub async fn write(&mut self, colors: &[crate::LedPixel; N]) {
+        let chained = &colors[LED_MATRIX_SIZE.min(N)..][..self.chained_leds];
+        let leds = colors[..self.led_count].iter().chain(chained);
+
         // Precompute the word bytes from the colors
         let mut words = [0u32; N];
-        for i in 0..N {
-            let word = (u32::from(colors[i].g) << 24)
-                | (u32::from(colors[i].r) << 16)
-                | (u32::from(colors[i].b) << 8)
+        let mut count = 0;
+        for (word, color) in words.iter_mut().zip(leds) {
+            *word = (u32::from(color.g) << 24)
+                | (u32::from(color.r) << 16)
+                | (u32::from(color.b) << 8)
                 | if crate::HAS_WHITE_LED {
-                    u32::from(colors[i].w)
+                    u32::from(color.w)
                 } else {
                     0
                 };
-
-            words[i] = word;
+            count += 1;
         }
 
         // DMA transfer
-        self.sm.tx().dma_push(self.dma.reborrow(), &words).await;
+        self.sm
+            .tx()
+            .dma_push(self.dma.reborrow(), &words[..count])
+            .await;
 
         Timer::after_micros(55).await;
     }
 }
+
+/// the leds of the matrix, then the ones of the badges chained after it
+pub type Frame = [crate::LedPixel; CHAIN_SIZE];
+pub type FrameSender = Sender<'static, CriticalSectionRawMutex, Frame>;
+pub type FrameReceiver = Receiver<'static, CriticalSectionRawMutex, Frame>;
+
+// badges of the daisy chain, this one included, see LedMatrix::set_chained_badges
+static CHAINED_BADGES: AtomicU8 = AtomicU8::new(1);
+
+pub fn set_chained_badges(badges: u8) {
+    CHAINED_BADGES.store(badges, Ordering::Relaxed);
+}
+
+/// the external strip, on the second state machine of the leds
+pub type StripLeds = Ws2812<'static, PIO0, 1, { strip::MAX_LEDS }>;
+
+/// sends the frames to the leds while the next one is being rendered, and to the strip
+/// at the same time
+#[embassy_executor::task]
+pub async fn ws2812_tsk(
+    mut ws2812: Ws2812<'static, PIO0, 0, CHAIN_SIZE>,
+    mut strip_leds: Option<StripLeds>,
+    mut frames: FrameReceiver,
+) {
+    // leds of the strip lit by the last frame, the ones after a shorter length are
+    // turned off once
+    let mut strip_lit = 0;
+    // the same for the chained badges
+    let mut chained_lit = 0;
+
+    loop {
+        let frame = frames.receive().await;
+        let strip = strip::get();
+
+        let chained = LED_MATRIX_SIZE * (CHAINED_BADGES.load(Ordering::Relaxed) as usize - 1);
+        ws2812.set_chained_leds(chained.max(chained_lit));
+        chained_lit = chained;
+
+        match &mut strip_leds {
+            Some(strip_leds) if strip.leds().max(strip_lit) > 0 => {
+                strip_leds.set_led_count(strip.leds().max(strip_lit));
+                strip_lit = strip.leds();
+                join(ws2812.write(frame), strip_leds.write(&strip.render(frame))).await;
+            }
+            _ => ws2812.write(frame).await,
+        }
+        frames.receive_done();
+    }
+}
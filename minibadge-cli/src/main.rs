@@ -2,7 +2,8 @@ use std::{io::Write, time::Duration};
 
 mod midi;
 
-use clap::{Args, Parser, Subcommand};
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 
 use capnp::message::Builder;
 use capnp::serialize;
@@ -23,6 +24,13 @@ struct Cli {
     #[arg(short, long)]
     serial_port: Option<String>,
 
+    /// Frame the messages for the UART of the badge instead of its USB interface
+    ///
+    /// Use it with a USB to serial adapter connected to the UART pins of a badge
+    /// built with the uart feature
+    #[arg(short, long)]
+    uart: bool,
+
     /// Set the badge to a solid color, the color should be written in hex format
     /// like "#ff0000" for red, etc.
     #[arg(short = 'c', long)]
@@ -52,6 +60,134 @@ struct Cli {
 enum Subcommands {
     /// Use the badge to send an infrared NEC command
     SendNec(SendNec),
+    /// Set the clock of the badge
+    SetTime(SetTime),
+    /// Show a notification on top of the current effect for a few seconds
+    Notify(Notify),
+    /// Start from a random effect when the badge is turned on
+    RandomBootScene(RandomBootScene),
+    /// Choose how the badge moves between effects, the setting is saved in the badge
+    Playback(Playback),
+    /// Create a new effect on the badge, it is shown while editing
+    #[command(subcommand)]
+    Scene(SceneCommand),
+    /// Log every rendered frame with its timestamp on the debug interface
+    Capture(Capture),
+    /// Show frames read from stdin, one per line, with 9 colors like the frame buffer option
+    ///
+    /// Lines logged by the capture command are accepted too, so a capture can be played back.
+    /// Press the button on the badge to go back to the effects.
+    Stream(Stream),
+    /// Correct the leds of the badge, the corrections are saved in the badge
+    #[command(subcommand)]
+    Calibrate(CalibrateCommand),
+    /// Set how many frames per second the badge renders, the setting is saved in the badge
+    FrameRate(FrameRate),
+    /// Set the brightness, like the button and the remote
+    Brightness(Brightness),
+    /// Limit the brightness of the leds whatever the other settings, the setting is saved in the badge
+    MaxBrightness(MaxBrightness),
+    /// Lower the brightness limit between two times of the day, the setting is saved in the badge
+    ///
+    /// The badge needs the time, from set-time, and keeps the lower of the two limits. Without
+    /// times the quiet hours are turned off.
+    QuietHours(QuietHours),
+    /// Set the colors of the indexed palettes, the setting is saved in the badge
+    PaletteTable(PaletteTable),
+    /// Light the top right led on the infrared traffic, to align the badges
+    ///
+    /// Dim white for every edge, green for the NEC packets and blue for the Samsung ones.
+    IrOverlay(IrOverlay),
+    /// Listen only to the given infrared remotes, the setting is saved in the badge
+    ///
+    /// Without remotes the badge listens to every remote. The packets of the other badges always
+    /// get through. The addresses of the packets are in the debug log, without a filter.
+    IrFilter(IrFilter),
+    /// Set the name of the owner of the badge, the setting is saved in the badge
+    Name(Name),
+    /// Show the initials of the owner in place of the effect every few seconds, the setting is
+    /// saved in the badge
+    ///
+    /// The letters are shown one at a time, without initials the badge shows only the effects.
+    Initials(Initials),
+    /// Replace the boot animation with frames read from stdin, the logo is saved in the badge
+    ///
+    /// Up to 16 frames, one per line, with 9 colors like the frame buffer option. The badge plays
+    /// it from the next boot.
+    BootLogo(BootLogo),
+    /// Restart the badge, or drop it into the USB bootloader to flash a new firmware
+    Reboot(Reboot),
+    /// Set up the external LED strip of the badge, the setting is saved in the badge
+    ///
+    /// The strip shows the effects of the badge when its firmware is built with the strip
+    /// feature.
+    Strip(Strip),
+    /// Set how many badges are daisy chained from this one, the setting is saved in the badge
+    ///
+    /// The data out pad of the LEDs of a badge drives the LEDs of the next one, and the effects
+    /// are drawn on the whole chain as one long display, the first badge on the left.
+    Chain(Chain),
+    /// Show the effects of a leader badge in step with it, over infrared, for walls of badges
+    ///
+    /// The leader sends its time and its effect every two seconds, the followers in front of it
+    /// follow.
+    Party(Party),
+    /// Log the frame rate, the frame times and the flash writes on the debug interface
+    Stats,
+    /// Record the button, IR and motion events of the badge and play them back
+    #[command(subcommand)]
+    Input(InputCommand),
+    /// Record the effect shown by the badge, to keep it as an effect of its own
+    ///
+    /// Up to 10 seconds at 10 frames per second, saved when stopped or full. The last 4
+    /// recordings are played in a loop after the other effects from the next boot.
+    Record(Record),
+    /// Mix a second effect with the current one, with a crossfader like the two decks of a DJ
+    ///
+    /// The badges can be mixed over infrared too, from an IR blaster, see the readme.
+    #[command(subcommand)]
+    Mix(MixCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum InputCommand {
+    /// "on" starts a new recording, "off" stops it
+    Record {
+        #[arg(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Send the recorded events again, with the same timing
+    Replay,
+}
+
+#[derive(Subcommand, Debug)]
+enum MixCommand {
+    /// Mix in an effect, by its number from 0, starting from the current effect only
+    Scene { scene: u8 },
+    /// Move the crossfader, in percent of the second effect
+    Amount {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        percent: u8,
+    },
+    /// Move the crossfader from one amount to another, in percent, a step every 50 ms
+    Fade {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        from: u8,
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        to: u8,
+        /// Seconds of the fade
+        #[arg(short, long, default_value_t = 2.0)]
+        seconds: f32,
+    },
+    /// Back to the current effect only
+    Stop,
+}
+
+#[derive(Args, Debug)]
+struct Record {
+    /// "on" starts a new recording, "off" stops it and saves it
+    #[arg(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    enabled: bool,
 }
 
 #[derive(Args, Debug)]
@@ -67,6 +203,392 @@ struct SendNec {
     repeat: bool,
 }
 
+#[derive(Args, Debug)]
+struct SetTime {
+    /// Time of the day, formatted like "HH:MM" or "HH:MM:SS"
+    time: String,
+}
+
+#[derive(Args, Debug)]
+struct Notify {
+    /// Kind of notification
+    #[arg(value_enum)]
+    notification: NotificationKind,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum NotificationKind {
+    Mention,
+    Message,
+    BuildFailed,
+    BuildPassed,
+}
+
+impl From<NotificationKind> for usb_messages_capnp::Notification {
+    fn from(kind: NotificationKind) -> Self {
+        match kind {
+            NotificationKind::Mention => usb_messages_capnp::Notification::Mention,
+            NotificationKind::Message => usb_messages_capnp::Notification::Message,
+            NotificationKind::BuildFailed => usb_messages_capnp::Notification::BuildFailed,
+            NotificationKind::BuildPassed => usb_messages_capnp::Notification::BuildPassed,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct RandomBootScene {
+    /// "on" or "off", the setting is saved in the badge
+    #[arg(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    enabled: bool,
+}
+
+#[derive(Args, Debug)]
+struct Playback {
+    #[arg(value_enum)]
+    mode: PlaybackMode,
+    /// Seconds between effects in shuffle mode, 0 = only on button press
+    #[arg(short, long, default_value_t = 0)]
+    interval: u16,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PlaybackMode {
+    /// One effect after the other, in order
+    Sequential,
+    /// Random order, every effect is shown once before repeating
+    Shuffle,
+}
+
+#[derive(Args, Debug)]
+struct Capture {
+    /// "on" or "off"
+    #[arg(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    enabled: bool,
+}
+
+#[derive(Args, Debug)]
+struct IrOverlay {
+    /// "on" or "off"
+    #[arg(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    enabled: bool,
+}
+
+#[derive(Args, Debug)]
+struct IrFilter {
+    /// Address of a NEC remote, can be repeated
+    #[arg(long)]
+    nec: Vec<u8>,
+    /// Address of a Samsung remote, can be repeated, the badge keeps up to 4 remotes in all
+    #[arg(long)]
+    samsung: Vec<u8>,
+}
+
+#[derive(Args, Debug)]
+struct Name {
+    /// Up to 16 bytes, a longer name is cut
+    name: String,
+}
+
+#[derive(Args, Debug)]
+struct Initials {
+    /// Up to 3 letters, the other characters are left out, none turns the initials off
+    #[arg(default_value = "")]
+    initials: String,
+}
+
+#[derive(Args, Debug)]
+struct BootLogo {
+    /// Milliseconds of every frame, from 20 to 250
+    #[arg(short, long, default_value_t = 100, value_parser = clap::value_parser!(u16).range(20..=250))]
+    frame_millis: u16,
+    /// Bring back the built-in boot animation, stdin is not read
+    #[arg(short, long)]
+    reset: bool,
+}
+
+// checked by the badge, so a stray message doesn't restart it
+const REBOOT_MAGIC: u32 = 0x4D425254;
+
+#[derive(Args, Debug)]
+struct Reboot {
+    /// Into the USB bootloader (BOOTSEL) instead of the firmware
+    #[arg(short, long)]
+    bootloader: bool,
+}
+
+#[derive(Args, Debug)]
+struct Strip {
+    /// LEDs of the strip, up to 144, 0 turns it off
+    #[arg(value_parser = clap::value_parser!(u16).range(0..=144))]
+    length: u16,
+    #[arg(short, long, value_enum, default_value_t = StripMapping::Mirror)]
+    mapping: StripMapping,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StripMapping {
+    /// The LEDs of the badge row by row, over and over
+    Mirror,
+    /// The columns of the badge stretched over the strip, with the colors in between
+    Gradient,
+}
+
+impl From<StripMapping> for usb_messages_capnp::StripMapping {
+    fn from(mapping: StripMapping) -> Self {
+        match mapping {
+            StripMapping::Mirror => usb_messages_capnp::StripMapping::Mirror,
+            StripMapping::Gradient => usb_messages_capnp::StripMapping::Gradient,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct Chain {
+    /// Badges in the chain, this one included, from 1 (no chain) to 8
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=8))]
+    badges: u8,
+}
+
+#[derive(Args, Debug)]
+struct Party {
+    #[arg(value_enum)]
+    role: PartyRole,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PartyRole {
+    /// Don't send or follow
+    Off,
+    /// Send the time and the effect of this badge
+    Leader,
+    /// Show the effect of the leader in step with it
+    Follower,
+}
+
+impl From<PartyRole> for usb_messages_capnp::PartyRole {
+    fn from(role: PartyRole) -> Self {
+        match role {
+            PartyRole::Off => usb_messages_capnp::PartyRole::Off,
+            PartyRole::Leader => usb_messages_capnp::PartyRole::Leader,
+            PartyRole::Follower => usb_messages_capnp::PartyRole::Follower,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct FrameRate {
+    /// Frames per second, from 10 to 200, the default is 100
+    #[arg(value_parser = clap::value_parser!(u8).range(10..=200))]
+    fps: u8,
+}
+
+#[derive(Args, Debug)]
+struct Brightness {
+    /// From 0 (night mode) to 15 (full)
+    #[arg(value_parser = clap::value_parser!(u8).range(0..=15))]
+    level: u8,
+}
+
+#[derive(Args, Debug)]
+struct MaxBrightness {
+    /// Percent of the full brightness, 100 = no limit, the firmware can have a lower limit
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=100))]
+    percent: u8,
+}
+
+#[derive(Args, Debug)]
+struct QuietHours {
+    /// Start, formatted like "HH:MM"
+    #[arg(requires = "end")]
+    start: Option<String>,
+    /// End, formatted like "HH:MM", the next day when before the start
+    end: Option<String>,
+    /// Percent of the full brightness in the quiet hours
+    #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100))]
+    max_brightness: u8,
+}
+
+#[derive(Args, Debug)]
+struct PaletteTable {
+    /// Up to 16 colors like "#ff0000", none for a rainbow
+    #[arg(num_args = 0..=16)]
+    colors: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct Stream {
+    /// Frames per second, the badge shows at most as many as its frame rate (100 by default)
+    #[arg(short, long, default_value_t = 30)]
+    fps: u32,
+}
+
+#[derive(Subcommand, Debug)]
+enum SceneCommand {
+    /// Start a new effect with a single layer
+    New,
+    /// Add a layer on top of the others, the following commands change it
+    AddLayer,
+    /// Set the leds turned on by the layer
+    Pattern {
+        /// 9 bits, from the top left led, like "010001111"
+        bits: String,
+    },
+    /// Set a solid color for the layer, like "#ff0000"
+    Color { color: String },
+    /// Set a palette the layer cycles through
+    Palette {
+        /// Colors per second
+        #[arg(short, long, default_value_t = 1.0)]
+        speed: f32,
+        /// Colors like "#ff0000", none for a rainbow
+        colors: Vec<String>,
+        /// Use the palette table of the badge instead of the colors, see the palette-table command
+        #[arg(long, conflicts_with = "colors")]
+        indexed: bool,
+    },
+    /// Set a blend of two colors, with the palette-cycle shader the leds alternate between them
+    Duotone {
+        /// Like "#ff0000"
+        a: String,
+        /// Like "#0000ff"
+        b: String,
+        /// 0 = all a, 1 = all b, when alternating how long b lasts
+        #[arg(short, long, default_value_t = 0.5)]
+        bias: f32,
+    },
+    /// Set colors that follow a sensor instead of the time
+    SensorPalette {
+        #[arg(value_enum)]
+        sensor: Sensor,
+        /// Colors like "#ff0000" from the lowest to the highest value, none for blue to red
+        colors: Vec<String>,
+    },
+    /// Add a shader to the layer
+    Shader {
+        #[arg(value_enum)]
+        kind: ShaderKind,
+        /// Speed, time constant or temperature, depending on the shader
+        param: f32,
+        /// Apply the shader to the whole matrix instead of the pattern
+        #[arg(long)]
+        screen: bool,
+    },
+    /// Save the effect in the badge, it is added to the others at the next boot
+    Save,
+    /// Throw away the effect
+    Discard,
+}
+
+#[derive(Subcommand, Debug)]
+enum CalibrateCommand {
+    /// Scale the colors of one led, so a white frame looks the same on every led
+    WhiteBalance {
+        /// Position in the led chain, 0 is the top left
+        led: u8,
+        /// Scale of each channel like "#ffe0c0", "#ffffff" leaves the led unchanged
+        scale: String,
+    },
+    /// Scale the brightness of one led, so the brighter ones match the others
+    Luminance {
+        /// Position in the led chain, 0 is the top left
+        led: u8,
+        /// 255 leaves the led unchanged
+        scale: u8,
+    },
+    /// Light one led at a time in white to measure them, press the button for the next one
+    StepLeds,
+    /// Turn off a broken led
+    DeadPixel {
+        /// Position in the led chain, 0 is the top left
+        led: u8,
+        /// Show its colors on this led, one of its neighbours
+        #[arg(short, long)]
+        move_to: Option<u8>,
+    },
+    /// Use a led again after it was marked as dead
+    WorkingPixel {
+        /// Position in the led chain, 0 is the top left
+        led: u8,
+    },
+    /// Set the gamma correction, one value for all the channels or one each for red, green and blue
+    Gamma {
+        /// From 1.0 (linear) to 4.0, the default is 2.8
+        #[arg(num_args = 1..=3, required = true)]
+        gamma: Vec<f32>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Sensor {
+    /// How close the leds are to being throttled because of the heat
+    Temperature,
+    /// Needs a badge with a microphone
+    AudioLevel,
+    /// Needs a badge with a microphone
+    Bass,
+    /// Needs a badge with a microphone
+    Mid,
+    /// Needs a badge with a microphone
+    Treble,
+}
+
+impl From<Sensor> for usb_messages_capnp::Sensor {
+    fn from(sensor: Sensor) -> Self {
+        match sensor {
+            Sensor::Temperature => usb_messages_capnp::Sensor::Temperature,
+            Sensor::AudioLevel => usb_messages_capnp::Sensor::AudioLevel,
+            Sensor::Bass => usb_messages_capnp::Sensor::Bass,
+            Sensor::Mid => usb_messages_capnp::Sensor::Mid,
+            Sensor::Treble => usb_messages_capnp::Sensor::Treble,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ShaderKind {
+    Breathing,
+    Blinking,
+    LowPass,
+    LowPassWithPeak,
+    Rainbow,
+    /// Needs a badge with a microphone
+    AudioLevel,
+    /// Needs a badge with a microphone
+    BeatPulse,
+    /// Warmer or colder whites, the parameter is the temperature in kelvin, 6500 = unchanged
+    WhitePoint,
+    /// Soften the leds into glows, the parameter goes from 0 to 1, the whole matrix is blurred
+    Blur,
+    /// Shift rows, invert leds or drop frames at random, the parameter is how many times per second
+    Glitch,
+    /// Short flashes, the parameter is how many per second, in sync with the beats and the sync pulses
+    Strobe,
+    /// Blue and slow when the badge is cool, red and fast when it's hot, the parameter is the beats per second when cool
+    Heartbeat,
+    /// Turn the palette of the layer across the leds, the parameter is the colors per second
+    PaletteCycle,
+}
+
+impl From<ShaderKind> for usb_messages_capnp::ShaderKind {
+    fn from(kind: ShaderKind) -> Self {
+        match kind {
+            ShaderKind::Breathing => usb_messages_capnp::ShaderKind::Breathing,
+            ShaderKind::Blinking => usb_messages_capnp::ShaderKind::Blinking,
+            ShaderKind::LowPass => usb_messages_capnp::ShaderKind::LowPass,
+            ShaderKind::LowPassWithPeak => usb_messages_capnp::ShaderKind::LowPassWithPeak,
+            ShaderKind::Rainbow => usb_messages_capnp::ShaderKind::Rainbow2d,
+            ShaderKind::AudioLevel => usb_messages_capnp::ShaderKind::AudioLevel,
+            ShaderKind::BeatPulse => usb_messages_capnp::ShaderKind::BeatPulse,
+            ShaderKind::WhitePoint => usb_messages_capnp::ShaderKind::WhitePoint,
+            ShaderKind::Blur => usb_messages_capnp::ShaderKind::Blur,
+            ShaderKind::Glitch => usb_messages_capnp::ShaderKind::Glitch,
+            ShaderKind::Strobe => usb_messages_capnp::ShaderKind::Strobe,
+            ShaderKind::Heartbeat => usb_messages_capnp::ShaderKind::Heartbeat,
+            ShaderKind::PaletteCycle => usb_messages_capnp::ShaderKind::PaletteCycle,
+        }
+    }
+}
+
 fn hex_color_to_rgb(color: String) -> RGB8 {
     let color = color.trim_start_matches("#");
     let r = u8::from_str_radix(&color[0..2], 16).unwrap();
@@ -88,6 +610,46 @@ fn midi_demo(file: String) {
         .expect("Failed to set LED color");
 }
 
+/// cobs encodes every write and terminates it with a 0, the format read by the uart of the badge
+struct CobsWriter<W: Write>(W);
+
+impl<W: Write> Write for CobsWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // the whole buffer is one message, so it must be written in one go
+        self.0.write_all(&cobs_encode(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0];
+    let mut code_index = 0;
+    let mut code = 1u8;
+
+    for &byte in data {
+        if byte != 0 {
+            out.push(byte);
+            code += 1;
+        }
+
+        // every block ends with a 0 or after 254 bytes
+        if byte == 0 || code == 0xFF {
+            out[code_index] = code;
+            code_index = out.len();
+            out.push(0);
+            code = 1;
+        }
+    }
+
+    out[code_index] = code;
+    out.push(0);
+    out
+}
+
 fn main() {
     let args = Cli::parse();
 
@@ -102,12 +664,17 @@ fn main() {
 
     let serial_port = args.serial_port.unwrap_or("/dev/ttyACM0".to_string());
 
-    let mut port = serialport::new(serial_port, 115_200)
+    let port = serialport::new(serial_port, 115_200)
         .timeout(Duration::from_millis(10))
         .open()
         .expect("Failed to open port");
 
-    #[allow(clippy::single_match)]
+    let mut port: Box<dyn Write> = if args.uart {
+        Box::new(CobsWriter(port))
+    } else {
+        Box::new(port)
+    };
+
     match args.subcommand {
         Some(Subcommands::SendNec(send_nec)) => {
             let mut message = Builder::new_default();
@@ -123,6 +690,576 @@ fn main() {
 
             port.write_all(&data).expect("Failed to write to port");
         }
+        Some(Subcommands::SetTime(set_time)) => {
+            let fields = set_time
+                .time
+                .split(":")
+                .map(|s| s.parse::<u8>().expect("Invalid time"))
+                .collect::<Vec<u8>>();
+
+            if fields.len() < 2 || fields.len() > 3 || fields[0] > 23 || fields[1] > 59 {
+                println!("Time must be formatted like HH:MM or HH:MM:SS");
+                return;
+            }
+
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let mut time = badgebound.init_set_time();
+            time.set_hour(fields[0]);
+            time.set_minute(fields[1]);
+            time.set_second(fields.get(2).copied().unwrap_or(0).min(59));
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Notify(notify)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_notify(notify.notification.into());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::RandomBootScene(random_boot_scene)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_random_boot_scene(random_boot_scene.enabled);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Playback(playback)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let mut set_playback = badgebound.init_set_playback();
+            set_playback.set_shuffle(matches!(playback.mode, PlaybackMode::Shuffle));
+            set_playback.set_interval(playback.interval);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Scene(scene)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let mut edit = badgebound.init_edit_scene();
+            match scene {
+                SceneCommand::New => edit.set_new(()),
+                SceneCommand::AddLayer => edit.set_add_layer(()),
+                SceneCommand::Pattern { bits } => {
+                    // the first char is the top left led, the highest bit
+                    if bits.len() != 9 || !bits.chars().all(|c| c == '0' || c == '1') {
+                        println!("The pattern must be 9 bits like \"010001111\"");
+                        return;
+                    }
+
+                    edit.set_set_pattern(u16::from_str_radix(&bits, 2).unwrap());
+                }
+                SceneCommand::Color { color } => {
+                    let color = hex_color_to_rgb(color);
+
+                    let mut set_color = edit.init_set_color();
+                    set_color.set_r(color.r);
+                    set_color.set_g(color.g);
+                    set_color.set_b(color.b);
+                }
+                SceneCommand::Palette {
+                    speed,
+                    colors,
+                    indexed,
+                } => {
+                    let mut palette = edit.init_set_palette();
+                    palette.set_speed(speed);
+                    palette.set_indexed(indexed);
+
+                    let mut list = palette.init_colors(colors.len() as u32);
+                    for (i, color) in colors.into_iter().enumerate() {
+                        let color = hex_color_to_rgb(color);
+
+                        let mut pixel = list.reborrow().get(i as u32);
+                        pixel.set_r(color.r);
+                        pixel.set_g(color.g);
+                        pixel.set_b(color.b);
+                    }
+                }
+                SceneCommand::Duotone { a, b, bias } => {
+                    let (a, b) = (hex_color_to_rgb(a), hex_color_to_rgb(b));
+
+                    let mut duotone = edit.init_set_duotone();
+                    duotone.set_bias(bias);
+
+                    let mut rgb = duotone.reborrow().init_a();
+                    rgb.set_r(a.r);
+                    rgb.set_g(a.g);
+                    rgb.set_b(a.b);
+
+                    let mut rgb = duotone.init_b();
+                    rgb.set_r(b.r);
+                    rgb.set_g(b.g);
+                    rgb.set_b(b.b);
+                }
+                SceneCommand::SensorPalette { sensor, colors } => {
+                    let mut palette = edit.init_set_sensor_palette();
+                    palette.set_sensor(sensor.into());
+
+                    let mut list = palette.init_colors(colors.len() as u32);
+                    for (i, color) in colors.into_iter().enumerate() {
+                        let color = hex_color_to_rgb(color);
+
+                        let mut pixel = list.reborrow().get(i as u32);
+                        pixel.set_r(color.r);
+                        pixel.set_g(color.g);
+                        pixel.set_b(color.b);
+                    }
+                }
+                SceneCommand::Shader {
+                    kind,
+                    param,
+                    screen,
+                } => {
+                    let mut shader = edit.init_add_shader();
+                    shader.set_kind(kind.into());
+                    shader.set_param(param);
+                    shader.set_screen(screen);
+                }
+                SceneCommand::Save => edit.set_save(()),
+                SceneCommand::Discard => edit.set_discard(()),
+            }
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Calibrate(calibrate)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let calibration = badgebound.init_calibrate();
+            match calibrate {
+                CalibrateCommand::WhiteBalance { led, scale } => {
+                    let scale = hex_color_to_rgb(scale);
+
+                    let mut white_balance = calibration.init_white_balance();
+                    white_balance.set_led(led);
+
+                    let mut rgb = white_balance.init_scale();
+                    rgb.set_r(scale.r);
+                    rgb.set_g(scale.g);
+                    rgb.set_b(scale.b);
+                }
+                CalibrateCommand::Luminance { led, scale } => {
+                    let mut luminance = calibration.init_luminance();
+                    luminance.set_led(led);
+                    luminance.set_scale(scale);
+                }
+                CalibrateCommand::StepLeds => calibration.set_step_leds(()),
+                CalibrateCommand::DeadPixel { led, move_to } => {
+                    let mut pixel = calibration.init_pixel_status();
+                    pixel.set_led(led);
+
+                    match move_to {
+                        Some(target) => pixel.set_moved_to(target),
+                        None => pixel.set_dead(()),
+                    }
+                }
+                CalibrateCommand::WorkingPixel { led } => {
+                    let mut pixel = calibration.init_pixel_status();
+                    pixel.set_led(led);
+                    pixel.set_working(());
+                }
+                CalibrateCommand::Gamma { gamma } => {
+                    let (r, g, b) = match gamma[..] {
+                        [all] => (all, all, all),
+                        [r, g, b] => (r, g, b),
+                        _ => {
+                            println!("Give one gamma for all the channels or three for red, green and blue");
+                            return;
+                        }
+                    };
+
+                    let mut set_gamma = calibration.init_gamma();
+                    set_gamma.set_r(r);
+                    set_gamma.set_g(g);
+                    set_gamma.set_b(b);
+                }
+            }
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Stream(stream)) => {
+            let period = Duration::from_secs(1) / stream.fps.clamp(1, 100);
+
+            for line in std::io::stdin().lines() {
+                let line = line.expect("Failed to read stdin");
+
+                // only the colors are used, the timestamp of captured frames is skipped
+                let colors = line
+                    .split_whitespace()
+                    .filter(|s| s.starts_with("#"))
+                    .map(|s| hex_color_to_rgb(s.to_string()))
+                    .collect::<Vec<RGB8>>();
+
+                if colors.len() != 9 {
+                    continue;
+                }
+
+                let mut message = Builder::new_default();
+
+                let mut badgebound =
+                    message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+                let frame = colors
+                    .iter()
+                    .flat_map(|c| [c.r, c.g, c.b])
+                    .collect::<Vec<u8>>();
+                badgebound.set_stream_frame(&frame);
+
+                let data = serialize::write_message_to_words(&message);
+
+                port.write_all(&data).expect("Failed to write to port");
+
+                std::thread::sleep(period);
+            }
+        }
+        Some(Subcommands::Capture(capture)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_frame_capture(capture.enabled);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::IrOverlay(overlay)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_ir_overlay(overlay.enabled);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::IrFilter(filter)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let remotes: Vec<_> = filter
+                .nec
+                .iter()
+                .map(|&address| (usb_messages_capnp::IrProtocol::Nec, address))
+                .chain(
+                    filter
+                        .samsung
+                        .iter()
+                        .map(|&address| (usb_messages_capnp::IrProtocol::Samsung, address)),
+                )
+                .collect();
+
+            let mut list = badgebound.init_set_ir_filter(remotes.len() as u32);
+            for (i, (protocol, address)) in remotes.into_iter().enumerate() {
+                let mut remote = list.reborrow().get(i as u32);
+                remote.set_protocol(protocol);
+                remote.set_address(address);
+            }
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Name(name)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_name(name.name.as_str());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Initials(initials)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_initials(initials.initials.as_str());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::BootLogo(logo)) => {
+            // one message per frame, a whole logo doesn't fit in the buffer of the badge
+            let mut frames = Vec::new();
+            if !logo.reset {
+                for line in std::io::stdin().lines() {
+                    let line = line.expect("Failed to read stdin");
+
+                    let colors = line
+                        .split_whitespace()
+                        .filter(|s| s.starts_with("#"))
+                        .map(|s| hex_color_to_rgb(s.to_string()))
+                        .collect::<Vec<RGB8>>();
+
+                    if colors.len() != 9 {
+                        continue;
+                    }
+                    frames.push(
+                        colors
+                            .iter()
+                            .flat_map(|c| [c.r, c.g, c.b])
+                            .collect::<Vec<u8>>(),
+                    );
+                }
+                if frames.len() > 16 {
+                    println!("Only the first 16 frames are used");
+                    frames.truncate(16);
+                }
+            }
+
+            let mut message = Builder::new_default();
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_new_boot_logo(());
+            let data = serialize::write_message_to_words(&message);
+            port.write_all(&data).expect("Failed to write to port");
+
+            for frame in &frames {
+                let mut message = Builder::new_default();
+                let mut badgebound =
+                    message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+                badgebound.set_add_boot_logo_frame(frame);
+                let data = serialize::write_message_to_words(&message);
+                port.write_all(&data).expect("Failed to write to port");
+
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            let mut message = Builder::new_default();
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_save_boot_logo(logo.frame_millis);
+            let data = serialize::write_message_to_words(&message);
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Reboot(reboot)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            let mut target = badgebound.init_reboot();
+            target.set_magic(REBOOT_MAGIC);
+            target.set_bootloader(reboot.bootloader);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Strip(strip)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            let mut set_strip = badgebound.init_set_strip();
+            set_strip.set_length(strip.length);
+            set_strip.set_mapping(strip.mapping.into());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Chain(chain)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_chained_badges(chain.badges);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Party(party)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_party_role(party.role.into());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::FrameRate(frame_rate)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_frame_rate(frame_rate.fps);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Brightness(brightness)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_brightness(brightness.level);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::MaxBrightness(max_brightness)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_set_max_brightness(max_brightness.percent);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::QuietHours(quiet_hours)) => {
+            let times = [quiet_hours.start, quiet_hours.end].map(|time| {
+                let fields = time
+                    .unwrap_or_else(|| "00:00".to_string())
+                    .split(":")
+                    .map(|s| s.parse::<u8>().expect("Invalid time"))
+                    .collect::<Vec<u8>>();
+                (fields.len() == 2 && fields[0] <= 23 && fields[1] <= 59).then_some(fields)
+            });
+            let [Some(start), Some(end)] = times else {
+                println!("Times must be formatted like HH:MM");
+                return;
+            };
+
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            // the same start and end turn the quiet hours off
+            let mut set_quiet_hours = badgebound.init_set_quiet_hours();
+            let mut time = set_quiet_hours.reborrow().init_start();
+            time.set_hour(start[0]);
+            time.set_minute(start[1]);
+            let mut time = set_quiet_hours.reborrow().init_end();
+            time.set_hour(end[0]);
+            time.set_minute(end[1]);
+            set_quiet_hours.set_max_brightness(quiet_hours.max_brightness);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::PaletteTable(table)) => {
+            let mut message = Builder::new_default();
+
+            let badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+
+            let mut list = badgebound.init_set_palette_table(table.colors.len() as u32);
+            for (i, color) in table.colors.into_iter().enumerate() {
+                let color = hex_color_to_rgb(color);
+
+                let mut pixel = list.reborrow().get(i as u32);
+                pixel.set_r(color.r);
+                pixel.set_g(color.g);
+                pixel.set_b(color.b);
+            }
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Stats) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_log_stats(());
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Input(input)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            match input {
+                InputCommand::Record { enabled } => badgebound.set_record_input(enabled),
+                InputCommand::Replay => badgebound.set_replay_input(()),
+            }
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
+        Some(Subcommands::Mix(mix)) => {
+            let amounts = match mix {
+                MixCommand::Fade { from, to, seconds } => {
+                    let steps = ((seconds * 20.0) as u32).max(1);
+                    (0..=steps)
+                        .map(|i| {
+                            let at = i as f32 / steps as f32;
+                            (from as f32 + (to as f32 - from as f32) * at).round() as u8
+                        })
+                        .collect()
+                }
+                MixCommand::Amount { percent } => vec![percent],
+                _ => vec![],
+            };
+
+            let scene = match mix {
+                MixCommand::Scene { scene } => Some(scene),
+                // past the last effect
+                MixCommand::Stop => Some(u8::MAX),
+                _ => None,
+            };
+            if let Some(scene) = scene {
+                let mut message = Builder::new_default();
+
+                let mut badgebound =
+                    message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+                badgebound.set_set_mix_scene(scene);
+
+                let data = serialize::write_message_to_words(&message);
+
+                port.write_all(&data).expect("Failed to write to port");
+            }
+
+            for amount in amounts {
+                let mut message = Builder::new_default();
+
+                let mut badgebound =
+                    message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+                badgebound.set_set_mix_amount(amount);
+
+                let data = serialize::write_message_to_words(&message);
+
+                port.write_all(&data).expect("Failed to write to port");
+
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+        Some(Subcommands::Record(record)) => {
+            let mut message = Builder::new_default();
+
+            let mut badgebound = message.init_root::<usb_messages_capnp::badge_bound::Builder>();
+            badgebound.set_record_frames(record.enabled);
+
+            let data = serialize::write_message_to_words(&message);
+
+            port.write_all(&data).expect("Failed to write to port");
+        }
         None => {}
     }
 
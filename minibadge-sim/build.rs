@@ -0,0 +1,14 @@
+// the built-in scenes, compiled from the scenes.ron of the firmware like it does
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[path = "../antani_sw/build_scenes.rs"]
+mod build_scenes;
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed=../antani_sw/scenes.ron");
+    build_scenes::compile_scenes(Path::new("../antani_sw/scenes.ron"), &out);
+}
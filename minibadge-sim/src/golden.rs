@@ -0,0 +1,76 @@
+// renders every built-in scene for ten seconds and compares some of its frames with the
+// ones saved in golden/, so a change of the shader math that changes what the badge shows
+// doesn't go unnoticed
+//
+// a missing golden file fails the test like a changed one, run it with UPDATE_GOLDEN=1 to
+// write them all again after a change that is meant to change the frames
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+use crate::rgbeffects::{RenderCommand, RenderManager, RenderTarget};
+use crate::time::Time;
+use crate::{scenes, RawFramebuffer, Terminal};
+
+const FPS: u8 = 100;
+/// the frames compared, the ones in between are rendered too for the shaders with a state
+const SNAPSHOT_FRAMES: [u32; 6] = [0, 1, 25, 50, 100, 1000];
+
+/// one line per snapshot, the frame number and the leds like minibadge-cli --frame-buffer
+fn render_snapshots(scene: &[RenderCommand]) -> String {
+    let mut renderman = RenderManager {
+        mtrx: Terminal::new(3),
+        rng: SmallRng::seed_from_u64(0),
+        persistent_data: Default::default(),
+        palette_table: Default::default(),
+    };
+
+    let mut snapshots = String::new();
+    for frame in 0..=SNAPSHOT_FRAMES[SNAPSHOT_FRAMES.len() - 1] {
+        renderman.render(scene, Time::default().after_frames(frame, FPS));
+
+        if SNAPSHOT_FRAMES.contains(&frame) {
+            let mut leds = RawFramebuffer::new();
+            for y in 0..3 {
+                for x in 0..3 {
+                    leds.set_pixel(x, y, renderman.mtrx.get_pixel(x, y));
+                }
+            }
+            writeln!(snapshots, "{} {}", frame, leds).unwrap();
+        }
+
+        renderman.mtrx.clear();
+    }
+    snapshots
+}
+
+#[test]
+// the spectrum scene moves the ones after it
+#[cfg_attr(feature = "mic", ignore)]
+fn built_in_scenes_match_golden_frames() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut changed = Vec::new();
+    for (index, scene) in scenes::scenes().iter().enumerate() {
+        let frames = render_snapshots(scene);
+        let path = dir.join(format!("scene_{:02}.txt", index));
+
+        if update {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&path, frames).unwrap();
+        } else if fs::read_to_string(&path).ok().as_ref() != Some(&frames) {
+            changed.push(index);
+        }
+    }
+
+    assert!(
+        changed.is_empty(),
+        "scenes {:?} don't match their golden frames or have none, run with UPDATE_GOLDEN=1 if the change is wanted",
+        changed
+    );
+}
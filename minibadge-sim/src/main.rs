@@ -0,0 +1,252 @@
+// runs the effects of the firmware on a pc and draws the leds in the terminal,
+// so a new effect can be tried without flashing the badge every time
+
+// the firmware modules have more than the simulator uses
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use heapless::Vec;
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+#[path = "../../antani_sw/src/calibration.rs"]
+mod calibration;
+#[path = "../../antani_sw/src/color.rs"]
+mod color;
+#[path = "../../antani_sw/src/drawing.rs"]
+mod drawing;
+#[path = "../../antani_sw/src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../../antani_sw/src/lut.rs"]
+mod lut;
+#[path = "../../antani_sw/src/records.rs"]
+mod records;
+#[path = "../../antani_sw/src/replay.rs"]
+mod replay;
+#[path = "../../antani_sw/src/rgbeffects.rs"]
+mod rgbeffects;
+#[path = "../../antani_sw/src/scenes.rs"]
+mod scenes;
+#[path = "../../antani_sw/src/strip.rs"]
+mod strip;
+#[path = "../../antani_sw/src/time.rs"]
+mod time;
+#[path = "../../antani_sw/src/timesync.rs"]
+mod timesync;
+
+use framebuffer::{ChainFramebuffer, LedPixel, RawFramebuffer, LED_MATRIX_SIZE};
+#[cfg(test)]
+mod golden;
+
+use rgbeffects::{
+    ColorPalette, FragmentShader, Pattern, RenderCommand, RenderManager, RenderTarget,
+};
+use time::Time;
+
+// stand ins for the hardware the effects read
+
+mod audio {
+    /// there is no microphone, the audio shaders see silence
+    pub fn level() -> f32 {
+        0.0
+    }
+
+    pub fn bands() -> [f32; 3] {
+        [0.0; 3]
+    }
+}
+
+mod thermal {
+    /// the pc is always cool
+    pub fn heat() -> f32 {
+        0.0
+    }
+}
+
+/// the levels of the brightness of the firmware, the config starts from the brightest
+const BRIGHTNESS_LEVELS: u8 = 16;
+
+mod input {
+    use serde::{Deserialize, Serialize};
+
+    // the remotes of the firmware, saved in the config, there is no ir here
+    pub const MAX_IR_REMOTES: usize = 4;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum IrProtocol {
+        Nec,
+        Samsung,
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct IrFilter {
+        pub remotes: [Option<(IrProtocol, u8)>; MAX_IR_REMOTES],
+    }
+}
+
+mod boards {
+    /// the matrix of the badge, the --size option repeats it
+    pub const MATRIX_WIDTH: usize = 3;
+    pub const MATRIX_HEIGHT: usize = 3;
+}
+
+mod clock {
+    use serde::{Deserialize, Serialize};
+
+    /// as in the firmware, for the config
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct QuietHours {
+        pub start: u16,
+        pub end: u16,
+        pub max_brightness: u8,
+    }
+
+    /// the time of the pc, in UTC
+    pub fn seconds_of_day() -> Option<u32> {
+        // fixed in the tests, so the clock scenes have golden frames too
+        if cfg!(test) {
+            return Some(12 * 3600 + 34 * 60);
+        }
+
+        let now = super::SystemTime::now()
+            .duration_since(super::UNIX_EPOCH)
+            .ok()?;
+        Some((now.as_secs() % 86400) as u32)
+    }
+}
+
+#[derive(Parser)]
+struct Cli {
+    /// Leds on each side of the matrix, the 3x3 patterns are repeated on bigger ones
+    #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(1..=32))]
+    size: u8,
+
+    /// Frames per second, like the frame-rate setting of the badge
+    #[arg(short, long, default_value_t = 100, value_parser = clap::value_parser!(u8).range(10..=200))]
+    fps: u8,
+
+    /// Show one of the built-in scenes of scenes.ron instead of the one in scene(), 0 is the first
+    #[arg(short = 'n', long)]
+    scene: Option<usize>,
+
+    /// Stop after this many seconds, 0 = run until ctrl-c
+    #[arg(short = 't', long, default_value_t = 0)]
+    seconds: u32,
+}
+
+/// the scene being developed, change it to try a new effect
+fn scene() -> [RenderCommand; 1] {
+    [RenderCommand {
+        effect: Pattern::Simple(0b010001111),
+        color: ColorPalette::Rainbow(0.2),
+        pattern_shaders: Vec::from_slice(&[FragmentShader::Breathing(0.7)]).unwrap(),
+        ..Default::default()
+    }]
+}
+
+/// a square of leds drawn with colored blocks, without gamma correction since
+/// the terminal already does its own
+pub struct Terminal {
+    size: usize,
+    pixels: std::vec::Vec<LedPixel>,
+}
+
+impl Terminal {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            pixels: vec![LedPixel::default(); size * size],
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, redraw: bool) -> std::io::Result<()> {
+        if redraw {
+            // back to the top left of the previous frame
+            write!(out, "\x1b[{}A", self.size)?;
+        }
+
+        for row in self.pixels.chunks(self.size) {
+            for led in row {
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\u{2588}\u{2588}",
+                    led.r, led.g, led.b
+                )?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        out.flush()
+    }
+}
+
+impl RenderTarget for Terminal {
+    fn size(&self) -> (usize, usize) {
+        (self.size, self.size)
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, colour: LedPixel) {
+        if x < self.size && y < self.size {
+            self.pixels[y * self.size + x] = colour;
+        }
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> LedPixel {
+        if x < self.size && y < self.size {
+            self.pixels[y * self.size + x]
+        } else {
+            LedPixel::default()
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill(LedPixel::default());
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    let mut renderman = RenderManager {
+        mtrx: Terminal::new(cli.size as usize),
+        rng: SmallRng::seed_from_u64(0),
+        persistent_data: Default::default(),
+        palette_table: Default::default(),
+    };
+    let scene = match cli.scene {
+        Some(index) => {
+            let scenes = scenes::scenes();
+            let Some(scene) = scenes.get(index) else {
+                eprintln!("There are only {} built-in scenes", scenes.len());
+                std::process::exit(1);
+            };
+            scene.to_vec()
+        }
+        None => scene().to_vec(),
+    };
+
+    let period = Duration::from_secs(1) / cli.fps as u32;
+    let frames = cli.seconds * cli.fps as u32;
+    let start = Instant::now();
+    let mut out = std::io::stdout().lock();
+
+    for frame in 0.. {
+        if frames > 0 && frame == frames {
+            break;
+        }
+
+        // the same time base as the badge, counted in frames
+        let t = Time::default().after_frames(frame, cli.fps);
+        renderman.render(&scene, t);
+        renderman.mtrx.draw(&mut out, frame > 0)?;
+        renderman.mtrx.clear();
+
+        if let Some(wait) = (start + period * (frame + 1)).checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    Ok(())
+}